@@ -3,25 +3,609 @@
  * This assembles low-level assembly code and package up binaries for linking with higher-level code.
  * It is a cargo-compatible super-build script. It reads the target architecture from the TARGET environment
  * variable set by cargo --target, and uses that to determine the tools it needs.
- * It assumes the necessary GNU binutils are present on the host system to assemble code and package binaries.
+ * It assumes the necessary GNU binutils are present on the host system to assemble code. Binaries are packaged
+ * into objects by Mason itself.
  * 
  * Mason is controlled by a TOML-compliant manifest configuration file named mason.toml.
- * It will search up the host file system tree from the current working directory for this file.
+ * It will search up the host file system tree from the current working directory for this file, stopping at
+ * the root of the crate's cargo workspace, as given by cargo metadata, if it can be run.
  * If no configuration file is found, Mason will exit with an error. The file format is:
  * 
- * defaults.include_files = array of binary file pathnames to link with the high-level code.
+ * extends = optional pathname of a base config file, relative to this one, whose settings this file adds to (see below)
+ * defaults.include_files = array of binary file pathnames to link with the high-level code. Each entry may
+ *                          instead be a table with the following keys, giving settings for the file:
+ *     path = pathname of the binary file
+ *     load_address = optional address the binary expects to be copied to before use, eg 0x8020_0000. As a TOML
+ *                    integer, this must be less than 0x8000_0000_0000_0000
+ *     format = optional encoding of the file's contents: "binary" for a raw image, "ihex" for Intel HEX, or "srec"
+ *              for Motorola S-records. Defaults to ihex for files ending .hex, .ihex, or .ihx, srec for those ending
+ *              .srec, .s19, .s28, .s37, or .mot, and binary for the rest. Intel HEX and S-record files are converted
+ *              to the raw image they describe before any transforms, with gaps between records filled with zeroes,
+ *              and the image's base address, ie its lowest, is used as the load_address if none is given
+ *     transforms = optional array of tables, each describing a step in preparing the file's contents before
+ *                  it's packaged, applied in order (see below). Each has a kind key, and others depending on it:
+ *         kind = "strip" to remove symbols and debug info from an ELF file with objcopy
+ *         kind = "objcopy" to convert an ELF file with objcopy, controlling which of its sections are kept.
+ *                output_target = optional object format to write, passed to objcopy -O, eg "binary" for the
+ *                raw image of the loadable sections or "elf64-littleriscv". Defaults to "binary".
+ *                only_sections = optional array of sections to keep, each passed to objcopy -j, eg
+ *                [".text", ".rodata"]. remove_sections = optional array of sections to drop, each passed to
+ *                objcopy -R, eg [".note*", ".comment"]. Section names may use objcopy's wildcards
+ *         kind = "compress" to compress the contents. tool = optional "gzip", "xz", "bzip2", or "zstd".
+ *                Defaults to "gzip". dictionary = true to compress with the zstd dictionary shared by the
+ *                bundle's binaries, which needs tool = "zstd" (see below)
+ *         kind = "sign" to sign the contents. command = array of the program to run and its arguments, in
+ *                which {input} is replaced by the pathname of the contents and {output} by that of the file
+ *                to write the signed contents to
+ *         kind = "pad" to pad the contents. size = optional size in bytes to pad to, align = optional power of
+ *                two to pad the size to a multiple of, and fill = optional byte to pad with. Defaults to 0
+ *     optional = optional boolean. If true and the file doesn't exist, it is left out of the build with a
+ *                warning rather than failing it, and assembly code is built with the symbol
+ *                _binary_<leafname>_absent defined as 1, so that it can test for the file with .ifdef
+ *     placeholder = optional setting for an optional file that's missing: true to package zero-length contents
+ *                   in its place, or the pathname of a stub file to package instead. The usual symbols are
+ *                   then defined for the file, so that code referring to them still links, and can check its
+ *                   _size at run time. Its transforms aren't applied to the placeholder. Defaults to false,
+ *                   leaving the file out
+ *     expect_format = optional kind of file the binary must be, judged by its magic number, to catch the wrong
+ *                     file being embedded: "elf", "dtb", "gzip", "xz", "bzip2", "zstd", "lz4", "cpio",
+ *                     "uimage" for a U-Boot image, or "text" for text starting with a Unicode byte-order mark.
+ *                     The file is checked as given, once converted from Intel HEX or S-records, but before its
+ *                     transforms. The build fails if it's anything else
+ *     embed_symbols = optional boolean. If true, the file must be an ELF, and its symbol table is extracted into
+ *                     a compact blob packaged alongside it (see below). Defaults to false
  * defaults.asm_dirs = array of directory pathnames of assembly source code to build and link with the high-level code.
  * target.<target architecture>.include_files = as for defaults but specific to the given architecture
  * target.<target architecture>.asm_dirs = as for defaults but specific to the given architecture
+ * defaults.object_files = array of prebuilt object file pathnames to add, as they are, to the bundle.
+ * target.<target architecture>.object_files = as for defaults but specific to the given architecture
+ * defaults.lib_files = array of prebuilt static library pathnames whose members are to be added to the bundle.
+ * target.<target architecture>.lib_files = as for defaults but specific to the given architecture
+ * defaults.object_format = BFD name of the object file format to package binary files in, eg elf64-littleriscv
+ * target.<target architecture>.object_format = as for defaults but specific to the given architecture
+ * defaults.ptr_width = pointer width in bits to build for, overriding the target's usual width: 16, 32, or 64
+ * target.<target architecture>.ptr_width = as for defaults but specific to the given architecture
+ * defaults.fp_width = floating-point register width in bits to build for, or 0 for none: 0, 32, 64, or 128
+ * target.<target architecture>.fp_width = as for defaults but specific to the given architecture
+ * defaults.float_abi = "soft" to pass floating-point values in integer registers, or "hard" to use FP registers
+ * target.<target architecture>.float_abi = as for defaults but specific to the given architecture
+ * defaults.allow_empty = false to fail the build if any of the defaults' asm_dirs contain no assembly source files
+ * target.<target architecture>.allow_empty = as for defaults but applied to the given architecture's asm_dirs
+ * defaults.gnu_prefix = prefix of the binutils to use, eg riscv64-unknown-elf for riscv64-unknown-elf-as, in place of
+ *                      the target's usual prefixes. An empty prefix selects the host's unprefixed tools, eg as
+ * target.<target architecture>.gnu_prefix = as for defaults but specific to the given architecture
+ * target.<target architecture>.aliases = array of other target triples that use this target's settings, eg
+ *                                       riscv64imac-unknown-none-elf in the riscv64gc-unknown-none-elf section
+ * defaults.ar_flags = array of extra options to pass to every ar command, before its operation, eg --plugin
+ * target.<target architecture>.ar_flags = as for defaults but specific to the given architecture
+ * defaults.ld_r_flags = no longer used, as binaries are packaged without ld, and ignored with a warning
+ * target.<target architecture>.ld_r_flags = as for defaults but specific to the given architecture
+ * defaults.objcopy_flags = array of extra options to pass to every objcopy command
+ * target.<target architecture>.objcopy_flags = as for defaults but specific to the given architecture
+ * defaults.libgcc = true to add the members of the target's libgcc.a to the bundle, or an array of the names of
+ *                   the members to add, eg ["_udivdi3.o"]. Defaults to false (see below)
+ * target.<target architecture>.libgcc = as for defaults but specific to the given architecture
+ * defaults.libgcc_flags = array of options to pass to gcc when asking it where libgcc.a is, eg to select the
+ *                         multilib matching the target, such as ["-march=rv64imac", "-mabi=lp64"]
+ * target.<target architecture>.libgcc_flags = as for defaults but specific to the given architecture
+ * defaults.min_version = oldest version of binutils the build supports, eg "2.38". Mason runs the assembler with
+ *                        --version before building and fails if it's older. Only checked by the gnu backend
+ * target.<target architecture>.min_version = as for defaults but specific to the given architecture
+ * defaults.max_version = newest version of binutils the build supports, eg "2.41", which admits every 2.41.x
+ *                        release. Mason fails if the assembler is newer. Only checked by the gnu backend
+ * target.<target architecture>.max_version = as for defaults but specific to the given architecture
+ *
+ * auto_link_script = true to pass Mason's generated linker script fragment to the linker automatically (see below)
+ * symbol_namespace = string to use in place of 'binary' in the symbols generated for binary files (see below)
+ * tool_timeout = number of seconds an external tool may run for before it is killed. By default there is no limit
+ * tool_retries = number of times to rerun an external tool that was killed after timing out. Defaults to 0
+ * retry.read = number of times to retry reading an input file if it fails with a possibly transient error
+ * retry.write = as for read, but for writing files to OUT_DIR
+ * retry.spawn = as for read, but for starting an external tool
+ * retry.backoff_ms = milliseconds to wait before the first retry, doubling with each further retry. Defaults to 100
+ * sandbox = true to run external tools in a restricted environment (see below)
+ * sandbox_env = array of names of environment variables to pass through to sandboxed tools, in addition to PATH
+ * audit = true to report files read by external tools that aren't declared in the config file (see below)
+ * symbol_conflicts = array of tables, each with the following keys, describing what to do when more than one
+ *                    object in the bundle defines the same global symbol:
+ *     pattern = regular expression matching the whole of the conflicting symbol's name
+ *     policy = "error" to fail the build, "first" to keep only the first definition, or
+ *              "rename" to rename the symbol in every object after the first by prepending the given prefix
+ *     prefix = string to prepend to renamed symbols, required for the "rename" policy
+ * arch_files = array of pathnames of architecture definition files describing targets (see below)
+ * load_address_symbols = true to define a _load_address symbol for each binary file given a load_address
+ * sniff_payloads = true to report what each packaged binary file's contents look like, eg a gzip'd file or an ELF
+ *                  executable, from their magic number, once cargo isn't quiet. Defaults to false
+ * provenance = true to write a provenance statement describing how the bundle was built (see below)
+ * report = true to write a JSON report of the files the build processed, with timings, to OUT_DIR (see below)
+ * zstd_dictionary_size = largest size in bytes of the shared zstd dictionary to train. Defaults to zstd's own limit
+ * bundle_doc = "markdown" or "json" to write an inventory of the binary files packaged, to OUT_DIR (see below)
+ * verbose = true to print the command line of every external tool Mason runs as a cargo warning (see below)
+ * object_name = template for the names of the objects Mason generates in OUT_DIR, without the .o (see below)
+ * source_map = true to assemble with debug info and map the symbols in assembly code to their source lines (see below)
+ * debug_compression = "zlib" to compress the debug info assembled when source_map is true, the default, "zstd" to
+ *                     compress it with zstd, or "none" to leave it uncompressed
+ * build_id = true to embed an identifier of the build's inputs in the bundle as _mason_build_id (see below)
+ * requires_env = array of environment variables the build needs, checked before building so that every unset or
+ *                empty one is reported at once. Each is given by its name, or as a table with keys:
+ *     name = name of the variable, eg DIOSIX_GUEST_IMAGE
+ *     description = optional description of what the variable is for, shown if it's missing
+ * builtin_ar = true to write the bundle's static archive with Mason's own archiver rather than ar (see below)
+ * backend = "gnu" to build with the target's GNU binutils, the default, "llvm" to use LLVM's tools, or "rustup" to use
+ *           those shipped with the Rust toolchain (see below)
+ * shared_dir = pathname of a directory in which crates using the same config file and target share their
+ *              assembled objects, rather than each assembling them again (see below)
+ * cache_dir = pathname of a persistent directory to cache assembled objects in, in place of OUT_DIR, so that they
+ *             can be reused by other crates and after cargo clean (see below). MASON_CACHE_DIR overrides this
+ * assemblers = table mapping source file extensions, without the leading ., to the assembler to use (see below):
+ *              "gas" for the target's GNU assembler, "nasm" for the Netwide Assembler, or "cpp+gas" to run the C
+ *              preprocessor over the source first. Defaults to { s = "gas" }
+ * asm_lint = table with the following keys describing a tool to check assembly source with before assembling it:
+ *     command = array of the program to run on each source file and its arguments, in which {source} is
+ *               replaced by the file's path, eg ["asmfmt", "--check", "{source}"]
+ *     strict = optional true to fail the build if any file fails the check, rather than warn. Defaults to false
+ * asm_constants = table mapping symbol names to integer values to define for assembly code in mason.inc (see below)
+ * stages = array of tables, each with the following keys, describing a stage of a bootloader chain (see below):
+ *     name = name of the stage, using only letters, digits and underscores
+ *     asm_dirs = optional array of directory pathnames of assembly source code to build into the stage
+ *     include_files = optional array of binary file pathnames to embed in the stage
+ *     embeds = optional array of names of other stages to embed in this one
+ *     link_script = optional pathname of the linker script to link the stage with
+ * guests = table of tables, one per guest virtual machine, named after the guest, each with the following keys,
+ *          describing a guest for a hypervisor to create at boot (see below):
+ *     image = pathname of the guest's binary image file
+ *     ram = bytes of memory to give the guest
+ *     vcpus = number of virtual CPU cores to give the guest
+ *     boot_args = optional string of arguments to pass to the guest when it boots
+ * initrd = table with the following keys describing a ramdisk to build and package (see below):
+ *     files = table mapping each path within the ramdisk to the pathname of the file to place there, or to
+ *             a table with the keys source, the file's pathname, and mode, its permissions, eg 0o755
+ *     format = optional string naming the ramdisk's format. Only "newc", the default, is supported
+ * payload_groups = array of tables, each with the following keys, describing binary files that must be placed
+ *                  together, one after another, in the given order (see below):
+ *     name = name of the group, using only letters, digits and underscores
+ *     files = array of binary file pathnames in the group, in the order they are to be placed
+ *     align = optional alignment in bytes of the start of the group, a power of two. Defaults to 8
  *
  * All pathnames are relative to the current working directory. All the entries in the config file are optional.
  * The arrays also stack, meaning that if you define, eg, default and per-target asm_dirs entries, they will be
  * combined into one array and processed together. Mason ensures a path is included only once: multiple entries
- * of the same file path will be treated as one.
+ * of the same file path will be treated as one. allow_empty defaults to true, meaning an existing but empty
+ * asm directory is silently skipped. If a directory is listed by more than one entry, it is allowed to be empty
+ * only if every entry listing it allows this. Prebuilt object files must be ELF objects matching the target's
+ * machine type and pointer width, and their leafnames must not clash with any object generated by Mason.
+ * The members of static libraries are checked in the same way, though if a member's name is already taken
+ * in the bundle, it is renamed <library>-<member>, or <library>-<n>-<member>, to keep it unique.
+ * object_format defaults to the target's usual format, so that ld doesn't guess the host's format instead.
+ * A target's object_format, if given, overrides that in defaults. The same goes for ptr_width and fp_width,
+ * which are for unusual configurations, eg a soft-float build for a target with an FPU, and change the ptrwidth
+ * and fpwidth symbols defined for assembly code, the width of each binary's _hash symbol, and the ELF class
+ * prebuilt objects must have. They do not change the options passed to the assembler. float_abi does: for
+ * riscv64gc, soft selects the lp64 ABI and hard selects lp64d. If float_abi isn't given, Mason matches the ABI
+ * the Rust code is compiled with, using hard if cargo's CARGO_CFG_TARGET_FEATURE includes the f or d features,
+ * and soft otherwise. A target's float_abi and gnu_prefix, if given, override those in defaults. A target's
+ * ar_flags and objcopy_flags are added after those in defaults, rather than replacing them. They
+ * are escape hatches for unusual toolchains, and are passed on as they are.
+ *
+ * Each assembly source file is assembled into OUT_DIR/<stem>.o, eg start.o for asm/start.s, and each binary file
+ * is packaged into OUT_DIR/<leafname>.o, eg fw.bin.o. If object_name is set, eg to "{dir}_{stem}", objects are
+ * named after it instead: {dir} is replaced by the input file's directory, with each run of characters other than
+ * letters and digits replaced by _, eg asm_boot for asm/boot, {stem} by its leafname without its extension, {ext}
+ * by its extension, and {name} by its whole leafname. The template may otherwise only contain letters, digits, _,
+ * -, and . Two inputs whose objects would have the same name are an error, rather than one silently replacing
+ * the other, so set object_name to tell apart eg asm/start.s and boot/start.s. object_name doesn't rename a
+ * binary's section or symbols, which are always named after its leafname, so two binaries with the same leafname,
+ * eg blobs/fw.bin and other/fw.bin, are an error whatever object_name is set to.
+ * As objects are named after their inputs, a path in include_files, object_files, or lib_files with no leafname,
+ * eg blobs/.., is an error. Files in asm_dirs whose names aren't UTF-8 are skipped, with a warning if they have
+ * the extension of a source file.
+ *
+ * Objects are considered in the order: packaged binaries, assembled code, prebuilt objects, then library members.
+ * A global symbol conflict is handled using the first symbol_conflicts entry whose pattern matches the symbol,
+ * or is an error if no pattern matches. Objects are only checked for conflicts, with nm, if symbol_conflicts is
+ * set or the bundle takes in prebuilt objects or libraries, including libgcc, as symbols from the bundle's own
+ * sources and binaries are otherwise left to the linker. Objects after the first defining the symbol have their definition made
+ * local to themselves for the "first" policy, or have it and their references to it renamed for "rename".
+ * 
+ * A config file that extends another is merged over it: tables, such as defaults, are merged key by key, arrays
+ * are joined with the base file's entries first, and other settings, eg symbol_namespace, are taken from the
+ * extending file if it has them. So a kernel's defaults.asm_dirs adds to those of the base, the arrays then
+ * stacking as usual, while its tool_timeout replaces the base's. A base file can extend another in turn, though
+ * not itself. Pathnames within a base file are still relative to the current working directory. cargo reruns
+ * Mason if the config file, or any file it extends, changes, eg when an entry is added to asm_dirs.
  * 
  * <target architecture> is specified by TARGET, eg: riscv64gc-unknown-none-elf 
+ *
+ * TARGET can also be the path of a custom target spec file, eg my-os.json, as cargo accepts for --target. The
+ * config file then refers to the target by the file's name without .json, eg target.my-os, and Mason builds for
+ * it using the triple in its llvm-target, made to match its target-endian, eg mipsel rather than mips for a
+ * little-endian spec. RISC-V llvm-targets only name the base ISA, eg riscv64, so the extensions enabled in the
+ * spec's features are added to it, eg riscv64gc for +m,+a,+f,+d,+c. The spec's target-pointer-width, if given,
+ * sets the pointer width. A targets entry named after the spec, if present, is used instead (see below).
+ *
+ * Mason learns how to build for a target from architecture definitions. Its built-in definitions are held in
+ * BUILTIN_ARCHS, and more can be given in the files listed by arch_files, which are checked first, in order,
+ * so that they can add or replace targets. Each file is a TOML array of tables named arch, with the keys:
+ *     arch = first part of the target triples covered by this definition, eg riscv64gc
+ *     suffix = optional string the triple must also end with, eg hf to cover armv7a-none-eabihf only
+ *     cpu_arch = CPU to generate code for. {last} is replaced by the last part of the triple,
+ *                eg atmega328 for avr-unknown-gnu-atmega328
+ *     tool_prefixes = array of tool prefixes, eg riscv64-linux-gnu for riscv64-linux-gnu-as, in order of
+ *                     preference. The first whose assembler is found on PATH, or in the host's tool directories,
+ *                     is used, or else the first in the array. An empty prefix selects the host's unprefixed
+ *                     tools, eg as. The prefix chosen is reported as a step of the build. On macOS, the host's
+ *                     tool directories are Homebrew's bin directories, /opt/homebrew/bin and /usr/local/bin, or
+ *                     HOMEBREW_PREFIX/bin if set, so that its cross binutils, eg riscv64-elf-as, are found even
+ *                     if they're not on PATH. Tools only found there are run by their full paths. When the
+ *                     target is the host itself, ie TARGET matches HOST, eg for unit tests or host-side utilities,
+ *                     the host's unprefixed tools are tried after the listed prefixes, so a native build doesn't
+ *                     need a cross-prefixed toolchain. On Windows, the host's tool directories are the bin
+ *                     directories of MSYS2's environments, under MSYS2_ROOT or C:\msys64
+ *     host_tool_prefixes = optional table of tool prefix arrays keyed by host OS, as Rust names it, eg macos,
+ *                          linux or windows. The array for the host, if given, is used in place of tool_prefixes,
+ *                          as package managers name cross binutils differently, eg riscv64-elf on Homebrew
+ *     as_flags = array of assembler options. {cpu_arch} and {abi} are replaced by those settings
+ *     llvm_arch = optional first part of the triple as LLVM names it, eg riscv64 for riscv64gc, if it differs.
+ *                 The rest of the triple follows it in the --target given to clang by the llvm backend
+ *     clang_flags = optional array of assembler options for clang, used by the llvm backend in place of
+ *                   as_flags, which are used if this is omitted. {cpu_arch} and {abi} are replaced as for as_flags
+ *     ld_flags = optional array of linker options, eg -EL to select little-endian objects, passed to ld when it
+ *                links stages
+ *     platform = name of the target's platform, eg riscv
+ *     ptr_width = pointer width in bits
+ *     fp_width = floating-point register width in bits, or 0 for no FPU
+ *     abi = name of the target's ABI, eg lp64d
+ *     soft_float_abi = optional name of the ABI to use if the float ABI is soft, eg lp64
+ *     hard_float_abi = optional name of the ABI to use if the float ABI is hard, eg lp64d
+ *     elf_machine = ELF e_machine value of the target's objects, eg 243 for RISC-V
+ *     bfd_format = BFD name of the target's object file format, eg elf64-littleriscv
+ * The first definition covering the target is used. All keys are required except suffix, llvm_arch, clang_flags,
+ * ld_flags, soft_float_abi, and hard_float_abi. If an architecture has both of the latter, Mason chooses between them as described above.
+ * If no definition covers the triple, Mason uses cargo's description of the target instead: the first definition
+ * covering the architecture named by CARGO_CFG_TARGET_ARCH, adjusted for CARGO_CFG_TARGET_ENDIAN and, for RISC-V,
+ * CARGO_CFG_TARGET_FEATURE as for target spec files, eg riscv64imac for riscv64 with the m, a, and c features.
+ * The pointer width is then taken from CARGO_CFG_TARGET_POINTER_WIDTH.
+ *
+ * A custom or vendor triple can instead be described in the config file by a table named after it in targets,
+ * eg [targets."riscv64gc-acme-elf"], which is used in preference to any architecture definition. Its keys are:
+ *     cpu_arch, abi, ptr_width, and fp_width = as for architecture definitions
+ *     gnu_prefix = tool prefix to use, eg riscv64-acme-elf for riscv64-acme-elf-as, or empty for the host's tools
+ *     as_flags, llvm_arch, clang_flags, ld_flags, platform, elf_machine, and bfd_format = optional, as for
+ *         architecture definitions. If omitted, they are taken from the architecture definition covering the
+ *         triple, if there is one. Otherwise, as_flags and ld_flags default to none, llvm_arch and platform to
+ *         the first part of the triple, clang_flags to as_flags, and elf_machine and bfd_format must be given
+ * float_abi has no effect on these targets: abi is used as it is.
  * Mason also uses the OUT_DIR environment variable, set by cargo, to write its files for linking.
+ * The binutils found this way can be overridden by setting MASON_AS, MASON_AR, MASON_LD, MASON_OBJCOPY, and
+ * MASON_NM to the paths of the executables to run instead, eg for CI and Nix builds. cargo reruns Mason when
+ * any of these change.
+ *
+ * backend selects the tools Mason builds with: "gnu", the default, for the target's GNU binutils, or "llvm" for
+ * hosts where LLVM is installed but cross binutils aren't. The llvm backend assembles with clang's integrated
+ * assembler, as clang -c --target=<triple>, using the target's clang_flags, archives with llvm-ar, links
+ * stages with ld.lld, and uses llvm-objcopy and llvm-nm for transforms and symbols, all unprefixed.
+ * The rustup backend works as the llvm backend does, but with the binutils shipped with the Rust toolchain
+ * building the crate, so that no cross toolchain is needed: rust-lld, through its gcc-ld/ld.lld wrapper, and,
+ * from the llvm-tools component, llvm-ar, llvm-objcopy, and llvm-nm, all found in the toolchain's
+ * lib/rustlib/<host>/bin directory. Tools missing from there are looked for on PATH, as installed by
+ * cargo-binutils, eg rust-objcopy, or else the host's LLVM tools. The toolchain has no assembler, so assembly
+ * code still needs clang.
+ * The MASON_BACKEND environment variable, if set, overrides backend, and MASON_AS, etc, still override the
+ * tools run: MASON_AS then names clang or its equivalent.
+ * Before building anything, Mason checks that each tool the build will run can be found and is executable, and
+ * if any can't, fails naming every missing tool and the package likely to provide it, eg binutils-riscv64-linux-gnu.
+ * Tools are only required if the build uses them: ld only to link stages, for example.
+ *
+ * Each packaged binary file is accessible from the symbols _binary_<leafname>_start, _binary_<leafname>_end,
+ * and _binary_<leafname>_size. The value of the symbol _binary_<leafname>_hash identifies the version of the
+ * binary: it is the first 8 bytes of the file's SHA-256 digest, or the first 4 bytes on 32-bit targets and
+ * 2 bytes on 16-bit targets, read as a big-endian integer. If symbol_namespace is set, eg to "mason", these become _mason_<leafname>_start,
+ * etc, avoiding clashes with other tools that generate _binary_ symbols. It may only contain letters, digits
+ * and underscores.
+ *
+ * Build logic embedding Mason can get the names of the symbols and Rust static generated for a binary file from
+ * symbols_for(), or from Build::symbols_for(), which takes symbol_namespace into account, rather than
+ * reimplementing these naming rules.
+ *
+ * Mason also writes Rust source to OUT_DIR/mason.rs that exposes each packaged binary as a static MasonBlob
+ * named after the binary's leafname, in upper case with non-alphanumeric characters replaced by _, eg FW_BIN
+ * for fw.bin, and _ prepended if it starts with a digit, eg _1_BIN for 1.bin. Two binaries whose statics would
+ * have the same name, eg fw-v1.bin and fw_v1.bin, are an error. The binary's symbols are declared in mason.rs
+ * under names made usable in Rust in the same way, linked with #[link_name], so any leafname works. A MasonBlob dereferences to a [u8] slice of the binary's contents, so code using include_bytes!()
+ * can switch to include!(concat!(env!("OUT_DIR"), "/mason.rs")) and &FW_BIN[..] with few other changes.
+ * Its hash field holds the same value as the binary's _hash symbol, and its load_address field holds the
+ * binary's load_address, if it has one. The file also defines MASON_BLOBS, a table of every packaged binary's
+ * leafname and MasonBlob, so that a loader can find each payload, and where it expects to be copied, without
+ * a separate configuration file. If load_address_symbols is true, each binary with a load_address also gets an
+ * absolute symbol, _binary_<leafname>_load_address, whose value is that address.
+ *
+ * A binary file with transforms has them applied in order, each to the output of the one before, and the
+ * result, written to OUT_DIR/transforms/<leafname>, is packaged in place of the file. Its symbols, section, and
+ * hash describe the transformed contents, but are named after the original file. For example,
+ * transforms = [{ kind = "strip" }, { kind = "compress", tool = "xz" }, { kind = "pad", align = 4096 }] replaces
+ * a script that strips, compresses, and pads a kernel before it's packaged.
+ *
+ * Mason hashes binary files, pads them, and packages them into objects a chunk at a time, so its own memory use
+ * doesn't grow with the size of a binary. The compress transform, though, holds the compressed contents in memory
+ * before writing them out, and builtin_ar reads every object in the bundle into memory, and builds the archive
+ * there, so it needs about twice the bundle's size in free memory. ELF32 objects, used for 32-bit and 16-bit
+ * targets, can't hold binaries of 4 GiB or more, so packaging one is an error.
+ * Each packaged object has a .stamp file beside it recording the binary's size, modification time, and hash, and
+ * how it was packaged. When cargo reruns the build script, a binary that hasn't changed, and is to be packaged the
+ * same way, keeps its object rather than being read and packaged again.
+ *
+ * Each guest's image is packaged as if it were listed by defaults.include_files, and mason.rs also defines
+ * MASON_GUESTS, a table of MasonGuest structures, one per guest in order of name, each holding the guest's
+ * name, a reference to its image's MasonBlob, and its ram, vcpus and boot_args settings. A hypervisor can walk
+ * this catalog at boot to create its guests. Guest names may only contain letters, digits and underscores.
+ *
+ * A binary file with embed_symbols set, eg a guest's image also listed in include_files as a table, has the
+ * function and data symbols it defines extracted into OUT_DIR/symtabs/<leafname>.symtab before any transforms,
+ * such as strip, are applied. The blob is packaged as if it were listed by defaults.include_files, exporting
+ * _binary_<leafname>_symtab_start, etc, so a kernel or hypervisor can symbolize backtraces without the binary's
+ * debug info. The blob is in the target's byte order and starts with a 16-byte header: the magic MSYM, a 16-bit
+ * version, 1, a 16-bit entry size, 16, a 32-bit count of entries, and the 32-bit size of the string table that
+ * follows the entries. Each entry holds the symbol's 64-bit address, its 32-bit size, and the 32-bit offset of
+ * its NUL-terminated name in the string table. Entries are sorted by address, so they can be binary searched.
+ *
+ * If initrd is given, Mason builds a newc-format cpio archive, as unpacked by Linux at boot, in
+ * OUT_DIR/initrd.cpio, and packages it as if it were listed by defaults.include_files, exporting
+ * _binary_initrd_cpio_start, etc. The ramdisk's files are added in order of path, each after its parent
+ * directories, and are owned by root. Files default to mode 0o644, so executables, such as init, need a
+ * mode. Timestamps are zeroed, so the archive only changes when its files do. Leading and repeated slashes are
+ * dropped from paths, so two paths that name the same file once they're gone, eg /bin/sh and bin/sh, are an
+ * error, as is a file at a path that's also one of another file's parent directories, eg bin and bin/sh.
+ *
+ * If any binary file's compress transform sets dictionary to true, Mason trains a zstd dictionary, with zstd
+ * --train, on the contents of every such binary, before they're transformed, cutting them into 128KiB blocks so
+ * that a few large files still give zstd enough samples. Intel HEX and S-record files are sampled as the images
+ * they describe, and missing optional files aren't sampled. Each binary is then compressed with zstd -D using the
+ * dictionary, so similar binaries, eg several guest kernels, compress better together than on their own. The
+ * dictionary is written to OUT_DIR/mason_zstd.dict and packaged once, as if it were listed by
+ * defaults.include_files, exporting _binary_mason_zstd_dict_start, etc, and MASON_ZSTD_DICT in mason.rs, for the
+ * runtime decompressor to load before decompressing any of them.
+ *
+ * Each packaged binary file is placed in its own read-only section, .rodata.mason.<leafname>, and Mason
+ * writes a linker script fragment, mason.ld, to OUT_DIR describing these sections with KEEP() and PROVIDE()
+ * entries. OUT_DIR is added to the linker's search path, so a custom linker script can simply use
+ * INCLUDE mason.ld within its SECTIONS block, before any generic .rodata input section rule.
+ * If auto_link_script is true, Mason instead passes a wrapper script to the linker with -T that inserts
+ * the fragment after .rodata, for crates that do not use their own linker script.
+ *
+ * The binary files in each of payload_groups are packaged as if they were listed by defaults.include_files,
+ * but rather than going into the fragment's .mason output section, they are placed in an output section of
+ * their own, .mason.<name>, in the order given, with no other sections between them. The start and end of the
+ * group are marked by PROVIDE()d symbols __mason_<name>_start and __mason_<name>_end. A file may be in only
+ * one group. This suits payloads that are loaded as one contiguous image, eg firmware, then kernel, then ramdisk.
  * 
+ * Mason lists every object in the archive, and the input it came from, in OUT_DIR/mason-objects.txt,
+ * one object per line with its path and origin separated by a tab. Library members' origins are given as
+ * <library>(<member>). The MASON_OBJECTS environment variable is set to this file's path when compiling
+ * the consuming crate.
+ *
+ * If source_map is true, assembly source is assembled with debug info, so that tools such as addr2line can
+ * attribute addresses in it to source lines, and Mason maps the symbols each source file defines to the lines
+ * covering them, for panic and backtrace decoders to use, eg to attribute an early-boot crash to .s source
+ * lines without the debug info. The map is written to OUT_DIR/mason-source-map.json, as a JSON array of
+ * objects with keys: symbol, object, source, first_line, and last_line. A symbol's lines run from the line
+ * labelling it to the line before the next symbol's label in the same file, or to the end of the file.
+ * The MASON_SOURCE_MAP environment variable is set to the map's path when compiling the consuming crate.
+ * The debug info is compressed as it's assembled, using debug_compression, so that debug-enabled bundles don't
+ * bloat the target directory: gas is passed --compress-debug-sections=<compression>, and clang -gz=<compression>.
+ * Archiving and the objcopy steps keep the sections compressed, and linkers and debuggers read them as they are.
+ * nasm can't compress debug info, so its objects' debug info is left as it is.
+ *
+ * If build_id is true, Mason computes an identifier for the build from the SHA-256 digests of every input file it
+ * read, with their paths relative to the project directory, and the target, once all the bundle's other objects
+ * are made. The identifier's 32 bytes are packaged in OUT_DIR/mason-build-id.o, in the .rodata.mason_build_id
+ * section, as _mason_build_id, and the MASON_BUILD_ID environment variable is set to it in hex when compiling the
+ * consuming crate, so that crash reports from hardware can be matched to the exact bundle the code ran with.
+ *
+ * If builtin_ar is true, Mason writes OUT_DIR/libmason-bundle.a itself, in the GNU format with a symbol index,
+ * rather than running ar, so that hosts without the target's binutils can still archive the bundle. Its members
+ * are given zero timestamps, owners, and 0644 permissions, so the same objects always give a byte-identical archive.
+ * ar_flags don't apply to this archiver. ar is still run to extract the members of prebuilt libraries.
+ * Otherwise ar is run in deterministic mode (D), which does the same, on a fresh archive. Either way, objects are
+ * archived in order of their names, so two builds of the same tree give the same libmason-bundle.a.
+ *
+ * By default, no step is retried. Errors that will not go away, such as a missing file or lack of
+ * permission, are never retried. Each retry is reported as a cargo warning, and if a step still fails,
+ * the error reported notes how many attempts were made.
+ *
+ * Mason records how long each assembly source file took to assemble in OUT_DIR/mason-stats.json, and
+ * in later builds assembles the slowest files first, and files it hasn't seen before ahead of those.
+ * These statistics stay on the host: they are only used to schedule the build. Source files are assembled
+ * in parallel, up to NUM_JOBS at once, or one per CPU if cargo didn't set it, and each assembler beyond the
+ * first takes a token from cargo's jobserver, if it passed one on, so that Mason doesn't run more jobs than
+ * cargo -j allows. Objects are added to the bundle in the same order however many jobs run. Each tool audited
+ * with strace logs to a trace file of its own, so audited tools can run at once too.
+ *
+ * Mason also keeps a copy of each object it assembles in OUT_DIR/mason-asm-cache, named after a SHA-256 key
+ * covering the source's path and contents, the target's assembler options, the assemblers used, the generated
+ * include files, the first line of each assembler's --version, and the contents of the asm_dirs' other files,
+ * which are those sources usually .include, and, if source_map is true, the crate's directory, which the debug
+ * info records. A source whose key matches a cached object isn't reassembled: the object is copied into place
+ * instead. Objects no source uses any more are removed from the cache. Files included from outside the asm_dirs
+ * aren't covered by the key, so change the source too, or run cargo clean, after changing one.
+ *
+ * If cache_dir, or the environment variable MASON_CACHE_DIR, names a directory, objects are cached there
+ * instead, where they outlive cargo clean and are shared by every crate using the directory: a source
+ * assembled the same way for the same target by any of them is assembled only once. Objects are renamed into
+ * place once written, so crates can build in parallel. Nothing is removed from a persistent cache: delete the
+ * directory to clear it.
+ *
+ * When sandbox is true, each external tool is run with an environment cleared of all variables except PATH
+ * and those listed in sandbox_env, with TMPDIR set to OUT_DIR, and with a working directory that must be
+ * within the current working directory or OUT_DIR. This stops host settings leaking into the build.
+ *
+ * When audit is true, Mason records the files each external tool reads, using strace if it is available on
+ * the host, or else by checking which of the tool's arguments are files. Any file read that is within the
+ * current working directory, but outside OUT_DIR, and that isn't one of the build's declared inputs, such as
+ * an assembly include file, is reported as a cargo warning. cargo won't rerun Mason when these files change.
+ *
+ * Files in asm_dirs are assembled according to their extension, using assemblers. Files with other extensions
+ * are ignored. gas runs the target's GNU assembler with the target's as_flags and defines the ptrwidth and
+ * fpwidth symbols. nasm runs nasm, outputting elf64 objects for 64-bit targets and elf32 otherwise, with
+ * ptrwidth and fpwidth defined as macros. cpp+gas first runs the host's cpp, with none of its predefined macros,
+ * and __ASSEMBLER__, ptrwidth and fpwidth defined as macros, writing OUT_DIR/<leafname>.i, which is then
+ * assembled with gas. Each source file's leafname, sans extension, must be unique, eg start.s and start.asm
+ * can't be built together, as both would be assembled into start.o. assemblers adds to and overrides the
+ * default table entry by entry: eg { S = "cpp+gas" } assembles both .s and .S files.
+ *
+ * If asm_lint is given, its command is run on each source file in asm_dirs, in order of path, before any are
+ * assembled, from the config file's directory. The command should exit with a failure if the file needs
+ * reformatting or breaks the project's style, as formatters given --check do. Mason then warns that the file
+ * failed the check, passing on what the command printed about it a line at a time, and carries on with the
+ * build. If strict is true, the build instead fails, with a check failure, once every file has been checked.
+ *
+ * OUT_DIR is on each assembler's include path, and Mason writes constants and tables for assembly code to
+ * include files there: asm_constants are written to mason.inc as .set directives, so that assembly code can
+ * .include "mason.inc". Build logic computing values, eg lookup tables, can add its own include files to the
+ * Plan between resolve() and execute() with Plan::asm_include(), giving the file's syntax as an Assembler:
+ * gas and cpp+gas files use .set and .quad, .long or .short directives, and nasm files use %define and dq, dd
+ * or dw, depending on the target's pointer width. Tables are placed in whichever section is current where
+ * the file is included. The plan's JSON also describes these files, under asm_includes.
+ *
+ * Each of stages is assembled, packaged, and linked in its own directory, OUT_DIR/stages/<name>, and converted
+ * to a raw binary, OUT_DIR/stages/<name>.bin, with objcopy -O binary. Stages are built after the stages they
+ * embed, each of which is packaged into it like a binary file, defining _binary_<name>_bin_start, etc. A stage
+ * not embedded by any other is the last of its chain, and its binary is packaged into the bundle as if it was
+ * listed in defaults.include_files. This replaces scripts that build eg stage 3, embed it in stage 2, embed
+ * that in stage 1, and hand the result to cargo. Objects built for stages don't go into the bundle.
+ *
+ * Mason works in two stages. Build::resolve() reads the config file and finds the build's inputs without
+ * running any tools, and returns a Plan listing the binaries to package, the assembly sources in the order
+ * they'll be assembled, prebuilt objects and libraries, the files the build will write, and the symbols it
+ * will generate. Build::execute() then carries out the plan. Neither exits the process if the build fails:
+ * each returns a MasonError describing the failure, which code embedding Mason can handle itself. Code
+ * embedding Mason can also inspect and amend the plan in between, eg to add an object only if a particular
+ * source file was found. If the MASON_PLAN_JSON environment variable is set, Mason writes the plan it resolved
+ * to the file it names, as a JSON object with keys: include_files, sources, object_files, lib_files, stages,
+ * asm_includes, outputs, and symbols. Along with the cargo directives Mason prints, this lets a test harness
+ * running Mason over a fixtures directory, with stub tools on its PATH, snapshot the build's behaviour and check
+ * that refactors don't change it. tests/snapshot/run.sh is such a harness: it builds each project in
+ * tests/snapshot/fixtures with the stub as, ar, and nm in tests/snapshot/stubs, and compares the plan, the cargo
+ * directives, the bundle's objects, the generated mason.rs and mason.ld, and the commands planned and run
+ * against the known-good copies in the fixture's expected directory. Likewise, Build::commands() gives the exact
+ * command lines the plan will run to preprocess and assemble each source file, without running them, and if the
+ * MASON_COMMANDS_JSON environment variable is set, Mason writes them to the file it names, as a JSON array of
+ * objects with keys: step ("preprocess" or "assemble"), input, and argv. Binary files are packaged without
+ * running any tools, so they have no commands. This lets tests check the options generated for each target.
+ *
+ * Build::new() takes the environment variables and working directory it builds with from the process when
+ * it's called, and Build::with_host() takes them from a HostEnv instead, as does Target::with_host(). A build
+ * reads nothing from the process's environment after that: tools are run with the HostEnv's variables and,
+ * unless they need another, in its directory, and relative paths in the config file are resolved against it.
+ * How it logs, ie CARGO_TERM_VERBOSE, CARGO_TERM_QUIET, and MASON_LOG_FORMAT, and where it writes its plan and
+ * commands, ie MASON_PLAN_JSON and MASON_COMMANDS_JSON, are also taken from the HostEnv. So code embedding Mason, eg a test suite or a tool building several targets, can run more than one build
+ * at once in the same process, on different threads, each with its own OUT_DIR, TARGET, and project.
+ *
+ * If provenance is true, Mason writes an in-toto statement of the build's SLSA provenance to
+ * OUT_DIR/mason-provenance.json, for release pipelines to attest how the bundle was produced. Its subjects are
+ * the bundle, mason.rs, and mason.ld, its resolved dependencies are the build's declared inputs, and its builder
+ * dependencies are the tools it can run, each with its path and the first line of its --version output. Every
+ * file is given with its SHA-256 digest.
+ *
+ * If report is true, Mason writes OUT_DIR/mason-report.json, a JSON object with keys: target, seconds (time
+ * taken to carry out the plan), and files, an array with an object for each object in the bundle, with keys:
+ * input (file it came from), step ("assemble", "package", "object", "library", or "generate"), commands (the
+ * command lines that assemble it, as for MASON_COMMANDS_JSON), seconds (time taken to assemble or package it,
+ * or null), cached (true if an earlier build's object was reused), and object (its path). CI can collect the
+ * report to track how long each source file takes to assemble, and what each input produced.
+ *
+ * If bundle_doc is set, Mason writes an inventory of the binary files packaged into the bundle to OUT_DIR: a
+ * Markdown table, mason-bundle.md, if it's "markdown", or a JSON array, mason-bundle.json, if it's "json". Each
+ * binary is given with its path, the symbols defined for it, the name of its MasonBlob in mason.rs, the size
+ * and SHA-256 digest of the contents packaged, its load_address, if any, and its origin: where the contents came
+ * from, eg generated by Mason for a stage, and how they were converted or transformed. The inventory holds
+ * nothing that differs between builds of the same inputs, so it can be committed with each release.
+ *
+ * If libgcc is set, Mason finds the target's libgcc.a by running gcc, with the binutils' prefix, with the
+ * libgcc_flags and -print-libgcc-file-name, and merges the library's members into the bundle as it does for
+ * lib_files, so that assembly code can call the compiler's intrinsics, such as soft-float and division
+ * routines, that the Rust code doesn't provide. If libgcc is an array, only the members named are added. The
+ * gcc run can be overridden by setting MASON_GCC to the path of the executable to run instead.
+ *
+ * If shared_dir is set, crates in a workspace that build the same assembly code for the same target share the
+ * objects assembled, instead of each assembling them again in its own OUT_DIR. Mason fingerprints everything
+ * that affects the objects: the target, its assembler and options, the generated include files, and the
+ * contents of every file in the asm_dirs. The first build with a given fingerprint copies its objects to
+ * <shared_dir>/<fingerprint>, and later ones copy them from there. A relative shared_dir is relative to the
+ * crate's directory, so crates sharing one should give its absolute path, or one relative to each of them.
+ * In shared_dir and cache_dir, {workspace_root} is replaced with the root directory of the crate's cargo
+ * workspace, and {target_dir} with the directory cargo builds it in, eg shared_dir = "{target_dir}/mason",
+ * as given by cargo metadata. The build fails if either is used and cargo metadata can't be run.
+ *
+ * Editors and watch modes can instead call Build::rebuild_file() to rebuild one assembly source file or binary
+ * file into the output of an earlier full build, replacing its object in the bundle, for quick feedback.
+ * Besides the binary's hash in mason.rs, nothing else is regenerated, so a full build is still needed after
+ * adding, removing, or renaming files, or changing the config file. A source file can be in any directory below
+ * one of the asm_dirs, and is an error rather than skipped if it has no leafname an object can be named after.
+ *
+ * The config file is parsed, checked, and merged for the target by parse_manifest(), which works on the file's
+ * text and returns any problem it finds as an error rather than panicking or exiting, so it can be fuzzed.
+ * fuzz/ holds a cargo-fuzz target for it, run with cargo fuzz run parse_manifest, seeded with the config files
+ * in fuzz/corpus/parse_manifest.
+ *
+ * Mason can also be run after the crate is linked, to check the result, by running the build script executable
+ * cargo compiled, eg target/debug/build/<package>-<hash>/build-script-build, with a command and its arguments:
+ *     check-asm = check that every assembly source file the config file describes for TARGET, including those of
+ *                 stages, assembles, discarding the objects and writing nothing to OUT_DIR, which needn't be set.
+ *                 Every failure is reported, not just the first, for lint jobs and pre-commit hooks
+ *     diff-config <target A> <target B> = show which of the config file's settings, once resolved for each
+ *                 target, differ between the two, eg include_files, asm_dirs, and tool options. Settings only
+ *                 target A has are prefixed with - and those only B has with +, colored red and green when
+ *                 printed to a terminal, unless NO_COLOR is set. Either target may be a target spec file
+ *     check-rodata <image> = check that the payload sections in the linked ELF image are read-only data, failing
+ *                            if any of them, or the segments loading them, are writable or executable, or if
+ *                            the image has no payload sections, eg because the linker script fragment was dropped
+ *     size-report <image> [<output directory>] [<baseline>] = report how many bytes of the linked ELF image's
+ *                            allocated sections are taken up by each payload, each other object from the bundle,
+ *                            and everything else, mostly Rust code. <output directory> is the OUT_DIR Mason
+ *                            wrote the bundle to, found as for out-dir if it's left out. The report is also
+ *                            written to mason-size.json in that directory, and if <baseline> names an earlier
+ *                            copy of that file, such as one kept in the crate's repository, the report gives
+ *                            each size's change from it
+ *     out-dir <image> = print the OUT_DIR cargo gave Mason when building the crate the linked image came from.
+ *                            Run from the crate's directory, this runs cargo metadata to find the target
+ *                            directory and the crate's package, and looks for the package's build output
+ *                            directories holding mason-objects.txt, in the build directory of the profile the
+ *                            image was linked in, or else of every profile. If there's more than one, eg for
+ *                            different features, the one written last is given
+ *
+ * Failures are passed back up to Mason's main(), which reports the first one and exits with one of the
+ * following codes:
+ *     1 = the host environment could not be used, eg the current working directory is inaccessible
+ *     2 = configuration error, eg the config file or build environment is missing or invalid
+ *     3 = a required tool, such as the assembler, could not be found
+ *     4 = a tool failed, eg the assembler rejected a source file
+ *     5 = a check, such as check-rodata or check-asm, found a problem
+ * If the MASON_ERROR_JSON environment variable is set, Mason also writes a description of the failure
+ * to the file it names, as a JSON object with keys: class ("host", "config", "missing_tool", "tool_failure",
+ * or "check_failure"), exit_code, and message, and also, where they're known, stage, the step of the build
+ * that failed, eg "assemble", file, the file being processed, command, the failed tool's command line as an
+ * array of its program and arguments, and stderr, the tool's error output if it isn't already in the message.
+ * The failure is printed as its message followed by an indented line for each of these. Before exiting, Mason
+ * passes cargo a warning giving the first line of the failure, so that it's shown as a diagnostic even where
+ * cargo's captured output isn't, and prints a final summary line to stderr, Mason failed: <class> error, exit
+ * code <code>, flushing its output so that none of it is lost.
+ *
+ * Mason reports what it's doing through log_event(). By default, messages are plain text: warnings are passed
+ * to cargo to show the user, and errors are printed to stderr as Mason error: <message>. The steps of the build,
+ * such as each file assembled, are reported only if CARGO_TERM_VERBOSE is true, and the results of commands, such as
+ * size-report, are not reported if CARGO_TERM_QUIET is true. If MASON_LOG_FORMAT is json, each message is
+ * instead printed as a JSON object on a line of its own, with keys: level ("step", "info", "warning", or
+ * "error"), event, a short name for what the message is about, eg "assemble" or "retry", and message.
+ * Anything an assembler, or the C preprocessor, prints while succeeding, eg a warning about a deprecated
+ * directive, is logged as a warning a line at a time, with GNU as messages given the source file's absolute path
+ * and line, so that it isn't lost with the rest of the tool's output.
+ *
+ * If verbose is true, or the environment variable MASON_VERBOSE is set to anything other than 0, which overrides
+ * the config file, Mason passes cargo a warning, Mason: running <command line>, before running each external tool,
+ * eg as, ld, objcopy, or ar, so that the command line is shown even when cargo isn't verbose. Arguments are quoted
+ * as a POSIX shell would need them, so that a failing tool can be rerun by hand, outside of cargo, from the
+ * directory holding the config file, or from the directory given in the warning, where it's another one.
+ *
  * Reminder: this runs on the host build system using the host's architecture.
  * Thus, a Rust toolchain that can build executables for the host arch must be installed, and
  * the host architecture must be the default toolchain target - or this script will fail.
@@ -34,23 +618,495 @@
  */
 
 use std::env;
+use std::ffi::OsString;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::{Command, exit};
+use std::process::{Command, Output, Stdio, exit};
+use std::io;
+use std::io::{ErrorKind, IsTerminal, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
 use std::collections::HashSet;
+use std::collections::HashMap;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::sync::{Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 extern crate toml;
 extern crate serde;
 extern crate serde_derive;
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
+
+extern crate serde_json;
 
 extern crate regex;
 use regex::Regex;
 
+extern crate sha2;
+use sha2::{Digest, Sha256};
+
+extern crate object;
+use object::{AddressSize, Architecture, BinaryFormat, Endianness, SymbolKind};
+use object::elf;
+use object::write;
+use object::read::archive::ArchiveFile;
+use object::read::{Object as _, ObjectSymbol as _};
+
+extern crate ar_archive_writer;
+use ar_archive_writer::{ArchiveKind, NewArchiveMember, DEFAULT_OBJECT_READER, write_archive_to_stream};
+
+extern crate jobserver;
+
+/* environment variable naming the file to write an error description to on failure */
+static ERROR_JSON_VAR: &str = "MASON_ERROR_JSON";
+
+/* environment variable selecting json rather than plain text log messages */
+static LOG_FORMAT_VAR: &str = "MASON_LOG_FORMAT";
+
+/* environment variable that, if set to anything but 0, prints the command line of every tool run */
+static VERBOSE_VAR: &str = "MASON_VERBOSE";
+
+/* environment variable naming the file to write the resolved build plan to */
+static PLAN_JSON_VAR: &str = "MASON_PLAN_JSON";
+
+/* environment variable naming the file to write the commands a plan will run to */
+static COMMANDS_JSON_VAR: &str = "MASON_COMMANDS_JSON";
+
+/* namespace of the symbols generated for binary files, unless the config file gives one */
+static DEFAULT_SYMBOL_NAMESPACE: &str = "binary";
+
 /* configuration file name */
 static CONFIG_FILE: &str = "mason.toml";
 
+/* name of the archive of generated objects the crate is linked with, and its filename */
+static ARCHIVE_NAME: &str = "mason-bundle";
+static ARCHIVE_FILE: &str = "libmason-bundle.a";
+
+/* linker script fragment describing Mason's generated sections, and its -T wrapper */
+static LINK_SCRIPT_FILE: &str = "mason.ld";
+static LINK_SCRIPT_WRAPPER_FILE: &str = "mason-auto.ld";
+
+/* Rust source exposing packaged binaries as statics */
+static RUST_SHIM_FILE: &str = "mason.rs";
+
+/* seconds taken to assemble each file, kept from one build to the next */
+static BUILD_STATS_FILE: &str = "mason-stats.json";
+
+/* list of objects in the archive and their origins */
+static OBJECT_LIST_FILE: &str = "mason-objects.txt";
+
+/* map of symbols defined by assembly source files to the lines defining them */
+static SOURCE_MAP_FILE: &str = "mason-source-map.json";
+
+/* object holding the build's identifier, and its section and symbol */
+static BUILD_ID_OBJECT: &str = "mason-build-id.o";
+static BUILD_ID_SECTION: &str = ".rodata.mason_build_id";
+static BUILD_ID_SYMBOL: &str = "_mason_build_id";
+
+/* in-toto statement of the build's SLSA provenance, and the identifiers of its formats */
+static PROVENANCE_FILE: &str = "mason-provenance.json";
+static PROVENANCE_STATEMENT_TYPE: &str = "https://in-toto.io/Statement/v1";
+static PROVENANCE_PREDICATE_TYPE: &str = "https://slsa.dev/provenance/v1";
+static PROVENANCE_BUILD_TYPE: &str = "https://github.com/diodesign/mason/build/v1";
+static PROVENANCE_BUILDER_ID: &str = "https://github.com/diodesign/mason";
+
+/* machine-readable report of the files a build processed */
+static REPORT_FILE: &str = "mason-report.json";
+
+/* number of tool runs traced with strace so far, so that each can log to a file of its own */
+static TRACED_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+/* inventory of the binary files packaged into the bundle, in each of its formats */
+static BUNDLE_DOC_MARKDOWN_FILE: &str = "mason-bundle.md";
+static BUNDLE_DOC_JSON_FILE: &str = "mason-bundle.json";
+
+/* bytes of a file Mason processes at a time, so that large binaries aren't held in memory */
+static STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/* subdirectory of the output directory that binaries' transformed contents are written to */
+static TRANSFORMS_DIR: &str = "transforms";
+
+/* subdirectory of the output directory that Intel HEX and S-record files' raw images are written to */
+static CONVERTED_DIR: &str = "converted";
+
+/* largest raw image, in bytes, Mason will convert an Intel HEX or S-record file to, to catch stray records */
+static MAX_IMAGE_SPAN: u64 = 256 * 1024 * 1024;
+
+/* subdirectory of the output directory that empty placeholders for missing optional binaries are written to */
+static PLACEHOLDERS_DIR: &str = "placeholders";
+
+/* subdirectory of the output directory that bootloader stages are built in */
+static STAGES_DIR: &str = "stages";
+
+/* assembly include file holding the config file's asm_constants */
+static ASM_CONSTANTS_FILE: &str = "mason.inc";
+
+/* environment variable selecting the backend, overriding the config file, and the tools each backend runs:
+   the assembler, archiver, linker, objcopy, and nm */
+static BACKEND_VAR: &str = "MASON_BACKEND";
+static GNU_TOOLS: [&str; 5] = ["as", "ar", "ld", "objcopy", "nm"];
+static LLVM_TOOLS: [&str; 5] = ["clang", "llvm-ar", "ld.lld", "llvm-objcopy", "llvm-nm"];
+
+/* candidates for each tool the rustup backend runs: first those in the Rust toolchain's bin directory, shipped with
+   rustc or its llvm-tools component, and then those on PATH, from cargo-binutils or else the host's LLVM. rust-lld
+   can only be run as a GNU-style linker through its gcc-ld wrapper, and the toolchain's own rust-objcopy can't
+   find its libraries when run by anything but rustc. the toolchain has no assembler */
+static RUSTUP_TOOLS: [(&[&str], &[&str]); 5] = [
+    (&[], &["clang"]),
+    (&["llvm-ar"], &["rust-ar", "llvm-ar"]),
+    (&["gcc-ld/ld.lld"], &["ld.lld"]),
+    (&["llvm-objcopy"], &["rust-objcopy", "llvm-objcopy"]),
+    (&["llvm-nm"], &["rust-nm", "llvm-nm"])
+];
+
+/* file marking a set of assembled objects in a shared_dir as complete, ready to be used by other builds */
+static SHARED_COMPLETE_FILE: &str = "complete";
+
+/* subdirectory of the output directory that assembled objects are cached in, named after their cache keys,
+   unless a persistent cache directory is given by the config file or the environment variable below */
+static ASM_CACHE_DIR: &str = "mason-asm-cache";
+static CACHE_DIR_VAR: &str = "MASON_CACHE_DIR";
+
+/* default templates for the names of objects generated from assembly source and binary files (see object_name()) */
+static SOURCE_OBJECT_NAME: &str = "{stem}";
+static PAYLOAD_OBJECT_NAME: &str = "{name}";
+
+/* object file the check-asm command discards assembled code to */
+static NULL_OBJECT: &str = "/dev/null";
+
+/* executables of the assemblers that aren't part of the target's binutils */
+static NASM_EXEC: &str = "nasm";
+static CPP_EXEC: &str = "cpp";
+
+/* initrd built from the initrd section of the config file */
+static INITRD_FILE: &str = "initrd.cpio";
+
+/* initrd archive format supported */
+static INITRD_FORMAT: &str = "newc";
+
+/* zstd dictionary trained on the binary files that are compressed with it, and packaged once for them all.
+   Files are cut into blocks of the given size for training, so that a few large binaries still give
+   zstd enough samples to learn from */
+static ZSTD_DICTIONARY_FILE: &str = "mason_zstd.dict";
+static ZSTD_TRAINING_BLOCK_SIZE: usize = 128 * 1024;
+
+/* subdirectory of the output directory that symbol tables extracted from ELF binaries are written to */
+static SYMTABS_DIR: &str = "symtabs";
+
+/* version of the format of extracted symbol tables, given in their headers after the magic MSYM */
+static SYMTAB_VERSION: u16 = 1;
+
+/* sizes of the parts of a linked image, written by the size-report command */
+static SIZE_REPORT_FILE: &str = "mason-size.json";
+
+/* built-in architecture definitions, in the same format as the files listed by arch_files.
+   elf_machine is the ELF e_machine value of objects built for the architecture */
+static BUILTIN_ARCHS: &str = r#"
+[[arch]]
+arch = "riscv64imac"
+cpu_arch = "rv64imac"
+llvm_arch = "riscv64"
+tool_prefixes = ["riscv64-unknown-elf", "riscv64-elf", "riscv64-linux-gnu"]
+host_tool_prefixes = { macos = ["riscv64-elf", "riscv64-unknown-elf"], windows = ["riscv64-unknown-elf", "riscv64-elf"] }
+as_flags = ["-march", "{cpu_arch}", "-mabi", "{abi}"]
+clang_flags = ["-march={cpu_arch}", "-mabi={abi}"]
+platform = "riscv"
+ptr_width = 64
+fp_width = 0
+abi = "lp64"
+soft_float_abi = "lp64"
+elf_machine = 243
+bfd_format = "elf64-littleriscv"
+
+[[arch]]
+arch = "riscv64gc"
+cpu_arch = "rv64gc"
+llvm_arch = "riscv64"
+tool_prefixes = ["riscv64-unknown-elf", "riscv64-elf", "riscv64-linux-gnu"]
+host_tool_prefixes = { macos = ["riscv64-elf", "riscv64-unknown-elf"], windows = ["riscv64-unknown-elf", "riscv64-elf"] }
+as_flags = ["-march", "{cpu_arch}", "-mabi", "{abi}"]
+clang_flags = ["-march={cpu_arch}", "-mabi={abi}"]
+platform = "riscv"
+ptr_width = 64
+fp_width = 64
+abi = "lp64d"
+soft_float_abi = "lp64"
+hard_float_abi = "lp64d"
+elf_machine = 243
+bfd_format = "elf64-littleriscv"
+
+[[arch]]
+arch = "riscv32imac"
+cpu_arch = "rv32imac"
+llvm_arch = "riscv32"
+tool_prefixes = ["riscv32-unknown-elf", "riscv64-unknown-elf", "riscv64-elf", "riscv64-linux-gnu"]
+host_tool_prefixes = { macos = ["riscv64-elf", "riscv32-unknown-elf", "riscv64-unknown-elf"], windows = ["riscv32-unknown-elf", "riscv64-unknown-elf", "riscv64-elf"] }
+as_flags = ["-march", "{cpu_arch}", "-mabi", "{abi}"]
+clang_flags = ["-march={cpu_arch}", "-mabi={abi}"]
+platform = "riscv"
+ptr_width = 32
+fp_width = 0
+abi = "ilp32"
+elf_machine = 243
+bfd_format = "elf32-littleriscv"
+
+[[arch]]
+arch = "riscv32imc"
+cpu_arch = "rv32imc"
+llvm_arch = "riscv32"
+tool_prefixes = ["riscv32-unknown-elf", "riscv64-unknown-elf", "riscv64-elf", "riscv64-linux-gnu"]
+host_tool_prefixes = { macos = ["riscv64-elf", "riscv32-unknown-elf", "riscv64-unknown-elf"], windows = ["riscv32-unknown-elf", "riscv64-unknown-elf", "riscv64-elf"] }
+as_flags = ["-march", "{cpu_arch}", "-mabi", "{abi}"]
+clang_flags = ["-march={cpu_arch}", "-mabi={abi}"]
+platform = "riscv"
+ptr_width = 32
+fp_width = 0
+abi = "ilp32"
+elf_machine = 243
+bfd_format = "elf32-littleriscv"
+
+[[arch]]
+arch = "riscv32i"
+cpu_arch = "rv32i"
+llvm_arch = "riscv32"
+tool_prefixes = ["riscv32-unknown-elf", "riscv64-unknown-elf", "riscv64-elf", "riscv64-linux-gnu"]
+host_tool_prefixes = { macos = ["riscv64-elf", "riscv32-unknown-elf", "riscv64-unknown-elf"], windows = ["riscv32-unknown-elf", "riscv64-unknown-elf", "riscv64-elf"] }
+as_flags = ["-march", "{cpu_arch}", "-mabi", "{abi}"]
+clang_flags = ["-march={cpu_arch}", "-mabi={abi}"]
+platform = "riscv"
+ptr_width = 32
+fp_width = 0
+abi = "ilp32"
+elf_machine = 243
+bfd_format = "elf32-littleriscv"
+
+[[arch]]
+arch = "x86_64"
+cpu_arch = "x86-64"
+tool_prefixes = ["x86_64-linux-gnu", "x86_64-elf", ""]
+host_tool_prefixes = { macos = ["x86_64-elf"], windows = ["x86_64-elf"] }
+as_flags = ["--64"]
+clang_flags = []
+platform = "x86_64"
+ptr_width = 64
+fp_width = 0
+abi = "sysv"
+elf_machine = 62
+bfd_format = "elf64-x86-64"
+
+[[arch]]
+arch = "powerpc64le"
+cpu_arch = "power8"
+tool_prefixes = ["powerpc64le-linux-gnu", "powerpc64-linux-gnu"]
+as_flags = ["-a64", "-m{cpu_arch}", "-mlittle"]
+clang_flags = ["-mcpu={cpu_arch}"]
+ld_flags = ["-EL"]
+platform = "powerpc"
+ptr_width = 64
+fp_width = 64
+abi = "elfv2"
+elf_machine = 21
+bfd_format = "elf64-powerpcle"
+
+[[arch]]
+arch = "powerpc64"
+cpu_arch = "ppc64"
+tool_prefixes = ["powerpc64-linux-gnu", "powerpc64le-linux-gnu"]
+as_flags = ["-a64", "-m{cpu_arch}", "-mbig"]
+clang_flags = ["-mcpu={cpu_arch}"]
+ld_flags = ["-EB"]
+platform = "powerpc"
+ptr_width = 64
+fp_width = 64
+abi = "elfv1"
+elf_machine = 21
+bfd_format = "elf64-powerpc"
+
+[[arch]]
+arch = "powerpc"
+cpu_arch = "ppc"
+tool_prefixes = ["powerpc-linux-gnu", "powerpc64-linux-gnu"]
+as_flags = ["-a32", "-m{cpu_arch}", "-mbig"]
+clang_flags = ["-mcpu={cpu_arch}"]
+ld_flags = ["-EB"]
+platform = "powerpc"
+ptr_width = 32
+fp_width = 64
+abi = "sysv"
+elf_machine = 20
+bfd_format = "elf32-powerpc"
+
+[[arch]]
+arch = "mips"
+cpu_arch = "mips32r2"
+tool_prefixes = ["mips-linux-gnu", "mipsel-linux-gnu"]
+as_flags = ["-march={cpu_arch}", "-mabi=32", "-EB"]
+ld_flags = ["-EB"]
+platform = "mips"
+ptr_width = 32
+fp_width = 32
+abi = "o32"
+elf_machine = 8
+bfd_format = "elf32-tradbigmips"
+
+[[arch]]
+arch = "mipsel"
+cpu_arch = "mips32r2"
+tool_prefixes = ["mipsel-linux-gnu", "mips-linux-gnu"]
+as_flags = ["-march={cpu_arch}", "-mabi=32", "-EL"]
+ld_flags = ["-EL"]
+platform = "mips"
+ptr_width = 32
+fp_width = 32
+abi = "o32"
+elf_machine = 8
+bfd_format = "elf32-tradlittlemips"
+
+[[arch]]
+arch = "mips64"
+cpu_arch = "mips64r2"
+tool_prefixes = ["mips64-linux-gnuabi64", "mips-linux-gnu"]
+as_flags = ["-march={cpu_arch}", "-mabi=64", "-EB"]
+ld_flags = ["-EB"]
+platform = "mips"
+ptr_width = 64
+fp_width = 64
+abi = "n64"
+elf_machine = 8
+bfd_format = "elf64-tradbigmips"
+
+[[arch]]
+arch = "loongarch64"
+cpu_arch = "la64"
+tool_prefixes = ["loongarch64-linux-gnu"]
+as_flags = ["-mabi={abi}"]
+platform = "loongarch"
+ptr_width = 64
+fp_width = 64
+abi = "lp64d"
+soft_float_abi = "lp64s"
+hard_float_abi = "lp64d"
+elf_machine = 258
+bfd_format = "elf64-loongarch"
+
+[[arch]]
+arch = "aarch64"
+suffix = "softfloat"
+cpu_arch = "armv8-a"
+tool_prefixes = ["aarch64-none-elf", "aarch64-elf", "aarch64-linux-gnu"]
+host_tool_prefixes = { macos = ["aarch64-elf", "aarch64-none-elf"], windows = ["aarch64-none-elf"] }
+as_flags = ["-march={cpu_arch}+nofp+nosimd", "-mabi={abi}"]
+clang_flags = ["-march={cpu_arch}+nofp+nosimd"]
+platform = "aarch64"
+ptr_width = 64
+fp_width = 0
+abi = "lp64"
+elf_machine = 183
+bfd_format = "elf64-littleaarch64"
+
+[[arch]]
+arch = "aarch64"
+cpu_arch = "armv8-a"
+tool_prefixes = ["aarch64-none-elf", "aarch64-elf", "aarch64-linux-gnu"]
+host_tool_prefixes = { macos = ["aarch64-elf", "aarch64-none-elf"], windows = ["aarch64-none-elf"] }
+as_flags = ["-march={cpu_arch}", "-mabi={abi}"]
+clang_flags = ["-march={cpu_arch}"]
+platform = "aarch64"
+ptr_width = 64
+fp_width = 128
+abi = "lp64"
+elf_machine = 183
+bfd_format = "elf64-littleaarch64"
+
+[[arch]]
+arch = "armv7a"
+suffix = "hf"
+cpu_arch = "armv7-a"
+tool_prefixes = ["arm-none-eabi"]
+as_flags = ["-march={cpu_arch}", "-mfpu=neon-vfpv3", "-mfloat-abi=hard"]
+platform = "arm"
+ptr_width = 32
+fp_width = 64
+abi = "eabihf"
+elf_machine = 40
+bfd_format = "elf32-littlearm"
+
+[[arch]]
+arch = "armv7a"
+cpu_arch = "armv7-a"
+tool_prefixes = ["arm-none-eabi"]
+as_flags = ["-march={cpu_arch}", "-mfloat-abi=soft"]
+platform = "arm"
+ptr_width = 32
+fp_width = 0
+abi = "eabi"
+elf_machine = 40
+bfd_format = "elf32-littlearm"
+
+[[arch]]
+arch = "thumbv6m"
+cpu_arch = "cortex-m0"
+tool_prefixes = ["arm-none-eabi"]
+as_flags = ["-mthumb", "-mcpu={cpu_arch}", "-mfloat-abi=soft"]
+platform = "arm"
+ptr_width = 32
+fp_width = 0
+abi = "eabi"
+elf_machine = 40
+bfd_format = "elf32-littlearm"
+
+[[arch]]
+arch = "thumbv7em"
+suffix = "hf"
+cpu_arch = "cortex-m4"
+tool_prefixes = ["arm-none-eabi"]
+as_flags = ["-mthumb", "-mcpu={cpu_arch}", "-mfpu=fpv4-sp-d16", "-mfloat-abi=hard"]
+platform = "arm"
+ptr_width = 32
+fp_width = 32
+abi = "eabihf"
+elf_machine = 40
+bfd_format = "elf32-littlearm"
+
+[[arch]]
+arch = "thumbv7em"
+cpu_arch = "cortex-m4"
+tool_prefixes = ["arm-none-eabi"]
+as_flags = ["-mthumb", "-mcpu={cpu_arch}", "-mfloat-abi=soft"]
+platform = "arm"
+ptr_width = 32
+fp_width = 0
+abi = "eabi"
+elf_machine = 40
+bfd_format = "elf32-littlearm"
+
+[[arch]]
+arch = "avr"
+cpu_arch = "{last}"
+tool_prefixes = ["avr"]
+as_flags = ["-mmcu={cpu_arch}"]
+platform = "avr"
+ptr_width = 16
+fp_width = 0
+abi = "avr"
+elf_machine = 83
+bfd_format = "elf32-avr"
+
+[[arch]]
+arch = "msp430"
+cpu_arch = "430"
+tool_prefixes = ["msp430-elf"]
+as_flags = ["-mcpu={cpu_arch}"]
+clang_flags = []
+platform = "msp430"
+ptr_width = 16
+fp_width = 0
+abi = "msp430"
+elf_machine = 105
+bfd_format = "elf32-msp430"
+"#;
+
 /* max attempts to search the host file system for a config file */
 static SEARCH_MAX: usize = 100;
 
@@ -59,393 +1115,7895 @@ static SEARCH_MAX: usize = 100;
 struct Config
 {
     defaults: Option<ConfigEntry>,
-    target: Option<BTreeMap<String, ConfigEntry>>
+    target: Option<BTreeMap<String, ConfigEntry>>,
+    auto_link_script: Option<bool>,
+    sandbox: Option<bool>,
+    symbol_namespace: Option<String>,
+    tool_timeout: Option<u64>,
+    tool_retries: Option<usize>,
+    retry: Option<RetryConfig>,
+    audit: Option<bool>,
+    sandbox_env: Option<Vec<String>>,
+    symbol_conflicts: Option<Vec<SymbolConflict>>,
+    arch_files: Option<Vec<String>>,
+    payload_groups: Option<Vec<PayloadGroup>>,
+    stages: Option<Vec<Stage>>,
+    guests: Option<BTreeMap<String, Guest>>,
+    initrd: Option<Initrd>,
+    assemblers: Option<BTreeMap<String, Assembler>>,
+    asm_constants: Option<BTreeMap<String, i64>>,
+    load_address_symbols: Option<bool>,
+    targets: Option<BTreeMap<String, TargetDef>>,
+    provenance: Option<bool>,
+    report: Option<bool>,
+    verbose: Option<bool>,
+    object_name: Option<String>,
+    shared_dir: Option<String>,
+    cache_dir: Option<String>,
+    backend: Option<Backend>,
+    source_map: Option<bool>,
+    debug_compression: Option<DebugCompression>,
+    build_id: Option<bool>,
+    builtin_ar: Option<bool>,
+    requires_env: Option<Vec<RequiredEnv>>,
+    sniff_payloads: Option<bool>,
+    bundle_doc: Option<BundleDoc>,
+    zstd_dictionary_size: Option<u64>,
+    asm_lint: Option<AsmLint>
 }
 
+/* an environment variable the build needs, given by its name or with a description */
 #[derive(Deserialize, Debug)]
-struct ConfigEntry
+#[serde(untagged)]
+enum RequiredEnv
+{
+    Name(String),                                        /* name of the variable */
+    Described { name: String, description: Option<String> } /* name of the variable, and what it's for */
+}
+
+impl RequiredEnv
+{
+    fn name(&self) -> &str
+    {
+        match self
+        {
+            RequiredEnv::Name(name) => name,
+            RequiredEnv::Described { name, .. } => name
+        }
+    }
+}
+
+/* select how debug info assembled for a source map is compressed */
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum DebugCompression
+{
+    None, /* leave debug sections uncompressed */
+    Zlib, /* compress debug sections with zlib, as most linkers and debuggers can read */
+    Zstd  /* compress debug sections with zstd, which is smaller but needs newer tools */
+}
+
+impl DebugCompression
+{
+    /* give the name tools use for this compression, eg in --compress-debug-sections=zlib */
+    fn name(&self) -> &'static str
+    {
+        match self
+        {
+            DebugCompression::None => "none",
+            DebugCompression::Zlib => "zlib",
+            DebugCompression::Zstd => "zstd"
+        }
+    }
+}
+
+/* select the format of the inventory of packaged binary files written to OUT_DIR */
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum BundleDoc
+{
+    Markdown, /* a table, for people to read, eg in release notes */
+    Json      /* an array of objects, for tools */
+}
+
+impl BundleDoc
+{
+    /* give the name of the file the inventory is written to in this format */
+    fn file(&self) -> &'static str
+    {
+        match self
+        {
+            BundleDoc::Markdown => BUNDLE_DOC_MARKDOWN_FILE,
+            BundleDoc::Json => BUNDLE_DOC_JSON_FILE
+        }
+    }
+}
+
+/* select the family of tools used to assemble, archive, and package files */
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Backend
+{
+    Gnu,   /* the target's GNU binutils */
+    Llvm,  /* clang's integrated assembler and LLVM's binutils */
+    Rustup /* as for llvm, but with the binutils shipped with the Rust toolchain */
+}
+
+/* describe how to build for one target triple, in place of the architecture definitions */
+#[derive(Deserialize)]
+pub struct TargetDef
+{
+    cpu_arch: String,
+    gnu_prefix: String,
+    abi: String,
+    ptr_width: usize,
+    fp_width: usize,
+    as_flags: Option<Vec<String>>, /* these are taken from the architecture definition covering the triple, if omitted */
+    llvm_arch: Option<String>,
+    clang_flags: Option<Vec<String>>,
+    ld_flags: Option<Vec<String>>,
+    platform: Option<String>,
+    elf_machine: Option<u16>,
+    bfd_format: Option<String>
+}
+
+/* describe a stage of a bootloader chain, linked into a raw binary that can be embedded in other stages */
+#[derive(Deserialize)]
+struct Stage
 {
+    name: String,
+    asm_dirs: Option<Vec<String>>,
     include_files: Option<Vec<String>>,
-    asm_dirs: Option<Vec<String>>
+    embeds: Option<Vec<String>>,
+    link_script: Option<String>
 }
 
-/* describe a build target from its user-supplied triple */
-struct Target
+/* describe a guest virtual machine for a hypervisor to create at boot */
+#[derive(Deserialize)]
+struct Guest
 {
-    pub cpu_arch: String,    /* define the CPU architecture to generate code for */
-    pub gnu_prefix: String,  /* locate the GNU as and ar tools */ 
-    pub platform: String,    /* locate the tail of the platform directory in src, eg riscv for src/platform-riscv */
-    pub ptr_width: usize,    /* pointer width in bits */
-    pub fp_width: usize,     /* floating-point register width in bits (or 0 for no FPU) */
-    pub abi: String          /* define the ABI for this target */
+    image: String,
+    ram: u64,
+    vcpus: u32,
+    boot_args: Option<String>
+}
+
+/* describe a formatter or linter to check assembly source with before it's assembled */
+#[derive(Deserialize)]
+struct AsmLint
+{
+    command: Vec<String>, /* program to run on each source file and its arguments, in which {source} is replaced by its path */
+    strict: Option<bool>  /* true to fail the build if any source file fails the check, rather than warn */
+}
+
+/* describe a ramdisk to build from files on the build system */
+#[derive(Deserialize)]
+struct Initrd
+{
+    files: BTreeMap<String, InitrdFile>,
+    format: Option<String>
+}
+
+/* a file to place in the ramdisk: either just the path of its source, or a table including its permissions */
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum InitrdFile
+{
+    Path(String),
+    Entry { source: String, mode: Option<u32> }
+}
+
+/* describe binary files to be placed together in order */
+#[derive(Deserialize)]
+struct PayloadGroup
+{
+    name: String,
+    files: Vec<String>,
+    align: Option<u64>
+}
+
+/* define the structure of an architecture definition file */
+#[derive(Deserialize)]
+struct ArchFile
+{
+    arch: Vec<ArchDef>
+}
+
+/* describe an architecture, and how to build for the target triples it covers */
+#[derive(Deserialize)]
+struct ArchDef
+{
+    arch: String,               /* first part of the target triples this covers, eg riscv64gc */
+    suffix: Option<String>,     /* if present, the triple must also end with this, eg hf */
+    cpu_arch: String,           /* CPU to generate code for. {last} is replaced by the triple's last part */
+    tool_prefixes: Vec<String>, /* candidate tool prefixes, in order of preference */
+    host_tool_prefixes: Option<BTreeMap<String, Vec<String>>>, /* candidates to use instead on given host OSes */
+    as_flags: Vec<String>,      /* assembler options. {cpu_arch} and {abi} are replaced by those settings */
+    llvm_arch: Option<String>,  /* first part of the triple as LLVM names it, if it differs, eg riscv64 */
+    clang_flags: Option<Vec<String>>, /* assembler options for clang, if they differ from as_flags */
+    ld_flags: Option<Vec<String>>, /* linker options, eg to select the target's endianness */
+    platform: String,
+    ptr_width: usize,
+    fp_width: usize,
+    abi: String,
+    soft_float_abi: Option<String>, /* ABI to use if float_abi is soft, if the architecture has one */
+    hard_float_abi: Option<String>, /* ABI to use if float_abi is hard, if the architecture has one */
+    elf_machine: u16,
+    bfd_format: String
+}
+
+/* describe how many times to retry steps that fail with possibly transient errors */
+#[derive(Deserialize)]
+struct RetryConfig
+{
+    read: Option<usize>,
+    write: Option<usize>,
+    spawn: Option<usize>,
+    backoff_ms: Option<u64>
+}
+
+/* describe how to handle global symbols defined more than once in the bundle */
+#[derive(Deserialize)]
+struct SymbolConflict
+{
+    pattern: String,
+    policy: ConflictPolicy,
+    prefix: Option<String>
+}
+
+/* select how source files with a given extension are assembled */
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum Assembler
+{
+    #[serde(rename = "gas")]
+    Gas,    /* the target's GNU assembler */
+    #[serde(rename = "nasm")]
+    Nasm,   /* the Netwide Assembler */
+    #[serde(rename = "cpp+gas")]
+    CppGas  /* the C preprocessor, then the target's GNU assembler */
+}
+
+/* select whether floating-point arguments are passed in integer or FP registers */
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FloatAbi
+{
+    Soft, /* integer registers, eg lp64 */
+    Hard  /* FP registers, eg lp64d */
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ConflictPolicy
+{
+    Error,  /* fail the build */
+    First,  /* keep the first definition, make later ones local */
+    Rename  /* keep the first definition, rename later ones with a prefix */
+}
+
+#[derive(Deserialize, Debug)]
+struct ConfigEntry
+{
+    include_files: Option<Vec<IncludeFile>>,
+    asm_dirs: Option<Vec<String>>,
+    object_files: Option<Vec<String>>,
+    lib_files: Option<Vec<String>>,
+    allow_empty: Option<bool>,
+    object_format: Option<String>,
+    ptr_width: Option<usize>,
+    fp_width: Option<usize>,
+    float_abi: Option<FloatAbi>,
+    gnu_prefix: Option<String>,
+    ar_flags: Option<Vec<String>>,
+    ld_r_flags: Option<Vec<String>>,
+    objcopy_flags: Option<Vec<String>>,
+    libgcc: Option<Libgcc>,
+    libgcc_flags: Option<Vec<String>>,
+    min_version: Option<String>,
+    max_version: Option<String>,
+    aliases: Option<Vec<String>>
+}
+
+/* which members of the target's libgcc.a to add to the bundle */
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum Libgcc
+{
+    All(bool),           /* true to add every member, or false to add none */
+    Members(Vec<String>) /* names of the members to add, eg _udivdi3.o */
+}
+
+/* a binary file to package up, given either as its pathname or as a table of settings */
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum IncludeFile
+{
+    Path(String),
+    Entry(IncludeEntry)
+}
+
+/* settings for a binary file to package up */
+#[derive(Deserialize, Debug, Clone)]
+pub struct IncludeEntry
+{
+    path: String,
+    load_address: Option<u64>,          /* address the binary expects to be copied to before use */
+    format: Option<ImageFormat>,        /* how the binary's contents are encoded, if not as its extension implies */
+    transforms: Option<Vec<Transform>>, /* steps to prepare the binary's contents before packaging, in order */
+    optional: Option<bool>,             /* true to skip the binary, rather than fail, if it's missing */
+    placeholder: Option<Placeholder>,   /* what to package instead if the binary is optional and missing */
+    expect_format: Option<PayloadFormat>, /* kind of file the binary must be, if checked */
+    embed_symbols: Option<bool>         /* true to package the binary's symbol table alongside it */
+}
+
+/* contents to package in place of an optional binary file that's missing */
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Placeholder
+{
+    Empty(bool),  /* true to package zero-length contents, or false to leave the binary out */
+    Stub(String)  /* pathname of a file to package instead */
+}
+
+/* how a binary file's contents are encoded */
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat
+{
+    Binary, /* the raw image, packaged as it is */
+    Ihex,   /* Intel HEX records, converted to the raw image they describe */
+    Srec    /* Motorola S-records, converted to the raw image they describe */
+}
+
+impl ImageFormat
+{
+    pub fn name(&self) -> &'static str
+    {
+        match self
+        {
+            ImageFormat::Binary => "binary",
+            ImageFormat::Ihex => "Intel HEX",
+            ImageFormat::Srec => "S-record"
+        }
+    }
+}
+
+/* kinds of file a binary's contents can be recognised as by their magic numbers */
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadFormat
+{
+    Elf,    /* ELF object, executable, or shared object */
+    Dtb,    /* flattened device tree blob */
+    Gzip,   /* gzip'd file */
+    Xz,     /* xz compressed file */
+    Bzip2,  /* bzip2 compressed file */
+    Zstd,   /* zstd compressed file */
+    Lz4,    /* lz4 compressed file, in its frame format */
+    Cpio,   /* cpio archive in the newc or odc format, eg an initrd */
+    Uimage, /* U-Boot legacy image */
+    Text    /* text starting with a Unicode byte-order mark */
+}
+
+impl PayloadFormat
+{
+    pub fn name(&self) -> &'static str
+    {
+        match self
+        {
+            PayloadFormat::Elf => "an ELF file",
+            PayloadFormat::Dtb => "a device tree blob",
+            PayloadFormat::Gzip => "a gzip'd file",
+            PayloadFormat::Xz => "an xz compressed file",
+            PayloadFormat::Bzip2 => "a bzip2 compressed file",
+            PayloadFormat::Zstd => "a zstd compressed file",
+            PayloadFormat::Lz4 => "an lz4 compressed file",
+            PayloadFormat::Cpio => "a cpio archive",
+            PayloadFormat::Uimage => "a U-Boot image",
+            PayloadFormat::Text => "text with a byte-order mark"
+        }
+    }
+}
+
+/* a step in preparing a binary file's contents for packaging */
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Transform
+{
+    Strip,                                                          /* remove symbols and debug info from an ELF file */
+    Objcopy { output_target: Option<String>, only_sections: Option<Vec<String>>, remove_sections: Option<Vec<String>> }, /* convert an ELF file */
+    Compress { tool: Option<String>, dictionary: Option<bool> },    /* compress with gzip, xz, bzip2, or zstd, optionally with the shared dictionary */
+    Sign { command: Vec<String> },                                  /* run a command to sign the contents */
+    Pad { align: Option<u64>, size: Option<u64>, fill: Option<u8> } /* pad the contents with fill bytes */
+}
+
+impl Transform
+{
+    /* describe the step, eg compress (xz), for people reading about how a binary was prepared */
+    pub fn describe(&self) -> String
+    {
+        match self
+        {
+            Transform::Strip => String::from("strip"),
+            Transform::Objcopy { output_target, .. } => format!("objcopy ({})", output_target.as_deref().unwrap_or("binary")),
+            Transform::Compress { tool, dictionary: Some(true) } => format!("compress ({}, with {})", tool.as_deref().unwrap_or("gzip"), ZSTD_DICTIONARY_FILE),
+            Transform::Compress { tool, .. } => format!("compress ({})", tool.as_deref().unwrap_or("gzip")),
+            Transform::Sign { .. } => String::from("sign"),
+            Transform::Pad { .. } => String::from("pad")
+        }
+    }
+}
+
+/* files gathered from the config file to process for this build */
+struct Inputs
+{
+    include_files: HashSet<String>,  /* binary files to package up */
+    include_settings: HashMap<String, IncludeEntry>, /* settings for binary files given as tables */
+    asm_dirs: HashMap<String, bool>, /* directories of assembly source, mapped to whether they may be empty */
+    object_files: HashSet<String>,   /* prebuilt objects to add to the archive as they are */
+    lib_files: HashSet<String>,      /* prebuilt static libraries to merge into the archive */
+    stages: Vec<String>              /* bootloader stages to build, in dependency order */
+}
+
+/* a config file's settings for one target, with the defaults merged in */
+pub struct Manifest
+{
+    config: Config,                /* config file as parsed */
+    inputs: Inputs,                /* files to process, from the defaults and the target's entry */
+    object_format: Option<String>, /* BFD object format to package binaries in, if overridden */
+    ptr_width: Option<usize>,      /* pointer width in bits, if overridden */
+    fp_width: Option<usize>,       /* floating-point register width in bits, if overridden */
+    float_abi: Option<FloatAbi>,   /* floating-point ABI, if selected */
+    gnu_prefix: Option<String>,    /* prefix of the binutils to use, if overridden */
+    tool_flags: ToolFlags,         /* extra options for binutils, from the defaults and the target's entry */
+    ld_r_flags: bool,              /* true if ld_r_flags, which is no longer used, is set */
+    libgcc: Option<Libgcc>,        /* members of libgcc.a to add to the bundle, if selected */
+    libgcc_flags: Vec<String>,     /* options for gcc when locating libgcc.a */
+    binutils_versions: VersionRange, /* versions of binutils the build supports */
+    config_files: Vec<String>      /* paths of the config file and any it extends, or empty if not read from a file */
+}
+
+/* oldest and newest versions of a tool the build supports, each as its numbered parts, eg [2, 38] for 2.38 */
+#[derive(Clone, Default)]
+pub struct VersionRange
+{
+    min: Option<Vec<u32>>, /* oldest version, or None for no limit */
+    max: Option<Vec<u32>>  /* newest version, compared only as far as it's given, or None for no limit */
+}
+
+/* extra options passed to binutils, for toolchains that need them */
+struct ToolFlags
+{
+    ar: Vec<String>,     /* passed to every ar command, before its operation */
+    objcopy: Vec<String> /* passed to every objcopy command */
+}
+
+/* Find the tools the rustup backend runs, preferring those shipped with the Rust toolchain building the crate,
+   found in <sysroot>/lib/rustlib/<host>/bin, over cargo-binutils' on PATH
+   => host = environment the build runs in
+   <= returns the assembler, archiver, linker, objcopy, and nm to run, in that order */
+fn rustup_tools(host: &HostEnv) -> Vec<String>
+{
+    let rustc = host.var("RUSTC").unwrap_or("rustc").to_string();
+    let query = |args: &[&str]| match host_command(&mut Command::new(&rustc), host).args(args).output()
+    {
+        Ok(output) if output.status.success() == true => Some(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        _ => None
+    };
+
+    let triple = match host.var("HOST")
+    {
+        Some(triple) => Some(triple.to_string()),
+        None => query(&["-vV"]).and_then(|v| v.lines().find_map(|l| l.strip_prefix("host: ").map(|h| h.to_string())))
+    };
+
+    let bin_dir = match (query(&["--print", "sysroot"]), triple)
+    {
+        (Some(sysroot), Some(triple)) => Some(Path::new(&sysroot).join("lib").join("rustlib").join(triple).join("bin")),
+        _ => None
+    };
+
+    RUSTUP_TOOLS.iter().map(|(shipped, installed)|
+    {
+        match bin_dir.as_ref().and_then(|dir| shipped.iter().map(|n| dir.join(n)).find(|p| p.is_file() == true))
+        {
+            Some(path) => path.to_string_lossy().into_owned(),
+            None => installed.iter().find(|n| find_in_path(n, host).is_some() == true).unwrap_or(&installed[0]).to_string()
+        }
+    })
+    .inspect(|tool| log_event(host, LogLevel::Step, "toolchain", format!("using {}", tool)))
+    .collect()
+}
+
+/* Locate the target's libgcc.a by asking gcc, with the target's tool prefix, where it is
+   => flags = options to pass to gcc, eg to select a multilib
+      context = build context
+   <= returns path to libgcc.a */
+fn find_libgcc(flags: &Vec<String>, context: &Context) -> Result<String, MasonError>
+{
+    let gcc = tool_exec("MASON_GCC", &context.target.gnu_prefix, "gcc", &context.host);
+    let result = run_tool(Command::new(&gcc)
+        .args(flags)
+        .arg("-print-libgcc-file-name"),
+        format!("Failed to execute {} to locate libgcc.a", &gcc), context)?;
+
+    let path = String::from(String::from_utf8_lossy(&result.stdout).trim());
+    if result.status.success() != true || Path::new(&path).is_absolute() == false || Path::new(&path).exists() == false
+    {
+        return Err(MasonError::tool_failure(format!("Can't locate libgcc.a with {}: it reported '{}'\n{}",
+            &gcc, &path, String::from_utf8_lossy(&result.stderr))));
+    }
+
+    log_event(&context.host, LogLevel::Step, "libgcc", format!("using {}", &path));
+    Ok(path)
+}
+
+/* find the executable to run for a binutils tool: the one named by the given environment variable, if
+   it's set, so that CI and Nix builds can use exact tool binaries, or else the tool with the given prefix,
+   found on PATH or in the host's tool directories. cargo is told to rerun the build if the variable changes
+   => var = name of environment variable overriding the tool, eg MASON_AS
+      prefix = tool prefix, eg riscv64-linux-gnu
+      tool = name of tool, eg as
+      host = environment the build runs in
+   <= returns the tool's executable, eg /nix/store/...-binutils/bin/as or riscv64-linux-gnu-as */
+fn tool_exec(var: &str, prefix: &str, tool: &str, host: &HostEnv) -> String
+{
+    println!("cargo:rerun-if-env-changed={}", var);
+    match host.var(var)
+    {
+        Some(path) if path.len() > 0 => path.to_string(),
+        _ => locate_tool(&prefixed_tool(prefix, tool), host)
+    }
+}
+
+/* name a binutils tool with the given prefix, or with no prefix if it's empty, eg the host's own as
+   => prefix = tool prefix, eg riscv64-linux-gnu
+      tool = name of tool, eg as
+   <= returns the tool's executable name, eg riscv64-linux-gnu-as */
+fn prefixed_tool(prefix: &str, tool: &str) -> String
+{
+    match prefix.len()
+    {
+        0 => String::from(tool),
+        _ => format!("{}-{}", prefix, tool)
+    }
+}
+
+/* describe a build target from its user-supplied triple */
+pub struct Target
+{
+    pub cpu_arch: String,    /* define the CPU architecture to generate code for */
+    pub gnu_prefix: String,  /* locate the GNU as and ar tools, eg riscv64-linux-gnu for riscv64-linux-gnu-as */
+    pub as_flags: Vec<String>, /* assembler options selecting the target's instruction set, FPU, and ABI */
+    pub llvm_target: String, /* target triple as LLVM names it, for clang --target */
+    pub clang_flags: Vec<String>, /* as for as_flags, but for clang's integrated assembler */
+    pub ld_flags: Vec<String>, /* linker options selecting the target's object format, eg its endianness */
+    pub platform: String,    /* locate the tail of the platform directory in src, eg riscv for src/platform-riscv */
+    pub ptr_width: usize,    /* pointer width in bits */
+    pub fp_width: usize,     /* floating-point register width in bits (or 0 for no FPU) */
+    pub abi: String,         /* define the ABI for this target */
+    pub elf_machine: u16,    /* ELF e_machine value of objects built for this target */
+    pub bfd_format: String,  /* BFD name of the target's object file format */
+    pub spec_file: Option<String> /* custom target spec file this target was derived from, if any */
+}
+
+impl Target
+{
+    /* create a target object from a full build triple string, using the config file's entry for the
+       triple in targets if it has one, or else the first architecture definition that covers it.
+       user-supplied definitions are checked before the built-in ones
+       => triple = target triple string
+          arch_files = paths of architecture definition files from the config file, if any
+          targets = target entries from the config file, by triple, if any
+          float_abi = floating-point ABI selected by the config file, if any
+       <= returns the target described by the triple */
+    pub fn new(triple: &String, arch_files: &Option<Vec<String>>, targets: &Option<BTreeMap<String, TargetDef>>, float_abi: Option<FloatAbi>) -> Result<Target, MasonError>
+    {
+        Target::with_host(triple, arch_files, targets, float_abi, &HostEnv::from_process()?)
+    }
+
+    /* create a target object as new() does, but taking cargo's description of the target, and the tools
+       available, from the given environment rather than the process's
+       => triple, arch_files, targets, float_abi = as for new()
+          host = environment the build runs in
+       <= returns the target described by the triple */
+    pub fn with_host(triple: &String, arch_files: &Option<Vec<String>>, targets: &Option<BTreeMap<String, TargetDef>>, float_abi: Option<FloatAbi>, host: &HostEnv) -> Result<Target, MasonError>
+    {
+        let mut defs = Vec::new();
+        if let Some(arch_files) = arch_files
+        {
+            for path in arch_files
+            {
+                let contents = match fs::read_to_string(host.path(path))
+                {
+                    Ok(c) => c,
+                    Err(e) => return Err(MasonError::config(format!("Can't read architecture definition file {}: {}", path, e)))
+                };
+
+                let file: ArchFile = match toml::from_str(contents.as_str())
+                {
+                    Ok(f) => f,
+                    Err(e) => return Err(MasonError::config(format!("Can't parse architecture definition file {}: {}", path, e)))
+                };
+                defs.extend(file.arch);
+            }
+        }
+
+        let builtin: ArchFile = toml::from_str(BUILTIN_ARCHS).unwrap();
+        defs.extend(builtin.arch);
+
+        let arch = triple.split('-').next().unwrap();
+        let covers = |d: &ArchDef, arch: &str| d.arch == arch && triple.ends_with(d.suffix.as_deref().unwrap_or("")) == true;
+        let def = defs.iter().position(|d| covers(d, arch)).map(|i| defs.remove(i));
+
+        if let Some(custom) = targets.as_ref().and_then(|t| t.get(triple))
+        {
+            return Target::from_entry(triple, custom, def);
+        }
+
+        /* for a triple no definition covers, fall back to the one matching the architecture, endianness,
+        and features cargo describes the target with, so that new triples for known architectures work */
+        let mut ptr_width = None;
+        let def = match def
+        {
+            Some(d) => d,
+            None => match host.var("CARGO_CFG_TARGET_ARCH")
+            {
+                Some(cfg_arch) =>
+                {
+                    let endian = host.var("CARGO_CFG_TARGET_ENDIAN");
+                    let features: Vec<&str> = host.var("CARGO_CFG_TARGET_FEATURE").unwrap_or("").split(',').collect();
+                    let derived = triple_arch(cfg_arch, endian, &features);
+
+                    match defs.iter().position(|d| covers(d, &derived)).map(|i| defs.remove(i))
+                    {
+                        Some(d) =>
+                        {
+                            log_event(host, LogLevel::Info, "target", format!("No definition covers target '{}', using {} from cargo's description of it", triple, &derived));
+                            ptr_width = host.var("CARGO_CFG_TARGET_POINTER_WIDTH").and_then(|w| w.parse().ok());
+                            d
+                        },
+                        None => return Err(MasonError::config(format!("Unsupported target '{}', whose architecture cargo describes as {}", &arch, &derived)))
+                    }
+                },
+                None => return Err(MasonError::config(format!("Unsupported target '{}'", &arch)))
+            }
+        };
+
+        /* if the config file doesn't choose a float ABI, match the one the Rust code is being
+        compiled with, judging by whether its target features include FP registers, so that
+        objects from both sides can be linked together */
+        let float_abi = match float_abi
+        {
+            Some(f) => Some(f),
+            None => match (&def.soft_float_abi, &def.hard_float_abi, host.var("CARGO_CFG_TARGET_FEATURE"))
+            {
+                (Some(_), Some(_), Some(features)) => match features.split(',').any(|f| f == "d" || f == "f")
+                {
+                    true => Some(FloatAbi::Hard),
+                    false => Some(FloatAbi::Soft)
+                },
+                _ => None
+            }
+        };
+
+        let abi = match float_abi
+        {
+            Some(FloatAbi::Soft) => match &def.soft_float_abi
+            {
+                Some(abi) => abi.clone(),
+                None => return Err(MasonError::config(format!("Target '{}' has no soft-float ABI", triple)))
+            },
+            Some(FloatAbi::Hard) => match &def.hard_float_abi
+            {
+                Some(abi) => abi.clone(),
+                None => return Err(MasonError::config(format!("Target '{}' has no hard-float ABI", triple)))
+            },
+            None => def.abi.clone()
+        };
+
+        let cpu_arch = def.cpu_arch.replace("{last}", triple.rsplit('-').next().unwrap());
+        let as_flags = def.as_flags.iter().map(|f| f.replace("{cpu_arch}", &cpu_arch).replace("{abi}", &abi)).collect();
+        let clang_flags = def.clang_flags.as_ref().unwrap_or(&def.as_flags).iter().map(|f| f.replace("{cpu_arch}", &cpu_arch).replace("{abi}", &abi)).collect();
+
+        /* use the first tool prefix whose assembler can be found, or else the
+        first prefix, so that a missing tool is reported under its preferred name.
+        when building for the host itself, eg for unit tests or a host-side utility,
+        its unprefixed tools will do if no cross toolchain is installed */
+        let prefixes = def.host_tool_prefixes.as_ref().and_then(|p| p.get(env::consts::OS)).unwrap_or(&def.tool_prefixes);
+        let mut candidates = prefixes.clone();
+        if host.var("HOST") == Some(triple.as_str()) && candidates.iter().any(|p| p.len() == 0) == false
+        {
+            candidates.push(String::new());
+        }
+
+        let gnu_prefix = match candidates.iter().find(|p| find_in_path(&prefixed_tool(p, "as"), host).is_some() == true)
+        {
+            Some(p) =>
+            {
+                log_event(host, LogLevel::Step, "toolchain", format!("using {} for target '{}'", prefixed_tool(p, "as"), triple));
+                p.clone()
+            },
+            None => match prefixes.first()
+            {
+                Some(p) =>
+                {
+                    let tried: Vec<String> = candidates.iter().map(|p| prefixed_tool(p, "as")).collect();
+                    log_event(host, LogLevel::Step, "toolchain", format!("none of {} found on PATH or in the host's tool directories for target '{}'", tried.join(", "), triple));
+                    p.clone()
+                },
+                None => return Err(MasonError::config(format!("Architecture definition for '{}' has no tool_prefixes", &def.arch)))
+            }
+        };
+
+        Ok(Target
+        {
+            cpu_arch: cpu_arch,
+            gnu_prefix: gnu_prefix,
+            as_flags: as_flags,
+            llvm_target: llvm_triple(triple, def.llvm_arch.as_deref()),
+            clang_flags: clang_flags,
+            ld_flags: def.ld_flags.unwrap_or(Vec::new()),
+            platform: def.platform,
+            ptr_width: ptr_width.unwrap_or(def.ptr_width),
+            fp_width: def.fp_width,
+            abi: abi,
+            elf_machine: def.elf_machine,
+            bfd_format: def.bfd_format,
+            spec_file: None
+        })
+    }
+
+    /* create a target object from the config file's entry for its triple. settings the entry
+       leaves out are taken from the architecture definition covering the triple, if there is one
+       => triple = target triple string
+          entry = config file's entry for the triple
+          def = first architecture definition covering the triple, if any
+       <= returns the target described by the entry */
+    fn from_entry(triple: &String, entry: &TargetDef, def: Option<ArchDef>) -> Result<Target, MasonError>
+    {
+        let as_flags = match (&entry.as_flags, &def)
+        {
+            (Some(flags), _) => flags.clone(),
+            (None, Some(def)) => def.as_flags.clone(),
+            (None, None) => Vec::new()
+        };
+
+        let clang_flags = match (&entry.clang_flags, &def)
+        {
+            (Some(flags), _) => flags.clone(),
+            (None, Some(def)) if entry.as_flags.is_none() == true => def.clang_flags.clone().unwrap_or(def.as_flags.clone()),
+            (None, _) => as_flags.clone()
+        };
+
+        let llvm_arch = entry.llvm_arch.clone().or(def.as_ref().and_then(|d| d.llvm_arch.clone()));
+
+        let elf_machine = match (entry.elf_machine, &def)
+        {
+            (Some(machine), _) => machine,
+            (None, Some(def)) => def.elf_machine,
+            (None, None) => return Err(MasonError::config(format!("Target '{}' needs an elf_machine, as no architecture definition covers it", triple)))
+        };
+
+        let bfd_format = match (&entry.bfd_format, &def)
+        {
+            (Some(format), _) => format.clone(),
+            (None, Some(def)) => def.bfd_format.clone(),
+            (None, None) => return Err(MasonError::config(format!("Target '{}' needs a bfd_format, as no architecture definition covers it", triple)))
+        };
+
+        let platform = match (&entry.platform, &def)
+        {
+            (Some(platform), _) => platform.clone(),
+            (None, Some(def)) => def.platform.clone(),
+            (None, None) => triple.split('-').next().unwrap().to_string()
+        };
+
+        Ok(Target
+        {
+            cpu_arch: entry.cpu_arch.clone(),
+            gnu_prefix: entry.gnu_prefix.clone(),
+            as_flags: as_flags.iter().map(|f| f.replace("{cpu_arch}", &entry.cpu_arch).replace("{abi}", &entry.abi)).collect(),
+            llvm_target: llvm_triple(triple, llvm_arch.as_deref()),
+            clang_flags: clang_flags.iter().map(|f| f.replace("{cpu_arch}", &entry.cpu_arch).replace("{abi}", &entry.abi)).collect(),
+            ld_flags: entry.ld_flags.clone().or(def.and_then(|d| d.ld_flags)).unwrap_or(Vec::new()),
+            platform: platform,
+            ptr_width: entry.ptr_width,
+            fp_width: entry.fp_width,
+            abi: entry.abi.clone(),
+            elf_machine: elf_machine,
+            bfd_format: bfd_format,
+            spec_file: None
+        })
+    }
+}
+
+/* name a target triple as LLVM does, by replacing its first part with LLVM's name for the architecture
+   => triple = target triple string, eg riscv64gc-unknown-none-elf
+      llvm_arch = LLVM's name for the triple's first part, if it differs, eg riscv64
+   <= returns the triple to pass to clang --target, eg riscv64-unknown-none-elf */
+fn llvm_triple(triple: &str, llvm_arch: Option<&str>) -> String
+{
+    match (llvm_arch, triple.split_once('-'))
+    {
+        (Some(arch), Some((_, rest))) => format!("{}-{}", arch, rest),
+        (Some(arch), None) => arch.to_string(),
+        (None, _) => triple.to_string()
+    }
+}
+
+/* look for an executable in the directories listed by PATH, and then in the host's tool directories
+   => name = leafname of executable
+      host = environment the build runs in
+   <= returns the executable's path, or None if it can't be found */
+fn find_in_path(name: &str, host: &HostEnv) -> Option<PathBuf>
+{
+    let mut dirs: Vec<PathBuf> = match host.var("PATH")
+    {
+        Some(p) => env::split_paths(p).collect(),
+        None => Vec::new()
+    };
+    dirs.extend(host_tool_dirs(host));
+
+    dirs.iter().find_map(|dir| tool_in_dir(dir, name))
+}
+
+/* look for a tool's executable in a directory, allowing for the host's executable suffix, eg .exe on Windows
+   => dir = directory to look in
+      name = tool's executable name, eg riscv64-elf-as
+   <= returns the executable's path, or None if it's not there */
+fn tool_in_dir(dir: &Path, name: &str) -> Option<PathBuf>
+{
+    let path = dir.join(name);
+    if path.is_file() == true
+    {
+        return Some(path);
+    }
+
+    let suffixed = dir.join(format!("{}{}", name, env::consts::EXE_SUFFIX));
+    match env::consts::EXE_SUFFIX.is_empty() == false && suffixed.is_file() == true
+    {
+        true => Some(suffixed),
+        false => None
+    }
+}
+
+/* give the directories the host's package manager installs cross binutils in, which may not be on PATH,
+   eg when cargo is run from an IDE. On macOS, Homebrew installs them in /opt/homebrew/bin on Apple silicon
+   and /usr/local/bin on Intel Macs, or in the bin directory of HOMEBREW_PREFIX, if it's set. On Windows,
+   MSYS2 installs them in the bin directories of its environments, under MSYS2_ROOT if set, or C:\msys64
+   => host = environment the build runs in
+   <= returns the directories, in order of preference */
+fn host_tool_dirs(host: &HostEnv) -> Vec<PathBuf>
+{
+    let mut dirs = Vec::new();
+    if cfg!(target_os = "macos") == true
+    {
+        if let Some(prefix) = host.var("HOMEBREW_PREFIX")
+        {
+            dirs.push(PathBuf::from(prefix).join("bin"));
+        }
+        dirs.push(PathBuf::from("/opt/homebrew/bin"));
+        dirs.push(PathBuf::from("/usr/local/bin"));
+    }
+    if cfg!(target_os = "windows") == true
+    {
+        let root = match host.var("MSYS2_ROOT")
+        {
+            Some(root) => PathBuf::from(root),
+            None => PathBuf::from("C:\\msys64")
+        };
+        for environment in [ "ucrt64", "mingw64", "clang64", "usr" ]
+        {
+            dirs.push(root.join(environment).join("bin"));
+        }
+    }
+    dirs
+}
+
+/* give the executable to run for a tool: its name, if it's a path or can be found on PATH, or else its path
+   in the host's tool directories, if it's there. Missing tools are left to fail under their own names
+   => name = tool's executable name, eg riscv64-elf-as
+      host = environment the build runs in
+   <= returns the executable to run, eg /opt/homebrew/bin/riscv64-elf-as */
+fn locate_tool(name: &str, host: &HostEnv) -> String
+{
+    let on_path = host.var("PATH")
+        .map(|p| env::split_paths(p).any(|dir| tool_in_dir(&dir, name).is_some() == true))
+        .unwrap_or(false);
+
+    if name.contains('/') == true || on_path == true
+    {
+        return name.to_string();
+    }
+
+    match host_tool_dirs(host).iter().find_map(|dir| tool_in_dir(dir, name))
+    {
+        Some(path) => path.to_string_lossy().into_owned(),
+        None => name.to_string()
+    }
+}
+
+/* an object to link, and where it came from */
+pub struct Object
+{
+    path: String,  /* full path to the object file */
+    origin: String /* input file the object was generated or taken from */
+}
+
+/* a binary file packaged into an object */
+pub struct Payload
+{
+    path: String,          /* path to the binary file */
+    symbol_prefix: String, /* prefix of the payload's symbols, eg _binary_leafname_ for _binary_leafname_start */
+    hash: u64,             /* payload's content hash, as given by its _hash symbol */
+    load_address: Option<u64>, /* address the payload expects to be copied to, if given */
+    size: u64,             /* size in bytes of the contents packaged */
+    digest: String,        /* SHA-256 digest of the contents packaged, as hex */
+    origin: String         /* description of where the contents came from, eg fw.hex, converted from Intel HEX */
+}
+
+/* number of times to retry each type of step, and the delay before the first retry */
+pub struct RetryPolicy
+{
+    read: usize,
+    write: usize,
+    spawn: usize,
+    backoff: Duration
+}
+
+/* files read by external tools during the build, for the hermeticity audit */
+pub struct Audit
+{
+    strace: bool,                                /* true to trace tools with strace, or false to scan their arguments */
+    accessed: Mutex<BTreeMap<PathBuf, String>>   /* absolute paths of files read, mapped to the tool that read it */
+}
+
+/* the environment a build runs in, captured once when it's set up, so that builds in the same process,
+   eg for different targets, each use their own rather than reading process-wide state as they go */
+#[derive(Clone)]
+pub struct HostEnv
+{
+    vars: BTreeMap<String, String>, /* environment variables, by name */
+    current_dir: String,            /* directory relative paths are resolved against */
+    workspace: OnceLock<Option<Workspace>> /* cargo workspace the build is in, once it's been looked up */
+}
+
+/* what cargo metadata says about the workspace a build is in */
+#[derive(Clone)]
+struct Workspace
+{
+    root: PathBuf,                 /* workspace's root directory */
+    target_dir: PathBuf,           /* directory cargo builds the workspace in */
+    members: Vec<WorkspaceMember>  /* packages in the workspace */
+}
+
+/* a package in a cargo workspace */
+#[derive(Clone)]
+struct WorkspaceMember
+{
+    name: String,       /* package's name */
+    dir: PathBuf,       /* directory holding the package's Cargo.toml */
+    build_script: bool  /* true if the package has a build script, as crates using Mason do */
+}
+
+impl HostEnv
+{
+    /* capture the process's environment variables and current working directory. variables whose names
+       or values aren't valid Unicode are left out
+       <= returns the process's environment */
+    pub fn from_process() -> Result<HostEnv, MasonError>
+    {
+        let current_dir = match env::current_dir()
+        {
+            Ok(p) => p.to_string_lossy().to_string(),
+            Err(e) => return Err(MasonError::host(format!("Can't get the current working directory ({})", e)))
+        };
+
+        let vars = env::vars_os().filter_map(|(var, value)| match (var.into_string(), value.into_string())
+        {
+            (Ok(var), Ok(value)) => Some((var, value)),
+            _ => None
+        }).collect();
+
+        Ok(HostEnv::new(vars, current_dir))
+    }
+
+    /* describe an environment other than the process's, eg to run a build for another crate
+       => vars = environment variables, by name
+          current_dir = directory relative paths are resolved against
+       <= returns the environment */
+    pub fn new(vars: BTreeMap<String, String>, current_dir: String) -> HostEnv
+    {
+        HostEnv
+        {
+            vars: vars,
+            current_dir: current_dir,
+            workspace: OnceLock::new()
+        }
+    }
+
+    /* look up an environment variable
+       => name = variable's name
+       <= returns the variable's value, or None if it isn't set */
+    pub fn var(&self, name: &str) -> Option<&str>
+    {
+        self.vars.get(name).map(|value| value.as_str())
+    }
+
+    /* resolve a path, eg from the config file, against the environment's directory if it's relative
+       => path = path to resolve
+       <= returns the path to use to reach the file */
+    pub fn path(&self, path: &str) -> PathBuf
+    {
+        Path::new(&self.current_dir).join(path)
+    }
+
+    /* give the directory cargo has the build script write its files to, bailing out if there isn't one
+       <= returns OUT_DIR */
+    pub fn output_dir(&self) -> Result<String, MasonError>
+    {
+        match self.var("OUT_DIR")
+        {
+            Some(d) => Ok(d.to_string()),
+            None => Err(MasonError::config(String::from("No output directory specified")))
+        }
+    }
+
+    /* describe the cargo workspace the build is in, running cargo metadata the first time it's needed
+       <= returns the workspace, or None if cargo metadata can't describe it, eg cargo isn't available */
+    fn workspace(&self) -> Option<&Workspace>
+    {
+        self.workspace.get_or_init(|| cargo_workspace(self)).as_ref()
+    }
+}
+
+/* Ask cargo metadata about the workspace of the package being built, or else the one the working directory is
+   in. Only the workspace's own packages are listed, and nothing is fetched, so this is quick and works offline
+   => host = environment the build runs in
+   <= returns the workspace, or None if cargo metadata can't be run or fails */
+fn cargo_workspace(host: &HostEnv) -> Option<Workspace>
+{
+    let cargo = host.var("CARGO").unwrap_or("cargo").to_string();
+    let mut cmd = Command::new(&cargo);
+    cmd.args(["metadata", "--format-version", "1", "--no-deps", "--offline"]);
+    if let Some(dir) = host.var("CARGO_MANIFEST_DIR")
+    {
+        cmd.arg("--manifest-path").arg(Path::new(dir).join("Cargo.toml"));
+    }
+
+    let result = match host_command(&mut cmd, host).output()
+    {
+        Ok(result) if result.status.success() == true => result,
+        _ =>
+        {
+            log_event(host, LogLevel::Step, "workspace", format!("cargo metadata isn't available in {}", &host.current_dir));
+            return None;
+        }
+    };
+
+    let metadata: serde_json::Value = serde_json::from_slice(&result.stdout).ok()?;
+    let members = metadata["packages"].as_array().iter().flat_map(|p| p.iter())
+        .filter_map(|p| match (p["name"].as_str(), p["manifest_path"].as_str().and_then(|m| Path::new(m).parent()))
+        {
+            (Some(name), Some(dir)) => Some(WorkspaceMember
+            {
+                name: name.to_string(),
+                dir: dir.to_path_buf(),
+                build_script: p["targets"].as_array().iter().flat_map(|t| t.iter())
+                    .any(|t| t["kind"].as_array().iter().flat_map(|k| k.iter()).any(|k| k.as_str() == Some("custom-build")) == true)
+            }),
+            _ => None
+        })
+        .collect();
+
+    let workspace = Workspace
+    {
+        root: PathBuf::from(metadata["workspace_root"].as_str()?),
+        target_dir: PathBuf::from(metadata["target_directory"].as_str()?),
+        members: members
+    };
+
+    log_event(host, LogLevel::Step, "workspace", format!("in cargo workspace {}, building in {}", workspace.root.display(), workspace.target_dir.display()));
+    Some(workspace)
+}
+
+/* Put the cargo workspace's directories into a path from the config file: {workspace_root} is replaced with the
+   workspace's root directory, and {target_dir} with the directory cargo builds it in, eg so that crates in
+   a workspace can share a directory of objects without each giving its path relative to their own
+   => key = config file key the path is from, for error messages
+      path = path from the config file
+      host = environment the build runs in
+   <= returns the path with the directories in place */
+fn workspace_path(key: &str, path: &str, host: &HostEnv) -> Result<String, MasonError>
+{
+    if path.contains("{workspace_root}") == false && path.contains("{target_dir}") == false
+    {
+        return Ok(path.to_string());
+    }
+
+    match host.workspace()
+    {
+        Some(workspace) => Ok(path.replace("{workspace_root}", &workspace.root.to_string_lossy())
+            .replace("{target_dir}", &workspace.target_dir.to_string_lossy())),
+        None => Err(MasonError::config(format!("{} '{}' refers to the cargo workspace, but cargo metadata can't describe it", key, path)))
+    }
+}
+
+/* shared context of this build run */
+pub struct Context<'a>
+{
+    /* defined by the host environment */
+    host: HostEnv,            /* environment variables and working directory the build runs with */
+    output_dir: String,       /* where we're outputting object code on the host */
+    project_dir: String,      /* directory config file paths are relative to, ie the host's working directory */
+    sandbox_env: Option<Vec<String>>, /* environment variables passed to sandboxed tools, or None to not sandbox */
+    audit: Option<Audit>,     /* record of files read by tools, or None to not audit */
+    tool_timeout: Option<Duration>, /* time a tool may run for before it's killed, or None for no limit */
+    tool_retries: usize,      /* times to rerun a tool that timed out */
+    retry: RetryPolicy,       /* times to retry steps that fail with possibly transient errors */
+    build_stats: BTreeMap<String, f64>, /* seconds taken to assemble each source file, by path */
+    file_times: BTreeMap<String, Option<f64>>, /* seconds taken to process each input this build, or None if its object was reused */
+    inputs: BTreeSet<PathBuf>, /* absolute paths of files the build is declared to depend on */
+    objects: Vec<Object>,     /* objects to link in the order they were registered */
+    sections: Vec<String>,    /* sections generated by Mason, to be described in the linker script fragment */
+    payloads: Vec<Payload>,   /* binary files packaged into objects */
+    as_exec: String,          /* path to target's GNU assembler executable */
+    ar_exec: String,          /* path to target's GNU archiver executable */
+    ld_exec: String,          /* path to target's GNU linker executable */
+    oc_exec: String,          /* path to the target's GNU objcopy executable */
+    nm_exec: String,          /* path to the target's GNU nm executable */
+    tool_flags: ToolFlags,    /* extra options for ar and objcopy */
+    backend: Backend,         /* family of tools the executables above belong to */
+    symbol_namespace: String, /* namespace of symbols generated for binary files, eg binary for _binary_leafname_start */
+    object_format: String,    /* BFD name of the object format to package binary files in */
+    load_address_symbols: bool, /* true to define _load_address symbols for payloads with load addresses */
+    assemblers: BTreeMap<String, Assembler>, /* how to assemble source files, by file extension */
+    absent_symbols: Vec<String>, /* symbols defined for assembly code to mark optional binary files that are missing */
+    placeholders: BTreeMap<String, String>, /* contents packaged in place of missing optional binary files, by path */
+    object_name: Option<String>, /* template for generated objects' names, or None for the default */
+    library_members: HashMap<String, Vec<String>>, /* members to import from prebuilt libraries, if not all, by path */
+    source_map: bool,         /* true to assemble with debug info and map the resulting symbols to source lines */
+    debug_compression: DebugCompression, /* how to compress debug info assembled for the source map */
+    builtin_ar: bool,         /* true to write the bundle's archive without running ar */
+    sniff_payloads: bool,     /* true to report what each packaged binary's contents look like */
+    verbose: bool,            /* true to print the command line of every tool run */
+    cache_dir: Option<String>, /* persistent directory to cache assembled objects in, or None to use OUT_DIR */
+    binutils_versions: VersionRange, /* versions of binutils the build supports */
+    target: &'a Target        /* describe the build target */
+}
+
+fn main()
+{
+    /* report any failure once, here, however deep in the build it happened */
+    if let Err(e) = run()
+    {
+        /* the failure is reported in the process's environment, as only this process's build logs through it */
+        let host = HostEnv::from_process().unwrap_or(HostEnv::new(BTreeMap::new(), String::from(".")));
+        fatal_error(e, &host);
+    }
+}
+
+/* carry out a build, or the post-build step given on the command line
+   <= returns Ok, or the failure to report */
+fn run() -> Result<(), MasonError>
+{
+    /* when run with arguments, carry out a post-build step rather than a build */
+    let args: Vec<String> = env::args().collect();
+    if args.len() > 1
+    {
+        return match args[1].as_str()
+        {
+            "check-rodata" => check_rodata(&args[2..]),
+            "size-report" => size_report(&args[2..]),
+            "out-dir" => out_dir(&args[2..]),
+            "check-asm" => check_asm(&args[2..]),
+            "diff-config" => diff_config(&args[2..]),
+            unknown => Err(MasonError::config(format!("Unknown command '{}'", unknown)))
+        };
+    }
+
+    let host = HostEnv::from_process()?;
+    let (manifest, target) = configure(&host)?;
+
+    /* work out what to do, and then do it */
+    let mut build = Build::with_host(manifest, &target, host.output_dir()?, host)?;
+    let plan = build.resolve()?;
+    write_plan(&plan, &build.context.host)?;
+    write_commands(&build, &plan)?;
+    build.execute(plan)
+}
+
+/* a command a build will run for one of its inputs, as returned by Build::commands() */
+#[derive(Serialize)]
+pub struct ToolCommand
+{
+    pub step: &'static str, /* what the command does: preprocess, assemble, or package */
+    pub input: String,      /* source or binary file the command builds */
+    pub argv: Vec<String>   /* program to run, followed by its arguments */
+}
+
+impl ToolCommand
+{
+    fn new(step: &'static str, input: &str, command: &Command) -> ToolCommand
+    {
+        ToolCommand
+        {
+            step: step,
+            input: input.to_string(),
+            argv: std::iter::once(command.get_program()).chain(command.get_args()).map(|a| a.to_string_lossy().into_owned()).collect()
+        }
+    }
+}
+
+/* what a build will do, resolved from the config file before any tools are run.
+   this can be inspected and amended before it is carried out */
+#[derive(Serialize)]
+pub struct Plan
+{
+    pub include_files: Vec<String>, /* binary files to package up */
+    pub sources: Vec<String>,       /* assembly source files, in the order they'll be assembled */
+    pub object_files: Vec<String>,  /* prebuilt objects to add to the archive as they are */
+    pub lib_files: Vec<String>,     /* prebuilt static libraries to merge into the archive */
+    pub stages: Vec<String>,        /* bootloader stages to build, in the order they'll be built */
+    pub asm_includes: BTreeMap<String, AsmInclude>, /* assembly include files to generate, by filename */
+    pub outputs: Vec<String>,       /* files the build will write to the output directory, besides library members */
+    pub symbols: Vec<String>        /* symbols the build will generate for packaged binaries */
+}
+
+impl Plan
+{
+    /* get an assembly include file to generate in the output directory, adding it to the plan if needed,
+       so that values computed by build logic can be given to assembly code, eg:
+       plan.asm_include("tables.inc", Assembler::Gas).constant("PAGE_SIZE", 4096).table("crc_table", crc_table());
+       => file = leafname of include file, eg tables.inc
+          syntax = assembler whose syntax the file is written in, if it's added to the plan
+       <= returns the include file, to which constants and tables can be added */
+    pub fn asm_include(&mut self, file: &str, syntax: Assembler) -> &mut AsmInclude
+    {
+        self.asm_includes.entry(file.to_string()).or_insert(AsmInclude
+        {
+            syntax: syntax,
+            constants: BTreeMap::new(),
+            tables: BTreeMap::new()
+        })
+    }
+}
+
+/* an assembly include file generated from values given by the config file or computed by build logic */
+#[derive(Serialize)]
+pub struct AsmInclude
+{
+    pub syntax: Assembler,                   /* assembler whose syntax to write the file in */
+    pub constants: BTreeMap<String, i64>,    /* symbols to define, by name */
+    pub tables: BTreeMap<String, Vec<u64>>   /* tables of pointer-sized words, by label */
+}
+
+impl AsmInclude
+{
+    /* define a constant symbol in the file
+       => name = name of the symbol
+          value = value of the symbol
+       <= returns the include file, so that definitions can be chained */
+    pub fn constant(&mut self, name: &str, value: i64) -> &mut AsmInclude
+    {
+        self.constants.insert(name.to_string(), value);
+        self
+    }
+
+    /* define a table of pointer-sized words in the file, placed in whichever section is current where it's included
+       => label = label marking the start of the table
+          values = words in the table, truncated to the target's pointer width
+       <= returns the include file, so that definitions can be chained */
+    pub fn table(&mut self, label: &str, values: Vec<u64>) -> &mut AsmInclude
+    {
+        self.tables.insert(label.to_string(), values);
+        self
+    }
+}
+
+/* a build run for a target, configured from the host environment and config file */
+pub struct Build<'a>
+{
+    context: Context<'a>,
+    config: Config,
+    inputs: Inputs
+}
+
+impl<'a> Build<'a>
+{
+    /* set up a build for the given target from its config file settings
+       => manifest = config file's settings for the target
+          target = build target described by the target triple
+       <= returns the configured build, ready for resolve(), or the failure */
+    pub fn new(manifest: Manifest, target: &'a Target) -> Result<Build<'a>, MasonError>
+    {
+        let host = HostEnv::from_process()?;
+        Build::with_host(manifest, target, host.output_dir()?, host)
+    }
+
+    /* set up a build for the given target that writes its files to the given directory rather than OUT_DIR
+       => manifest = config file's settings for the target
+          target = build target described by the target triple
+          output_dir = directory to write files to
+       <= returns the configured build, ready for resolve(), or the failure */
+    pub fn with_output_dir(manifest: Manifest, target: &'a Target, output_dir: String) -> Result<Build<'a>, MasonError>
+    {
+        Build::with_host(manifest, target, output_dir, HostEnv::from_process()?)
+    }
+
+    /* set up a build for the given target that runs in the given environment rather than the process's, so
+       that more than one build can be run at once in the same process, eg on different threads
+       => manifest = config file's settings for the target
+          target = build target described by the target triple
+          output_dir = directory to write files to
+          host = environment variables and working directory to build with
+       <= returns the configured build, ready for resolve(), or the failure */
+    pub fn with_host(manifest: Manifest, target: &'a Target, output_dir: String, host: HostEnv) -> Result<Build<'a>, MasonError>
+    {
+        let project_dir = host.current_dir.clone();
+
+        /* choose the tools to build with. the environment can override the config file, eg to try LLVM
+        on a host without cross binutils, and the LLVM tools aren't prefixed */
+        println!("cargo:rerun-if-env-changed={}", BACKEND_VAR);
+        let backend = match host.var(BACKEND_VAR)
+        {
+            Some("gnu") => Backend::Gnu,
+            Some("llvm") => Backend::Llvm,
+            Some("rustup") => Backend::Rustup,
+            Some(other) => return Err(MasonError::config(format!("Unknown {} '{}': use gnu, llvm, or rustup", BACKEND_VAR, other))),
+            None => manifest.config.backend.unwrap_or(Backend::Gnu)
+        };
+
+        let tools: Vec<String> = match backend
+        {
+            Backend::Gnu => GNU_TOOLS.iter().map(|t| prefixed_tool(&target.gnu_prefix, t)).collect(),
+            Backend::Llvm => LLVM_TOOLS.iter().map(|t| t.to_string()).collect(),
+            Backend::Rustup => rustup_tools(&host)
+        };
+
+        /* binaries were once packaged by ld -r, which ld_r_flags gave options to */
+        if manifest.ld_r_flags == true
+        {
+            log_event(&host, LogLevel::Warning, "config", String::from("ld_r_flags is no longer used: binaries are packaged without ld"));
+        }
+
+        /* create a shared context describing this build */
+        let mut context = Context
+        {
+            as_exec: tool_exec("MASON_AS", "", &tools[0], &host),
+            ar_exec: tool_exec("MASON_AR", "", &tools[1], &host),
+            ld_exec: tool_exec("MASON_LD", "", &tools[2], &host),
+            oc_exec: tool_exec("MASON_OBJCOPY", "", &tools[3], &host),
+            nm_exec: tool_exec("MASON_NM", "", &tools[4], &host),
+            host: host,
+            output_dir: output_dir,
+            project_dir: project_dir,
+            sandbox_env: None,
+            audit: None,
+            tool_timeout: None,
+            tool_retries: 0,
+            retry: RetryPolicy
+            {
+                read: 0,
+                write: 0,
+                spawn: 0,
+                backoff: Duration::from_millis(100)
+            },
+            build_stats: BTreeMap::new(),
+            file_times: BTreeMap::new(),
+            inputs: BTreeSet::new(),
+            objects: Vec::new(),
+            sections: Vec::new(),
+            payloads: Vec::new(),
+            tool_flags: manifest.tool_flags,
+            backend: backend,
+            symbol_namespace: String::from(DEFAULT_SYMBOL_NAMESPACE),
+            object_format: target.bfd_format.clone(),
+            load_address_symbols: false,
+            assemblers: [(String::from("s"), Assembler::Gas)].iter().cloned().collect(),
+            absent_symbols: Vec::new(),
+            placeholders: BTreeMap::new(),
+            object_name: None,
+            library_members: HashMap::new(),
+            source_map: false,
+            debug_compression: DebugCompression::Zlib,
+            builtin_ar: false,
+            sniff_payloads: false,
+            verbose: false,
+            cache_dir: None,
+            binutils_versions: manifest.binutils_versions,
+            target: target
+        };
+
+        let config = manifest.config;
+
+        /* rebuild if the config file, or any it extends, changes */
+        for path in manifest.config_files.iter()
+        {
+            declare_input(path, &mut context);
+        }
+
+        /* rebuild if the definition of the target's architecture changes */
+        if let Some(arch_files) = &config.arch_files
+        {
+            for path in arch_files
+            {
+                declare_input(path, &mut context);
+            }
+        }
+
+        if let Some(spec_file) = &target.spec_file
+        {
+            declare_input(spec_file, &mut context);
+        }
+
+        if let Some(namespace) = &config.symbol_namespace
+        {
+            context.symbol_namespace = namespace.clone();
+        }
+
+        context.load_address_symbols = config.load_address_symbols.unwrap_or(false);
+        context.object_name = config.object_name.clone();
+        context.source_map = config.source_map.unwrap_or(false);
+        context.debug_compression = config.debug_compression.unwrap_or(DebugCompression::Zlib);
+        context.builtin_ar = config.builtin_ar.unwrap_or(false);
+        context.sniff_payloads = config.sniff_payloads.unwrap_or(false);
+
+        /* the environment can turn on verbose mode, eg to debug a failing build, without editing the config file */
+        println!("cargo:rerun-if-env-changed={}", VERBOSE_VAR);
+        context.verbose = match context.host.var(VERBOSE_VAR)
+        {
+            Some(value) if value.len() > 0 => value != "0",
+            _ => config.verbose.unwrap_or(false)
+        };
+
+        /* the environment can point every crate at one cache, eg from a CI job, whatever their config files say */
+        println!("cargo:rerun-if-env-changed={}", CACHE_DIR_VAR);
+        context.cache_dir = match context.host.var(CACHE_DIR_VAR)
+        {
+            Some(dir) if dir.len() > 0 => Some(dir.to_string()),
+            _ => config.cache_dir.clone()
+        }.map(|dir| workspace_path("cache_dir", &dir, &context.host).map(|dir| context.host.path(&dir).to_string_lossy().to_string())).transpose()?;
+
+        if let Some(assemblers) = &config.assemblers
+        {
+            context.assemblers.extend(assemblers.iter().map(|(ext, asm)| (ext.clone(), *asm)));
+        }
+
+        if let Some(seconds) = config.tool_timeout
+        {
+            context.tool_timeout = Some(Duration::from_secs(seconds));
+        }
+        context.tool_retries = config.tool_retries.unwrap_or(0);
+
+        if let Some(retry) = &config.retry
+        {
+            context.retry.read = retry.read.unwrap_or(0);
+            context.retry.write = retry.write.unwrap_or(0);
+            context.retry.spawn = retry.spawn.unwrap_or(0);
+            if let Some(ms) = retry.backoff_ms
+            {
+                context.retry.backoff = Duration::from_millis(ms);
+            }
+        }
+
+        /* always let sandboxed tools find their own executables */
+        if config.sandbox.unwrap_or(false) == true
+        {
+            let mut allowed = vec![String::from("PATH")];
+            if let Some(vars) = &config.sandbox_env
+            {
+                allowed.extend(vars.iter().cloned());
+            }
+            context.sandbox_env = Some(allowed);
+        }
+
+        if config.audit.unwrap_or(false) == true
+        {
+            let strace = match host_command(&mut Command::new("strace"), &context.host).arg("-V").output()
+            {
+                Ok(output) => output.status.success(),
+                Err(_) => false
+            };
+
+            context.audit = Some(Audit
+            {
+                strace: strace,
+                accessed: Mutex::new(BTreeMap::new())
+            });
+        }
+
+        /* apply settings that the target can override */
+        if let Some(format) = manifest.object_format
+        {
+            context.object_format = format;
+        }
+
+        /* bring in the compiler's intrinsics for assembly code that calls them */
+        let mut inputs = manifest.inputs;
+        let libgcc = match manifest.libgcc
+        {
+            Some(Libgcc::All(true)) => Some(None),
+            Some(Libgcc::Members(members)) => Some(Some(members)),
+            Some(Libgcc::All(false)) | None => None
+        };
+
+        if let Some(members) = libgcc
+        {
+            let path = find_libgcc(&manifest.libgcc_flags, &context)?;
+            if let Some(members) = members
+            {
+                context.library_members.insert(path.clone(), members);
+            }
+            inputs.lib_files.insert(path);
+        }
+
+        /* leave out optional binary files that are missing, so that the build goes ahead without them,
+        or package their placeholders in their place. they're still declared as inputs so that cargo
+        reruns the build once they appear */
+        let mut absent: Vec<IncludeEntry> = inputs.include_settings.values()
+            .filter(|e| e.optional.unwrap_or(false) == true && context.host.path(&e.path).exists() == false)
+            .cloned()
+            .collect();
+        absent.sort_by(|a, b| a.path.cmp(&b.path));
+
+        for entry in absent
+        {
+            let path = entry.path;
+            declare_input(&path, &mut context);
+            context.absent_symbols.push(payload_symbols(&path, &context.symbol_namespace).absent);
+
+            let placeholder = match entry.placeholder
+            {
+                Some(Placeholder::Empty(true)) => Some(placeholder_path(&path, &context)),
+                Some(Placeholder::Stub(stub)) =>
+                {
+                    declare_input(&stub, &mut context);
+                    Some(stub)
+                },
+                Some(Placeholder::Empty(false)) | None => None
+            };
+
+            match placeholder
+            {
+                Some(contents) =>
+                {
+                    log_event(&context.host, LogLevel::Warning, "optional", format!("Optional binary file {} not found, packaging {} in its place", &path, &contents));
+                    context.placeholders.insert(path, contents);
+                },
+                None =>
+                {
+                    log_event(&context.host, LogLevel::Warning, "optional", format!("Optional binary file {} not found, building without it", &path));
+                    inputs.include_files.remove(&path);
+                    inputs.include_settings.remove(&path);
+                }
+            }
+        }
+
+        Ok(Build
+        {
+            context: context,
+            config: config,
+            inputs: inputs
+        })
+    }
+
+    /* work out what the build will do from the config file, without running any tools
+       <= returns the build's plan, to pass to execute(), or the failure */
+    pub fn resolve(&mut self) -> Result<Plan, MasonError>
+    {
+        let context = &mut self.context;
+        let config = &self.config;
+        let inputs = &self.inputs;
+
+        /* find all asm code in each of these directories */
+        let mut sources = Vec::new();
+        for (dir, allow_empty) in inputs.asm_dirs.iter()
+        {
+            sources.extend(scan_directory(dir.clone(), *allow_empty, &context)?);
+        }
+
+        /* assemble the files that took longest last time first, so that they
+        don't hold up the end of the build. files not seen before go first. files that took as long, such as
+        those not seen before, are ordered by path, so that the plan is the same however asm_dirs is iterated */
+        load_build_stats(context);
+        sources.sort_by(|a, b|
+        {
+            let a_time = context.build_stats.get(a).cloned().unwrap_or(f64::INFINITY);
+            let b_time = context.build_stats.get(b).cloned().unwrap_or(f64::INFINITY);
+            b_time.partial_cmp(&a_time).unwrap_or(std::cmp::Ordering::Equal).then_with(|| a.cmp(b))
+        });
+
+        let mut plan = Plan
+        {
+            include_files: inputs.include_files.iter().cloned().collect(),
+            sources: sources,
+            object_files: inputs.object_files.iter().cloned().collect(),
+            lib_files: inputs.lib_files.iter().cloned().collect(),
+            stages: inputs.stages.clone(),
+            asm_includes: BTreeMap::new(),
+            outputs: Vec::new(),
+            symbols: Vec::new()
+        };
+
+        plan.include_files.sort();
+        plan.object_files.sort();
+        plan.lib_files.sort();
+
+        if let Some(constants) = &config.asm_constants
+        {
+            plan.asm_include(ASM_CONSTANTS_FILE, Assembler::Gas).constants.extend(constants.iter().map(|(k, v)| (k.clone(), *v)));
+        }
+
+        /* the last stage of each bootloader chain, ie those not embedded in another stage, is packaged up */
+        if let Some(stages) = &config.stages
+        {
+            for stage in stages.iter().filter(|s| stages.iter().any(|other| other.embeds.iter().flatten().any(|e| e == &s.name)) == false)
+            {
+                plan.include_files.push(stage_output(&stage.name, context));
+            }
+        }
+
+        if config.initrd.is_some() == true
+        {
+            plan.include_files.push(format!("{}/{}", &context.output_dir, INITRD_FILE));
+        }
+
+        /* binaries compressed with the shared zstd dictionary need it packaged alongside them to be decompressed */
+        if plan.include_files.iter().any(|f| uses_zstd_dictionary(f, inputs) == true) == true
+        {
+            plan.include_files.push(format!("{}/{}", &context.output_dir, ZSTD_DICTIONARY_FILE));
+        }
+
+        /* symbol tables extracted from ELF binaries are packaged alongside them */
+        let symtabs: Vec<String> = plan.include_files.iter()
+            .filter(|f| embeds_symbols(f, inputs, context) == true)
+            .map(|f| symtab_path(f, context))
+            .collect();
+        plan.outputs.extend(symtabs.iter().cloned());
+        plan.include_files.extend(symtabs);
+
+        for file in plan.asm_includes.keys()
+        {
+            plan.outputs.push(format!("{}/{}", &context.output_dir, file));
+        }
+
+        for stage in plan.stages.iter()
+        {
+            plan.outputs.push(format!("{}/{}/{}.elf", &context.output_dir, STAGES_DIR, stage));
+            plan.outputs.push(stage_output(stage, context));
+        }
+
+        /* predict the objects and symbols the build will generate. a binary's section and symbols are named after
+        its leafname alone, whatever object_name gives its object, so binaries must not share them */
+        let mut prefixes: HashMap<String, &String> = HashMap::new();
+        let mut sections: HashMap<String, &String> = HashMap::new();
+        for f in plan.include_files.iter()
+        {
+            let prefix = payload_symbol_prefix(f, context);
+            if let Some(other) = prefixes.insert(prefix.clone(), f)
+            {
+                return Err(MasonError::config(format!("Binary files {} and {} would both define the symbols {}start, etc.: rename one of them, as a binary's symbols are named after its leafname",
+                    other, f, &prefix)));
+            }
+            let section = payload_section(f);
+            if let Some(other) = sections.insert(section.clone(), f)
+            {
+                return Err(MasonError::config(format!("Binary files {} and {} would both be placed in section {}: rename one of them, as a binary's section is named after its leafname",
+                    other, f, &section)));
+            }
+
+            if let Some(placeholder) = context.placeholders.get(f)
+            {
+                if *placeholder == placeholder_path(f, context)
+                {
+                    plan.outputs.push(placeholder.clone());
+                }
+            }
+            else
+            {
+                if image_format(f, inputs.include_settings.get(f)) != ImageFormat::Binary
+                {
+                    plan.outputs.push(converted_path(f, context));
+                }
+                if inputs.include_settings.get(f).and_then(|e| e.transforms.as_ref()).is_some() == true
+                {
+                    plan.outputs.push(transformed_path(f, context));
+                }
+            }
+            plan.outputs.push(payload_object_path(f, context));
+            plan.outputs.push(payload_stamp_path(&payload_object_path(f, context)));
+
+            let symbols = payload_symbols(f, &context.symbol_namespace);
+            plan.symbols.extend([symbols.start, symbols.end, symbols.size, symbols.hash].iter().cloned());
+
+            /* converted images take their base address as their load address unless they're given one */
+            let converted = context.placeholders.contains_key(f) == false && image_format(f, inputs.include_settings.get(f)) != ImageFormat::Binary;
+            if context.load_address_symbols == true && (inputs.include_settings.get(f).and_then(|e| e.load_address).is_some() == true || converted == true)
+            {
+                plan.symbols.push(symbols.load_address);
+            }
+        }
+
+        for f in plan.sources.iter()
+        {
+            let name = object_name(f, SOURCE_OBJECT_NAME, context);
+            if source_assembler(f, context) == Some(Assembler::CppGas)
+            {
+                plan.outputs.push(format!("{}/{}.i", &context.output_dir, &name));
+            }
+            plan.outputs.push(format!("{}/{}.o", &context.output_dir, &name));
+        }
+
+        for f in plan.object_files.iter()
+        {
+            plan.outputs.push(format!("{}/{}", &context.output_dir, file_leafname(f)));
+        }
+
+        for f in [ARCHIVE_FILE, OBJECT_LIST_FILE, RUST_SHIM_FILE, LINK_SCRIPT_FILE, BUILD_STATS_FILE].iter()
+        {
+            plan.outputs.push(format!("{}/{}", &context.output_dir, f));
+        }
+
+        if config.auto_link_script.unwrap_or(false) == true
+        {
+            plan.outputs.push(format!("{}/{}", &context.output_dir, LINK_SCRIPT_WRAPPER_FILE));
+        }
+
+        if context.source_map == true
+        {
+            plan.outputs.push(format!("{}/{}", &context.output_dir, SOURCE_MAP_FILE));
+        }
+
+        if config.provenance.unwrap_or(false) == true
+        {
+            plan.outputs.push(format!("{}/{}", &context.output_dir, PROVENANCE_FILE));
+        }
+
+        if config.report.unwrap_or(false) == true
+        {
+            plan.outputs.push(format!("{}/{}", &context.output_dir, REPORT_FILE));
+        }
+
+        if let Some(format) = config.bundle_doc
+        {
+            plan.outputs.push(format!("{}/{}", &context.output_dir, format.file()));
+        }
+
+        if config.build_id.unwrap_or(false) == true
+        {
+            plan.outputs.push(format!("{}/{}", &context.output_dir, BUILD_ID_OBJECT));
+            plan.symbols.push(BUILD_ID_SYMBOL.to_string());
+        }
+
+        Ok(plan)
+    }
+
+    /* give the names of the symbols this build will generate for a binary file, using the config file's symbol_namespace
+       => binary_path = path to binary file, as listed in the config file
+       <= returns the names of the file's symbols */
+    pub fn symbols_for(&self, binary_path: &str) -> PayloadSymbols
+    {
+        payload_symbols(binary_path, &self.context.symbol_namespace)
+    }
+
+    /* Rebuild one file into the output of an earlier full build, for editors and watch modes wanting quick
+       feedback: assemble one source file from the asm_dirs, or repackage one binary file, and replace its
+       object in the bundle. Nothing else is regenerated, besides the binary's hash in mason.rs
+       => path = path of source or binary file, as found in or below asm_dirs or listed in the config file
+       <= returns the path of the replaced object, or the failure, eg if the file can't be rebuilt */
+    pub fn rebuild_file(&mut self, path: &str) -> Result<String, MasonError>
+    {
+        let context = &mut self.context;
+        let inputs = &self.inputs;
+        let archive = format!("{}/{}", &context.output_dir, ARCHIVE_FILE);
+        if Path::new(&archive).exists() == false
+        {
+            return Err(MasonError::config(format!("No bundle in {} to rebuild {} into: run a full build first", &context.output_dir, path)));
+        }
+
+        if context.host.path(path).is_file() == false
+        {
+            return Err(MasonError::config(format!("Can't rebuild {}: it isn't a file", path)));
+        }
+
+        let object_file = if inputs.include_files.contains(path) == true
+        {
+            let binary = path.to_string();
+            let object_file = payload_object_path(&binary, context);
+            let payload = make_payload_object(&binary, &object_file, inputs.include_settings.get(path), context)?;
+            update_shim_hash(&payload, context).map_err(MasonError::config)?;
+            object_file
+        }
+        else
+        {
+            /* accept sources anywhere below one of the asm_dirs, including in its subdirectories */
+            let in_asm_dir = Path::new(path).parent()
+                .map(|dir| inputs.asm_dirs.keys().any(|d| dir.starts_with(d) == true)).unwrap_or(false);
+            if in_asm_dir == false || source_assembler(path, context).is_none() == true
+            {
+                return Err(MasonError::config(format!("{} is neither an assembly source file in asm_dirs nor a binary file to package", path)));
+            }
+
+            let output_dir = context.output_dir.clone();
+            let started = Instant::now();
+            let object_file = match assemble_into(path, &output_dir, context)?
+            {
+                Some(object_file) => object_file,
+                None => return Err(MasonError::config(format!("Can't rebuild {}: it has no usable leafname", path)))
+            };
+            load_build_stats(context);
+            context.build_stats.insert(path.to_string(), started.elapsed().as_secs_f64());
+            save_build_stats(&context)?;
+            object_file
+        };
+
+        /* replace the object's old version in the bundle */
+        if context.builtin_ar == true
+        {
+            replace_archive_member(&archive, &object_file, context).map_err(MasonError::host)?;
+        }
+        else
+        {
+            let mut command = Command::new(&context.ar_exec);
+            command.args(&context.tool_flags.ar).arg("rsD").arg(&archive).arg(&object_file);
+            let result = run_tool(&mut command, format!("Failed to execute command to update {}", &archive), &context)?;
+
+            if result.status.success() != true
+            {
+                return Err(MasonError::tool_failure(format!("Updating {} with {} failed:\n{}\n{}", &archive, &object_file,
+                    String::from_utf8_lossy(&result.stdout), String::from_utf8_lossy(&result.stderr))).with_command(&command, None));
+            }
+        }
+
+        Ok(object_file)
+    }
+
+    /* check that every assembly source file in a plan, and in its bootloader stages, assembles, discarding the
+       objects. Only intermediate files, such as generated include files, are written to the output directory
+       => plan = plan to check, as returned by resolve()
+       <= returns the paths of the source files checked, and descriptions of those that failed, or the failure
+          if they couldn't be checked */
+    pub fn check(&mut self, plan: &Plan) -> Result<(Vec<String>, Vec<String>), MasonError>
+    {
+        let context = &mut self.context;
+        for (file, include) in plan.asm_includes.iter()
+        {
+            write_asm_include(file, include, context)?;
+        }
+
+        let mut sources = plan.sources.clone();
+        for stage in self.config.stages.iter().flatten()
+        {
+            for dir in stage.asm_dirs.iter().flatten()
+            {
+                sources.extend(scan_directory(dir.clone(), true, &context)?);
+            }
+        }
+
+        let mut failures = Vec::new();
+        let output_dir = context.output_dir.clone();
+        for source in sources.iter()
+        {
+            if let (Some(_), Some(assembler)) = (source_leafname(source), source_assembler(source, context))
+            {
+                let name = object_name(source, SOURCE_OBJECT_NAME, context);
+                if let Err(e) = assemble_source(source, &name, assembler, NULL_OBJECT, &output_dir, context)
+                {
+                    failures.push(e.context().message.clone());
+                }
+            }
+        }
+
+        Ok((sources, failures))
+    }
+
+    /* give the commands that a plan's assembly source files will be preprocessed and assembled with, without
+       running them, so that tests can check the options generated for each target. Binary files are packaged
+       without running any tools, so they have no commands
+       => plan = plan to describe, as returned by resolve()
+       <= returns each command, in the order the plan runs them */
+    pub fn commands(&self, plan: &Plan) -> Vec<ToolCommand>
+    {
+        let context = &self.context;
+        let mut commands = Vec::new();
+
+        for source in plan.sources.iter()
+        {
+            if let (Some(_), Some(assembler)) = (source_leafname(source), source_assembler(source, context))
+            {
+                let name = object_name(source, SOURCE_OBJECT_NAME, context);
+                let object_file = format!("{}/{}.o", &context.output_dir, &name);
+                let mut input = source.clone();
+                if assembler == Assembler::CppGas
+                {
+                    input = format!("{}/{}.i", &context.output_dir, &name);
+                    commands.push(ToolCommand::new("preprocess", source, &preprocess_command(source, &input, context)));
+                }
+                commands.push(ToolCommand::new("assemble", source, &assemble_command(&input, assembler, &object_file, context)));
+            }
+        }
+
+        commands
+    }
+
+    /* carry out a build's plan, generating and linking with the bundle
+       => plan = what to build, as returned by resolve() and optionally amended */
+    pub fn execute(&mut self, plan: Plan) -> Result<(), MasonError>
+    {
+        /* the commands each source is built with are given in the report, if one's written */
+        let started = Instant::now();
+        let commands = match self.config.report.unwrap_or(false)
+        {
+            true => self.commands(&plan),
+            false => Vec::new()
+        };
+
+        let context = &mut self.context;
+        let config = &self.config;
+        let inputs = &self.inputs;
+
+        /* make sure every tool the plan needs is installed before running any of them */
+        check_tools_present(&plan, config, inputs, context).map_err(|e| e.in_stage("tools"))?;
+
+        /* and that the assembler is a version of binutils the build supports. as is only run if there are
+        stages to link or sources it assembles */
+        let assembles = plan.stages.len() > 0 || plan.sources.iter()
+            .any(|s| matches!(source_assembler(s, context), Some(a) if a != Assembler::Nasm)) == true;
+        if context.backend == Backend::Gnu && assembles == true
+        {
+            check_binutils_version(context).map_err(|e| e.in_stage("tools"))?;
+        }
+
+        /* make sure the tools used to link bootloader stages can produce objects for the target.
+        LLVM's tools don't list their formats, but each handles every target LLVM was built for */
+        if context.backend == Backend::Gnu && plan.stages.len() > 0
+        {
+            check_tool_supports_format(&context.ld_exec, &context).map_err(|e| e.in_stage("tools"))?;
+            check_tool_supports_format(&context.oc_exec, &context).map_err(|e| e.in_stage("tools"))?;
+        }
+
+        /* generate include files before any assembly code that uses them is built */
+        for (file, include) in plan.asm_includes.iter()
+        {
+            write_asm_include(file, include, context).map_err(|e| e.in_stage("asm_include").with_file(file))?;
+        }
+
+        /* build bootloader stages first, as their binaries may be packaged up below */
+        for name in plan.stages.iter()
+        {
+            match config.stages.iter().flatten().find(|s| &s.name == name)
+            {
+                Some(stage) =>
+                {
+                    log_event(&context.host, LogLevel::Step, "stage", format!("building stage {}", name));
+                    build_stage(stage, context).map_err(|e| e.in_stage("stage"))?
+                },
+                None => return Err(MasonError::config(format!("Plan includes stage {}, which isn't defined", name)))
+            }
+        }
+
+        if let Some(initrd) = &config.initrd
+        {
+            log_event(&context.host, LogLevel::Step, "initrd", format!("building {} with {} file(s)", INITRD_FILE, initrd.files.len()));
+            build_initrd(initrd, context).map_err(|e| e.in_stage("initrd"))?;
+        }
+
+        /* extract symbol tables before the binaries they come from are transformed, eg stripped */
+        for f in plan.include_files.iter().filter(|f| embeds_symbols(f, inputs, context) == true)
+        {
+            log_event(&context.host, LogLevel::Step, "symtab", format!("extracting the symbol table of {}", f));
+            write_symbol_table(f, context).map_err(|e| e.in_stage("symtab").with_file(f))?;
+        }
+
+        /* train the dictionary shared by binaries compressed with zstd before any of them are compressed */
+        let members: Vec<&String> = plan.include_files.iter().filter(|f| uses_zstd_dictionary(f, inputs) == true).collect();
+        if members.len() > 0
+        {
+            log_event(&context.host, LogLevel::Step, "dictionary", format!("training {} on {} binary file(s)", ZSTD_DICTIONARY_FILE, members.len()));
+            train_zstd_dictionary(&members, config.zstd_dictionary_size, inputs, context).map_err(|e| e.in_stage("dictionary"))?;
+        }
+
+        /* package up individual binary files */
+        for f in plan.include_files.iter()
+        {
+            log_event(&context.host, LogLevel::Step, "package", format!("packaging {}", f));
+            package_binary(f, inputs.include_settings.get(f), context).map_err(|e| e.in_stage("package").with_file(f))?;
+        }
+
+        /* check the assembly code's style with the project's own tool before any of it is assembled */
+        if let Some(lint) = &config.asm_lint
+        {
+            log_event(&context.host, LogLevel::Step, "lint", format!("checking {} source file(s) with {}", plan.sources.len(), &lint.command[0]));
+            lint_sources(&plan.sources, lint, context).map_err(|e| e.in_stage("lint"))?;
+        }
+
+        /* use the objects another crate assembled from the same sources for the same target, if it's
+        shared them. otherwise, assemble all asm code, timing each file for future builds */
+        let shared = match &config.shared_dir
+        {
+            Some(dir) => Some(shared_objects_dir(&workspace_path("shared_dir", dir, &context.host)?, &plan, inputs, context)?),
+            None => None
+        };
+        match &shared
+        {
+            Some(dir) if dir.join(SHARED_COMPLETE_FILE).exists() == true =>
+            {
+                log_event(&context.host, LogLevel::Step, "shared", format!("using {} shared object(s) from {}", plan.sources.len(), dir.display()));
+                for source in plan.sources.iter()
+                {
+                    use_shared_object(source, dir, context).map_err(|e| e.in_stage("shared").with_file(source))?;
+                }
+            },
+            _ =>
+            {
+                assemble_sources(&plan, inputs, context).map_err(|e| e.in_stage("assemble"))?;
+                save_build_stats(&context)?;
+
+                if let Some(dir) = &shared
+                {
+                    share_objects(&plan.sources, dir, context);
+                }
+            }
+        }
+
+        /* bring in prebuilt objects */
+        for f in plan.object_files.iter()
+        {
+            log_event(&context.host, LogLevel::Step, "object", format!("including object {}", f));
+            include_object(f, context).map_err(|e| e.in_stage("object").with_file(f))?;
+        }
+
+        /* merge in the members of prebuilt libraries. do this last so that any
+        member whose name clashes with another object is the one that's renamed */
+        for f in plan.lib_files.iter()
+        {
+            log_event(&context.host, LogLevel::Step, "library", format!("importing library {}", f));
+            import_library(f, context).map_err(|e| e.in_stage("library").with_file(f))?;
+        }
+
+        /* deal with any symbols defined by more than one object */
+        if scans_symbols(&plan, config) == true
+        {
+            resolve_symbol_conflicts(&config.symbol_conflicts, context).map_err(|e| e.in_stage("symbols"))?;
+        }
+
+        /* identify the bundle by its inputs, now they're all known, so crash reports can be matched to it */
+        if config.build_id.unwrap_or(false) == true
+        {
+            write_build_id(context)?;
+        }
+
+        /* package up all the generated object files into an archive and link against it */
+        log_event(&context.host, LogLevel::Step, "archive", format!("archiving {} object(s) into {}", context.objects.len(), ARCHIVE_FILE));
+        link_archive(context).map_err(|e| e.in_stage("archive"))?;
+
+        /* list what went into the archive, for debugging tools */
+        write_object_list(&context)?;
+
+        /* help panic and backtrace decoders attribute addresses in assembly code to its source */
+        if context.source_map == true
+        {
+            write_source_map(&plan.sources, &context)?;
+        }
+
+        /* give Rust code a safe way to reach the packaged binaries */
+        write_rust_shim(&config.guests, &context)?;
+
+        /* describe the generated sections to the linker */
+        write_link_script(config.auto_link_script.unwrap_or(false), &config.payload_groups, context)?;
+
+        /* warn about any files tools read that the build doesn't depend on */
+        report_audit(&context);
+
+        /* describe how the bundle was made, for release pipelines to attest */
+        if config.provenance.unwrap_or(false) == true
+        {
+            write_provenance(&context)?;
+        }
+
+        /* and what went into it, for CI to track build times and artifacts */
+        if config.report.unwrap_or(false) == true
+        {
+            write_report(&plan, &commands, started.elapsed().as_secs_f64(), &context)?;
+        }
+
+        /* and which binaries it carries, for teams to keep an inventory of with each release */
+        if let Some(format) = config.bundle_doc
+        {
+            write_bundle_doc(format, &context)?;
+        }
+        Ok(())
+    }
+}
+
+/* Turn a binary file into a linkable .o object file.
+   the following symbols will be defined pointing to the start and end
+   of the object when it is located in memory, and its size in bytes:
+
+    _binary_leafname_start
+    _binary_leafname_end
+    _binary_leafname_size
+    _binary_leafname_hash
+   
+   where leafname is the leafname of the binary file, and binary is replaced
+   by the configured symbol namespace, if one is set. the _hash symbol's value
+   is the first bytes of the file's SHA-256 digest, truncated to the pointer width.
+   if the binary has a load address and load_address_symbols is set, it is also
+   given as the value of _binary_leafname_load_address
+
+   => binary_path = path to binary file to convert
+      settings   = the binary's settings from the config file, if given as a table
+      context    = build context
+*/
+fn package_binary(binary_path: &String, settings: Option<&IncludeEntry>, mut context: &mut Context) -> Result<(), MasonError>
+{
+    /* generate path to output .o object file for this given binary */
+    let object_file = payload_object_path(binary_path, context);
+    let payload = make_payload_object(binary_path, &object_file, settings, context)?;
+
+    register_object(&object_file, &binary_path, &mut context)?;
+    context.sections.push(payload_section(binary_path));
+    context.payloads.push(payload);
+    Ok(())
+}
+
+/* Turn a binary file into a linkable .o object file at the given path, defining the symbols
+   described for package_binary(), without adding the object to the bundle
+   => binary_path = path to binary file to convert
+      object_file = path of object file to generate
+      settings = the binary's settings from the config file, if any
+      context = build context
+   <= returns a description of the packaged binary */
+fn make_payload_object(binary_path: &String, object_file: &String, settings: Option<&IncludeEntry>, mut context: &mut Context) -> Result<Payload, MasonError>
+{
+    let started = Instant::now();
+    let renamed_prefix = payload_symbol_prefix(binary_path, context);
+
+    /* turn Intel HEX and S-record files into the raw images they describe, noting where those start */
+    let format = image_format(binary_path, settings);
+    let (contents, base_address) = match context.placeholders.contains_key(binary_path) == false && format != ImageFormat::Binary
+    {
+        true =>
+        {
+            let (path, base) = convert_image(binary_path, format, context)?;
+            (path, Some(base))
+        },
+        false => (binary_path.clone(), None)
+    };
+
+    /* make sure the binary is the kind of file it's meant to be before it's transformed, eg compressed */
+    if let Some(expected) = settings.and_then(|s| s.expect_format)
+    {
+        if context.placeholders.contains_key(binary_path) == false
+        {
+            let (format, description) = sniff_payload(&contents, context)?;
+            if format != Some(expected)
+            {
+                return Err(MasonError::config(format!("Binary file {} should be {} but looks like {}", binary_path, expected.name(), description)));
+            }
+        }
+    }
+
+    /* package the binary's prepared contents, if it has transforms, rather than the file itself,
+    or its placeholder as it is if it's optional and missing */
+    let source = match (context.placeholders.get(binary_path).cloned(), settings.and_then(|s| s.transforms.as_ref()))
+    {
+        (Some(placeholder), _) =>
+        {
+            if placeholder == placeholder_path(binary_path, context)
+            {
+                write_empty_placeholder(&placeholder, context)?;
+            }
+            placeholder
+        },
+        (None, Some(transforms)) => apply_transforms(binary_path, &contents, transforms, context)?,
+        (None, None) => contents
+    };
+
+    /* identify this version of the binary by a hash of its contents, truncated to fit the pointer width.
+    if it's unchanged since it was last packaged, in the same way, its object is kept as it is */
+    let load_address = settings.and_then(|s| s.load_address).or(base_address);
+    let section = payload_section(binary_path);
+    let packing = format!("{} {} {} {:?} {} {} {}", &context.object_format, context.target.ptr_width, &section,
+        load_address, context.load_address_symbols, &renamed_prefix, &source);
+    let stamp_file = payload_stamp_path(object_file);
+    let (digest, up_to_date) = payload_up_to_date(&source, object_file, &stamp_file, &packing, context)?;
+    let hash = payload_hash(&digest, context);
+
+    if context.sniff_payloads == true
+    {
+        let (_, description) = sniff_payload(&source, context)?;
+        log_event(&context.host, LogLevel::Info, "payload", format!("{} looks like {}", binary_path, description));
+    }
+
+    /* write the binary's contents into its own read-only section of a new object, so that it can be
+    kept and placed by the generated linker script, and define its symbols alongside them */
+    match up_to_date
+    {
+        true =>
+        {
+            log_event(&context.host, LogLevel::Step, "package", format!("{} is unchanged, keeping its object", binary_path));
+            context.file_times.insert(binary_path.clone(), None);
+        },
+        false =>
+        {
+            write_payload_object(&source, object_file, &section, &renamed_prefix, hash, load_address, context)?;
+            write_payload_stamp(&source, object_file, &stamp_file, &digest, &packing, context);
+            context.file_times.insert(binary_path.clone(), Some(started.elapsed().as_secs_f64()));
+        }
+    }
+
+    /* describe where the contents came from, for the bundle's inventory */
+    let mut origin = match context.placeholders.contains_key(binary_path)
+    {
+        true => format!("placeholder for missing optional file {}", binary_path),
+        false if Path::new(binary_path).starts_with(&context.output_dir) == true => format!("{}, generated by Mason", binary_path),
+        false => binary_path.clone()
+    };
+    if base_address.is_some() == true
+    {
+        origin.push_str(format!(", converted from {}", format.name()).as_str());
+    }
+    if let (false, Some(transforms)) = (context.placeholders.contains_key(binary_path), settings.and_then(|s| s.transforms.as_ref()))
+    {
+        let steps: Vec<String> = transforms.iter().map(|t| t.describe()).collect();
+        origin.push_str(format!(", transformed by {}", steps.join(", ")).as_str());
+    }
+
+    declare_input(&binary_path, &mut context);
+    Ok(Payload
+    {
+        path: binary_path.to_string(),
+        symbol_prefix: renamed_prefix,
+        hash: hash,
+        load_address: load_address,
+        size: file_size_modified(&source, context).map(|(size, _)| size).unwrap_or(0),
+        digest: digest.iter().map(|b| format!("{:02x}", b)).collect(),
+        origin: origin
+    })
+}
+
+/* what a payload object was last packaged from, so that an unchanged binary isn't packaged again */
+#[derive(Serialize, Deserialize)]
+struct PayloadStamp
+{
+    packing: String,     /* settings the object was packaged with, and the path of the contents packaged */
+    size: u64,           /* size of the contents in bytes */
+    modified: u128,      /* when the contents were last modified, in nanoseconds since the Unix epoch */
+    digest: String,      /* SHA-256 digest of the contents, as hex */
+    object_modified: u128 /* when the object was written, so that an object changed since isn't trusted */
+}
+
+/* generate the path of the stamp recording what a payload object was packaged from
+   => object_file = path of the payload's object file
+   <= returns path to the stamp */
+fn payload_stamp_path(object_file: &str) -> String
+{
+    format!("{}.stamp", object_file)
+}
+
+/* give a file's size and when it was last modified, in nanoseconds since the Unix epoch
+   => path = path to file
+      context = build context
+   <= returns size and modification time, or None if they can't be read */
+fn file_size_modified(path: &str, context: &Context) -> Option<(u64, u128)>
+{
+    let metadata = fs::metadata(context.host.path(path)).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_nanos()))
+}
+
+/* Check whether a payload's object was packaged from the same contents, in the same way, as it would be
+   now. If the contents' size and modification time match its stamp, the digest recorded there is used,
+   so that large binaries aren't hashed again. Otherwise the contents are hashed, and the object is still
+   up to date if the digest matches, eg when a transform rewrote the same contents
+   => source = path to the contents to package
+      object_file = path of the payload's object file
+      stamp_file = path of the object's stamp
+      packing = settings the object is to be packaged with
+      context = build context
+   <= returns the contents' SHA-256 digest, and true if the object is up to date, or the failure */
+fn payload_up_to_date(source: &str, object_file: &str, stamp_file: &str, packing: &str, context: &Context) -> Result<(Vec<u8>, bool), MasonError>
+{
+    let stamp: Option<PayloadStamp> = fs::read_to_string(stamp_file).ok().and_then(|s| serde_json::from_str(&s).ok());
+    let object = file_size_modified(object_file, context);
+    let contents = file_size_modified(source, context);
+
+    let stamp = match stamp
+    {
+        Some(stamp) if stamp.packing == packing && object.map(|(_, modified)| modified) == Some(stamp.object_modified) => stamp,
+        _ => return Ok((hash_file(source, context)?, false))
+    };
+
+    let recorded: Option<Vec<u8>> = hex_bytes(&stamp.digest);
+    if let (Some(digest), Some((size, modified))) = (&recorded, contents)
+    {
+        if size == stamp.size && modified == stamp.modified
+        {
+            return Ok((digest.clone(), true));
+        }
+    }
+
+    let digest = hash_file(source, context)?;
+    let unchanged = recorded.as_ref() == Some(&digest);
+    Ok((digest, unchanged))
+}
+
+/* record what a payload object was packaged from, for payload_up_to_date(). The build doesn't depend on
+   the stamp, so failing to write it only means the binary is packaged again next time
+   => source = path to the contents packaged
+      object_file = path of the payload's object file
+      stamp_file = path of the stamp to write
+      digest = SHA-256 digest of the contents
+      packing = settings the object was packaged with
+      context = build context */
+fn write_payload_stamp(source: &str, object_file: &str, stamp_file: &str, digest: &[u8], packing: &str, context: &Context)
+{
+    if let (Some((size, modified)), Some((_, object_modified))) = (file_size_modified(source, context), file_size_modified(object_file, context))
+    {
+        let stamp = PayloadStamp
+        {
+            packing: packing.to_string(),
+            size: size,
+            modified: modified,
+            digest: digest.iter().map(|b| format!("{:02x}", b)).collect(),
+            object_modified: object_modified
+        };
+        let _ = retry(Step::Write, context, || fs::write(stamp_file, serde_json::to_string(&stamp).unwrap()));
+    }
+}
+
+/* generate the path of the file holding a binary file's contents once its transforms are applied.
+   This keeps the binary's leafname, so that tools reading it name it in the same way
+   => binary_path = path to binary file
+      context = build context
+   <= returns path to transformed file */
+fn transformed_path(binary_path: &String, context: &Context) -> String
+{
+    let leafname = file_leafname(binary_path);
+    format!("{}/{}/{}", &context.output_dir, TRANSFORMS_DIR, leafname)
+}
+
+/* check whether a binary file's symbol table is to be packaged alongside it. Placeholders have none
+   => binary_path = path to binary file
+      inputs = files gathered from the config file
+      context = build context
+   <= returns true if its symbol table is to be extracted */
+fn embeds_symbols(binary_path: &String, inputs: &Inputs, context: &Context) -> bool
+{
+    inputs.include_settings.get(binary_path).and_then(|e| e.embed_symbols).unwrap_or(false) == true &&
+        context.placeholders.contains_key(binary_path) == false
+}
+
+/* generate the path of the symbol table extracted from an ELF binary. The blob is named after the
+   binary's leafname, so that its symbols are too, eg _binary_guest_elf_symtab_start
+   => binary_path = path to binary file
+      context = build context
+   <= returns path to the symbol table */
+fn symtab_path(binary_path: &String, context: &Context) -> String
+{
+    let leafname = file_leafname(binary_path);
+    format!("{}/{}/{}.symtab", &context.output_dir, SYMTABS_DIR, leafname)
+}
+
+/* Extract the function and data symbols an ELF binary defines into a compact, address-sorted blob in the
+   target's byte order, in the format described at the top of this file, and write it to symtab_path().
+   The binary's static symbol table is used, or its dynamic one if it has no other
+   => binary_path = path to ELF binary
+      context = build context
+*/
+fn write_symbol_table(binary_path: &String, context: &Context) -> Result<(), MasonError>
+{
+    let contents = match retry(Step::Read, &context, || fs::read(context.host.path(binary_path)))
+    {
+        Ok(c) => c,
+        Err(e) => return Err(MasonError::config(format!("Can't read {} to extract its symbol table: {}", binary_path, e)))
+    };
+
+    let file = match object::read::File::parse(&*contents)
+    {
+        Ok(f) if f.format() == BinaryFormat::Elf => f,
+        _ => return Err(MasonError::config(format!("{} has embed_symbols set but isn't an ELF file", binary_path)))
+    };
+
+    let mut symbols: Vec<(u64, u64, String)> = match file.symbols().next().is_some()
+    {
+        true => file.symbols().collect::<Vec<_>>(),
+        false => file.dynamic_symbols().collect::<Vec<_>>()
+    }
+    .iter()
+    .filter(|sym| sym.is_definition() == true && matches!(sym.kind(), SymbolKind::Text | SymbolKind::Data) == true)
+    .filter_map(|sym| match sym.name()
+    {
+        Ok(name) if name.len() > 0 => Some((sym.address(), sym.size(), name.to_string())),
+        _ => None
+    })
+    .collect();
+    symbols.sort();
+    symbols.dedup();
+
+    /* lay out the entries and the string table they point into, and then the header describing them */
+    let (_, endian) = payload_architecture(context)?;
+    let u16_bytes = |v: u16| if endian == Endianness::Big { v.to_be_bytes() } else { v.to_le_bytes() };
+    let u32_bytes = |v: u32| if endian == Endianness::Big { v.to_be_bytes() } else { v.to_le_bytes() };
+    let u64_bytes = |v: u64| if endian == Endianness::Big { v.to_be_bytes() } else { v.to_le_bytes() };
+
+    let mut entries = Vec::new();
+    let mut strings = Vec::new();
+    for (address, size, name) in symbols.iter()
+    {
+        entries.extend_from_slice(&u64_bytes(*address));
+        entries.extend_from_slice(&u32_bytes((*size).min(u32::MAX as u64) as u32));
+        entries.extend_from_slice(&u32_bytes(strings.len() as u32));
+        strings.extend_from_slice(name.as_bytes());
+        strings.push(0);
+    }
+
+    let mut blob = Vec::new();
+    blob.extend_from_slice(b"MSYM");
+    blob.extend_from_slice(&u16_bytes(SYMTAB_VERSION));
+    blob.extend_from_slice(&u16_bytes(16));
+    blob.extend_from_slice(&u32_bytes(symbols.len() as u32));
+    blob.extend_from_slice(&u32_bytes(strings.len() as u32));
+    blob.extend(entries);
+    blob.extend(strings);
+
+    let output = symtab_path(binary_path, context);
+    let dir = format!("{}/{}", &context.output_dir, SYMTABS_DIR);
+    if let Err(e) = retry(Step::Write, &context, || fs::create_dir_all(&dir).and_then(|_| fs::write(&output, &blob)))
+    {
+        return Err(MasonError::host(format!("Can't write {}'s symbol table to {}: {}", binary_path, &output, e)));
+    }
+    Ok(())
+}
+
+/* Recognise what kind of file a binary is from its magic number, and describe it
+   => path = path to the file to look at
+      context = build context
+   <= returns the kind of file, or None if it's not recognised, and a description of it, eg
+      an ELF 64-bit little-endian executable for machine 243 */
+fn sniff_payload(path: &str, context: &Context) -> Result<(Option<PayloadFormat>, String), MasonError>
+{
+    let read_header = || -> io::Result<Vec<u8>>
+    {
+        let mut header = Vec::new();
+        fs::File::open(context.host.path(path))?.take(64).read_to_end(&mut header)?;
+        Ok(header)
+    };
+
+    let header = match retry(Step::Read, context, read_header)
+    {
+        Ok(h) => h,
+        Err(e) => return Err(MasonError::config(format!("Can't read {} to see what it holds: {}", path, e)))
+    };
+
+    let be32 = |offset: usize| header.get(offset..offset + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]])).unwrap_or(0);
+    Ok(match header.as_slice()
+    {
+        [] => (None, String::from("an empty file")),
+        [0x7f, b'E', b'L', b'F', class, data, ..] if header.len() >= 20 =>
+        {
+            let machine = match data
+            {
+                2 => u16::from_be_bytes([header[18], header[19]]),
+                _ => u16::from_le_bytes([header[18], header[19]])
+            };
+            let kind = match (data, header[16], header[17])
+            {
+                (2, 0, 1) | (1, 1, 0) => "relocatable object",
+                (2, 0, 2) | (1, 2, 0) => "executable",
+                (2, 0, 3) | (1, 3, 0) => "shared object",
+                _ => "file"
+            };
+            let width = if *class == 2 { 64 } else { 32 };
+            let endian = if *data == 2 { "big" } else { "little" };
+            (Some(PayloadFormat::Elf), format!("an ELF {}-bit {}-endian {} for machine {}", width, endian, kind, machine))
+        },
+        [0xd0, 0x0d, 0xfe, 0xed, ..] =>
+            (Some(PayloadFormat::Dtb), format!("a device tree blob, version {}, of {} bytes", be32(20), be32(4))),
+        [0xed, 0xfe, 0x0d, 0xd0, ..] => (None, String::from("a device tree blob with its bytes swapped")),
+        [0x1f, 0x8b, ..] => (Some(PayloadFormat::Gzip), String::from(PayloadFormat::Gzip.name())),
+        [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => (Some(PayloadFormat::Xz), String::from(PayloadFormat::Xz.name())),
+        [b'B', b'Z', b'h', ..] => (Some(PayloadFormat::Bzip2), String::from(PayloadFormat::Bzip2.name())),
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => (Some(PayloadFormat::Zstd), String::from(PayloadFormat::Zstd.name())),
+        [0x04, 0x22, 0x4d, 0x18, ..] => (Some(PayloadFormat::Lz4), String::from(PayloadFormat::Lz4.name())),
+        [b'0', b'7', b'0', b'7', b'0', b'1' | b'2' | b'7', ..] => (Some(PayloadFormat::Cpio), String::from(PayloadFormat::Cpio.name())),
+        [0x27, 0x05, 0x19, 0x56, ..] => (Some(PayloadFormat::Uimage), String::from(PayloadFormat::Uimage.name())),
+        [0xef, 0xbb, 0xbf, ..] => (Some(PayloadFormat::Text), String::from("UTF-8 text with a byte-order mark")),
+        [0xff, 0xfe, ..] => (Some(PayloadFormat::Text), String::from("UTF-16 little-endian text with a byte-order mark")),
+        [0xfe, 0xff, ..] => (Some(PayloadFormat::Text), String::from("UTF-16 big-endian text with a byte-order mark")),
+        _ => (None, format!("an unrecognised file starting {}",
+            header.iter().take(4).map(|b| format!("{:02x}", b)).collect::<Vec<String>>().join(" ")))
+    })
+}
+
+/* generate the path of the file holding the raw image an Intel HEX or S-record file is converted to.
+   As for transformed_path(), this keeps the binary's leafname
+   => binary_path = path to binary file
+      context = build context
+   <= returns path to converted file */
+fn converted_path(binary_path: &String, context: &Context) -> String
+{
+    let leafname = file_leafname(binary_path);
+    format!("{}/{}/{}", &context.output_dir, CONVERTED_DIR, leafname)
+}
+
+/* work out how a binary file's contents are encoded, from its settings or else its extension
+   => binary_path = path to binary file
+      settings = the binary's settings from the config file, if any
+   <= returns the file's format */
+fn image_format(binary_path: &str, settings: Option<&IncludeEntry>) -> ImageFormat
+{
+    if let Some(format) = settings.and_then(|s| s.format)
+    {
+        return format;
+    }
+
+    let extension = Path::new(binary_path).extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or(String::new());
+    match extension.as_str()
+    {
+        "hex" | "ihex" | "ihx" => ImageFormat::Ihex,
+        "srec" | "s19" | "s28" | "s37" | "mot" => ImageFormat::Srec,
+        _ => ImageFormat::Binary
+    }
+}
+
+/* Convert an Intel HEX or S-record file into the raw image it describes, as objcopy -O binary would,
+   writing the image to the converted directory in OUT_DIR. Bail out if the file can't be read or parsed
+   => binary_path = path to binary file
+      format = the file's format
+      context = build context
+   <= returns the path of the raw image and the address of its first byte */
+fn convert_image(binary_path: &String, format: ImageFormat, context: &Context) -> Result<(String, u64), MasonError>
+{
+    log_event(&context.host, LogLevel::Step, "convert", format!("converting {} from {}", binary_path, format.name()));
+
+    let text = match retry(Step::Read, &context, || fs::read_to_string(context.host.path(binary_path)))
+    {
+        Ok(t) => t,
+        Err(e) => return Err(MasonError::config(format!("Can't read {} file {}: {}", format.name(), binary_path, e)))
+    };
+
+    let records = match format
+    {
+        ImageFormat::Ihex => parse_ihex(&text),
+        ImageFormat::Srec => parse_srec(&text),
+        ImageFormat::Binary => unreachable!()
+    };
+
+    let (base, image) = match records.and_then(image_from_records)
+    {
+        Ok(i) => i,
+        Err(e) => return Err(MasonError::config(format!("Can't convert {} file {}: {}", format.name(), binary_path, e)))
+    };
+
+    let output = converted_path(binary_path, context);
+    let dir = format!("{}/{}", &context.output_dir, CONVERTED_DIR);
+    if let Err(e) = retry(Step::Write, &context, || fs::create_dir_all(&dir).and_then(|_| fs::write(&output, &image)))
+    {
+        return Err(MasonError::host(format!("Can't write {}'s raw image to {}: {}", binary_path, &output, e)));
+    }
+
+    Ok((output, base))
+}
+
+/* read the data records of an Intel HEX file, allowing for its extended segment and linear address records,
+   and checking each record's checksum
+   => text = contents of the file
+   <= returns the address and bytes of each data record, in the file's order, or a description of the error */
+fn parse_ihex(text: &str) -> Result<Vec<(u64, Vec<u8>)>, String>
+{
+    let mut records = Vec::new();
+    let mut base: u64 = 0;
+    for (number, line) in text.lines().enumerate()
+    {
+        let line = line.trim();
+        if line.len() == 0
+        {
+            continue;
+        }
+
+        /* each record is :, then a byte count, a 16-bit address, a record type, the data, and a checksum */
+        let bytes = match line.strip_prefix(':').and_then(hex_bytes)
+        {
+            Some(b) if b.len() >= 5 && b.len() == b[0] as usize + 5 => b,
+            _ => return Err(format!("line {} isn't a valid Intel HEX record", number + 1))
+        };
+
+        if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0
+        {
+            return Err(format!("line {} has a bad checksum", number + 1));
+        }
+
+        let address = u16::from_be_bytes([bytes[1], bytes[2]]) as u64;
+        let data = &bytes[4..bytes.len() - 1];
+        match bytes[3]
+        {
+            0x00 => records.push((base + address, data.to_vec())),
+            0x01 => break,
+            0x02 if data.len() == 2 => base = (u16::from_be_bytes([data[0], data[1]]) as u64) << 4,
+            0x04 if data.len() == 2 => base = (u16::from_be_bytes([data[0], data[1]]) as u64) << 16,
+            0x03 | 0x05 => (), /* start addresses, which don't affect the image */
+            kind => return Err(format!("line {} has an unsupported record type {:02x}", number + 1, kind))
+        }
+    }
+    Ok(records)
+}
+
+/* read the data records of a Motorola S-record file, checking each record's checksum
+   => text = contents of the file
+   <= returns the address and bytes of each data record, in the file's order, or a description of the error */
+fn parse_srec(text: &str) -> Result<Vec<(u64, Vec<u8>)>, String>
+{
+    let mut records = Vec::new();
+    for (number, line) in text.lines().enumerate()
+    {
+        let line = line.trim();
+        if line.len() == 0
+        {
+            continue;
+        }
+
+        /* each record is S, then its type, a byte count, an address, the data, and a checksum. the type
+        sets the address's width. types 1 to 3 hold data, and the rest a header, counts, or a start address */
+        let kind = line.strip_prefix('S').and_then(|rest| rest.chars().next()).unwrap_or(' ');
+        let width = match kind
+        {
+            '0' | '1' | '5' | '9' => 2,
+            '2' | '6' | '8' => 3,
+            '3' | '7' => 4,
+            _ => return Err(format!("line {} isn't a valid S-record", number + 1))
+        };
+
+        let bytes = match hex_bytes(&line[2..])
+        {
+            Some(b) if b.len() >= width + 2 && b.len() == b[0] as usize + 1 => b,
+            _ => return Err(format!("line {} isn't a valid S-record", number + 1))
+        };
+
+        if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0xff
+        {
+            return Err(format!("line {} has a bad checksum", number + 1));
+        }
+
+        if matches!(kind, '1' | '2' | '3') == true
+        {
+            let address = bytes[1..width + 1].iter().fold(0u64, |address, b| (address << 8) | *b as u64);
+            records.push((address, bytes[width + 1..bytes.len() - 1].to_vec()));
+        }
+    }
+    Ok(records)
+}
+
+/* turn a string of hex digit pairs into the bytes they give
+   => digits = hex digits, eg 0a1b
+   <= returns the bytes, or None if the digits aren't all pairs of hex digits */
+fn hex_bytes(digits: &str) -> Option<Vec<u8>>
+{
+    match digits.len() % 2
+    {
+        0 => digits.as_bytes().chunks(2)
+            .map(|pair| std::str::from_utf8(pair).ok().and_then(|p| u8::from_str_radix(p, 16).ok()))
+            .collect(),
+        _ => None
+    }
+}
+
+/* lay out data records as one contiguous image, filling the gaps between them with zeroes
+   => records = address and bytes of each data record
+   <= returns the address of the image's first byte and its contents, or a description of the error */
+fn image_from_records(mut records: Vec<(u64, Vec<u8>)>) -> Result<(u64, Vec<u8>), String>
+{
+    records.retain(|(_, data)| data.len() > 0);
+    records.sort_by_key(|(address, _)| *address);
+
+    let base = match records.first()
+    {
+        Some((address, _)) => *address,
+        None => return Err(String::from("it holds no data"))
+    };
+    let end = records.iter().map(|(address, data)| address + data.len() as u64).max().unwrap();
+    if end - base > MAX_IMAGE_SPAN
+    {
+        return Err(format!("its data spans 0x{:x} to 0x{:x}, more than the {} bytes Mason will convert", base, end, MAX_IMAGE_SPAN));
+    }
+
+    let mut image = vec![0; (end - base) as usize];
+    let mut covered = base;
+    for (address, data) in records.iter()
+    {
+        if *address < covered
+        {
+            return Err(format!("its records overlap at 0x{:x}", address));
+        }
+        let offset = (address - base) as usize;
+        image[offset..offset + data.len()].copy_from_slice(data);
+        covered = address + data.len() as u64;
+    }
+
+    Ok((base, image))
+}
+
+/* generate the path of the empty file packaged in place of a missing optional binary file
+   => binary_path = path to binary file
+      context = build context
+   <= returns path to placeholder file */
+fn placeholder_path(binary_path: &String, context: &Context) -> String
+{
+    let leafname = file_leafname(binary_path);
+    format!("{}/{}/{}", &context.output_dir, PLACEHOLDERS_DIR, leafname)
+}
+
+/* create an empty placeholder file for a missing optional binary file
+   => path = path of placeholder file, from placeholder_path()
+      context = build context */
+fn write_empty_placeholder(path: &String, context: &Context) -> Result<(), MasonError>
+{
+    let dir = format!("{}/{}", &context.output_dir, PLACEHOLDERS_DIR);
+    if let Err(e) = retry(Step::Write, &context, || fs::create_dir_all(&dir).and_then(|_| fs::write(path, &[])))
+    {
+        return Err(MasonError::host(format!("Can't write placeholder {}: {}", path, e)));
+    }
+    Ok(())
+}
+
+/* give the arguments to pass to a compression tool to compress a file to its standard output
+   => tool = name of tool
+   <= returns the arguments, or None if the tool isn't supported */
+fn compressor_args(tool: &str) -> Option<Vec<&'static str>>
+{
+    /* -n stops gzip recording the file's name and timestamp, so the output only changes with its input */
+    match tool
+    {
+        "gzip" => Some(vec!["-n", "-9", "-c"]),
+        "xz" => Some(vec!["-9", "-c"]),
+        "bzip2" => Some(vec!["-9", "-c"]),
+        "zstd" => Some(vec!["-q", "-19", "-c"]),
+        _ => None
+    }
+}
+
+/* check whether a binary file is compressed with the zstd dictionary shared by the bundle's binaries
+   => binary_path = path to binary file
+      inputs = files gathered from the config file
+   <= returns true if one of its transforms uses the dictionary */
+fn uses_zstd_dictionary(binary_path: &String, inputs: &Inputs) -> bool
+{
+    inputs.include_settings.get(binary_path).and_then(|e| e.transforms.as_ref()).map(|transforms| transforms.iter()
+        .any(|t| matches!(t, Transform::Compress { dictionary: Some(true), .. }))).unwrap_or(false)
+}
+
+/* Train the zstd dictionary shared by binaries compressed with it on their contents, before they're transformed,
+   and write it to the output directory, to be packaged once for a runtime decompressor to use with all of them.
+   Similar binaries, eg several guest kernels, then compress better together than they do on their own.
+   Optional binaries that are missing aren't sampled. If none are left, the dictionary is left empty
+   => members = binary files compressed with the dictionary
+      max_size = largest dictionary to train in bytes, or None for zstd's default
+      inputs = files gathered from the config file
+      context = build context */
+fn train_zstd_dictionary(members: &Vec<&String>, max_size: Option<u64>, inputs: &Inputs, context: &Context) -> Result<(), MasonError>
+{
+    let output = format!("{}/{}", &context.output_dir, ZSTD_DICTIONARY_FILE);
+
+    /* Intel HEX and S-record files are sampled as the images they describe, which is what's compressed */
+    let mut samples = Vec::new();
+    for binary in members.iter().filter(|f| context.placeholders.contains_key(**f) == false)
+    {
+        samples.push(match image_format(binary, inputs.include_settings.get(*binary))
+        {
+            ImageFormat::Binary => binary.to_string(),
+            format => convert_image(binary, format, context)?.0
+        });
+    }
+
+    if samples.len() == 0
+    {
+        if let Err(e) = retry(Step::Write, context, || fs::write(&output, &[]))
+        {
+            return Err(MasonError::host(format!("Cannot write zstd dictionary {}: {}", &output, e)));
+        }
+        return Ok(());
+    }
+
+    let mut command = Command::new("zstd");
+    command.arg("--train").arg("-q").arg("-f").arg(format!("-B{}", ZSTD_TRAINING_BLOCK_SIZE));
+    if let Some(size) = max_size
+    {
+        command.arg(format!("--maxdict={}", size));
+    }
+    command.arg("-o").arg(&output).args(&samples);
+
+    let result = run_tool(&mut command, format!("Couldn't run zstd to train {}", ZSTD_DICTIONARY_FILE), context)?;
+    if result.status.success() == false
+    {
+        return Err(MasonError::tool_failure(format!("Couldn't train {} on {}: {}", ZSTD_DICTIONARY_FILE,
+            samples.join(", "), String::from_utf8_lossy(&result.stderr).trim())));
+    }
+    Ok(())
+}
+
+/* Prepare a binary file's contents for packaging by applying its transforms in order, each working on the
+   previous one's output. Each step's output is written to the transforms directory in OUT_DIR
+   => binary_path = path to binary file
+      contents = path to the file holding the binary's contents, which is its raw image if it was converted
+      transforms = steps to apply
+      context = build context
+   <= returns path to the file holding the transformed contents */
+fn apply_transforms(binary_path: &String, contents: &String, transforms: &Vec<Transform>, context: &Context) -> Result<String, MasonError>
+{
+    let output = transformed_path(binary_path, context);
+    let dir = format!("{}/{}", &context.output_dir, TRANSFORMS_DIR);
+    if let Err(e) = retry(Step::Write, &context, || fs::create_dir_all(&dir))
+    {
+        return Err(MasonError::host(format!("Cannot create directory {} for transformed binaries: {}", &dir, e)));
+    }
+
+    let mut input = contents.clone();
+    for (step, transform) in transforms.iter().enumerate()
+    {
+        let result = if step + 1 == transforms.len() { output.clone() } else { format!("{}.{}", &output, step) };
+        let (name, tool) = match transform
+        {
+            Transform::Strip =>
+            {
+                ("strip", Some(run_tool(Command::new(&context.oc_exec).args(&context.tool_flags.objcopy).arg("--strip-all").arg(&input).arg(&result),
+                    format!("Couldn't run command to strip {}", binary_path), &context)?))
+            },
+            Transform::Objcopy { output_target, only_sections, remove_sections } =>
+            {
+                let mut command = Command::new(&context.oc_exec);
+                command.args(&context.tool_flags.objcopy)
+                    .arg("-O")
+                    .arg(output_target.as_deref().unwrap_or("binary"));
+                for section in only_sections.iter().flatten()
+                {
+                    command.arg("-j").arg(section);
+                }
+                for section in remove_sections.iter().flatten()
+                {
+                    command.arg("-R").arg(section);
+                }
+                ("objcopy", Some(run_tool(command.arg(&input).arg(&result),
+                    format!("Couldn't run objcopy to convert {}", binary_path), &context)?))
+            },
+            Transform::Compress { tool, dictionary } =>
+            {
+                let tool = tool.as_deref().unwrap_or("gzip");
+                let mut command = Command::new(tool);
+                command.args(compressor_args(tool).unwrap());
+                if dictionary.unwrap_or(false) == true
+                {
+                    command.arg("-D").arg(format!("{}/{}", &context.output_dir, ZSTD_DICTIONARY_FILE));
+                }
+                let compressed = run_tool(command.arg(&input),
+                    format!("Couldn't run {} to compress {}", tool, binary_path), &context)?;
+
+                if compressed.status.success() == true
+                {
+                    if let Err(e) = retry(Step::Write, &context, || fs::write(&result, &compressed.stdout))
+                    {
+                        return Err(MasonError::host(format!("Cannot write compressed {}: {}", &result, e)));
+                    }
+                }
+                ("compress", Some(compressed))
+            },
+            Transform::Sign { command } =>
+            {
+                let args: Vec<String> = command.iter().map(|a| a.replace("{input}", &input).replace("{output}", &result)).collect();
+                ("sign", Some(run_tool(Command::new(&args[0]).args(&args[1..]),
+                    format!("Couldn't run command to sign {}", binary_path), &context)?))
+            },
+            Transform::Pad { align, size, fill } =>
+            {
+                /* copy the contents and append the padding in chunks, rather than holding it all in memory */
+                let mut length = match retry(Step::Read, &context, || fs::metadata(context.host.path(&input)))
+                {
+                    Ok(m) => m.len(),
+                    Err(e) => return Err(MasonError::config(format!("Can't read {} to pad it: {}", &input, e)))
+                };
+                let unpadded = length;
+
+                if let Some(size) = size
+                {
+                    if length > *size
+                    {
+                        return Err(MasonError::config(format!("{} is {} bytes once transformed, more than its pad size of {} bytes", binary_path, length, size)));
+                    }
+                    length = *size;
+                }
+
+                if let Some(align) = align
+                {
+                    length = (length + align - 1) & !(align - 1);
+                }
+
+                let chunk = vec![fill.unwrap_or(0); STREAM_CHUNK_SIZE];
+                let pad = || -> io::Result<()>
+                {
+                    fs::copy(context.host.path(&input), &result)?;
+                    let mut file = fs::OpenOptions::new().append(true).open(&result)?;
+                    let mut remaining = length - unpadded;
+                    while remaining > 0
+                    {
+                        let count = remaining.min(STREAM_CHUNK_SIZE as u64);
+                        file.write_all(&chunk[..count as usize])?;
+                        remaining = remaining - count;
+                    }
+                    Ok(())
+                };
+
+                if let Err(e) = retry(Step::Write, &context, pad)
+                {
+                    return Err(MasonError::host(format!("Cannot write padded {}: {}", &result, e)));
+                }
+                ("pad", None)
+            }
+        };
+
+        if let Some(tool) = tool
+        {
+            if tool.status.success() != true
+            {
+                return Err(MasonError::tool_failure(format!("Transform {} of {} failed:\n{}\n{}",
+                    name, binary_path, String::from_utf8_lossy(&tool.stdout), String::from_utf8_lossy(&tool.stderr))));
+            }
+        }
+
+        input = result;
+    }
+
+    Ok(input)
+}
+
+/* give the leafname of an input file. The config file's inputs are checked to have one when it's
+   parsed, so the path itself is only given for paths that don't, eg / or .., to avoid panicking
+   => path = path to file
+   <= returns the file's leafname, eg fw.bin for blobs/fw.bin */
+fn file_leafname(path: &str) -> String
+{
+    match Path::new(path).file_name()
+    {
+        Some(leafname) => leafname.to_string_lossy().into_owned(),
+        None => String::from(path)
+    }
+}
+
+/* generate the path of the .o object file a binary file will be packaged into
+   => binary_path = path to binary file
+      context = build context
+   <= returns path to object file */
+fn payload_object_path(binary_path: &String, context: &Context) -> String
+{
+    format!("{}/{}.o", &context.output_dir, object_name(binary_path, PAYLOAD_OBJECT_NAME, context))
+}
+
+/* name the object generated from an input file, using the config file's object_name template, or
+   else the given default. {dir} is replaced by the file's directory, with each run of characters
+   other than letters and digits replaced by _, {stem} by its leafname without its extension,
+   {ext} by its extension, and {name} by its whole leafname
+   => path = path to source or binary file
+      default = template to use if the config file doesn't give one
+      context = build context
+   <= returns the object's name, without .o */
+fn object_name(path: &str, default: &str, context: &Context) -> String
+{
+    let path = Path::new(path);
+    let part = |p: Option<&std::ffi::OsStr>| p.map(|p| p.to_string_lossy().to_string()).unwrap_or(String::new());
+    let dir = Regex::new(r"[^A-Za-z0-9]+").unwrap()
+        .replace_all(&part(path.parent().map(|p| p.as_os_str())), "_")
+        .trim_matches('_')
+        .to_string();
+
+    context.object_name.as_deref().unwrap_or(default)
+        .replace("{dir}", &dir)
+        .replace("{stem}", &part(path.file_stem()))
+        .replace("{ext}", &part(path.extension()))
+        .replace("{name}", &part(path.file_name()))
+}
+
+/* generate the name of the section a packaged binary file's contents are placed in
+   => binary_path = path to binary file
+   <= returns the section name, eg .rodata.mason.leafname */
+fn payload_section(binary_path: &String) -> String
+{
+    let leafname = file_leafname(binary_path);
+    format!(".rodata.mason.{}", leafname.replace(".", "_"))
+}
+
+/* generate the prefix of the symbols defined for a packaged binary file
+   => binary_path = path to binary file
+      context = build context
+   <= returns the prefix, eg _binary_leafname_ for _binary_leafname_start */
+fn payload_symbol_prefix(binary_path: &String, context: &Context) -> String
+{
+    payload_symbols(binary_path, &context.symbol_namespace).prefix
+}
+
+/* names Mason gives to the symbols and Rust static it generates for a packaged binary file */
+pub struct PayloadSymbols
+{
+    pub prefix: String,       /* prefix shared by the symbols, eg _binary_fw_bin_ */
+    pub start: String,        /* symbol marking the first byte of the contents, eg _binary_fw_bin_start */
+    pub end: String,          /* symbol marking the first byte after the contents */
+    pub size: String,         /* absolute symbol whose value is the size of the contents in bytes */
+    pub hash: String,         /* absolute symbol whose value identifies the contents */
+    pub load_address: String, /* absolute symbol whose value is the load_address, if load_address_symbols is true */
+    pub absent: String,       /* symbol defined for assembly code instead if the file is optional and missing */
+    pub rust_static: String   /* name of the MasonBlob in mason.rs, eg FW_BIN */
+}
+
+/* give the names of the symbols Mason will generate for a binary file, with the default symbol
+   namespace, so that build logic can generate code referring to them without copying Mason's
+   naming rules. Use Build::symbols_for() to take the config file's symbol_namespace into account
+   => binary_path = path to binary file, as listed in the config file
+   <= returns the names of the file's symbols */
+pub fn symbols_for(binary_path: &str) -> PayloadSymbols
+{
+    payload_symbols(binary_path, DEFAULT_SYMBOL_NAMESPACE)
+}
+
+/* give the names of the symbols generated for a binary file in the given namespace
+   => binary_path = path to binary file
+      namespace = symbol namespace, eg binary
+   <= returns the names of the file's symbols */
+fn payload_symbols(binary_path: &str, namespace: &str) -> PayloadSymbols
+{
+    let leafname = file_leafname(binary_path);
+    let prefix = format!("_{}_{}_", namespace, leafname.replace(".", "_"));
+    PayloadSymbols
+    {
+        start: format!("{}start", &prefix),
+        end: format!("{}end", &prefix),
+        size: format!("{}size", &prefix),
+        hash: format!("{}hash", &prefix),
+        load_address: format!("{}load_address", &prefix),
+        absent: format!("{}absent", &prefix),
+        rust_static: rust_identifier(&leafname).to_ascii_uppercase(),
+        prefix: prefix
+    }
+}
+
+/* make a name usable as a Rust identifier, replacing each character other than a letter, digit, or _
+   with _, and prepending _ if it starts with a digit
+   => name = name to convert, eg a leafname or symbol
+   <= returns the identifier, eg _1_bin for 1.bin */
+fn rust_identifier(name: &str) -> String
+{
+    let identifier: String = name.chars().map(|c| if c.is_ascii_alphanumeric() == true || c == '_' { c } else { '_' }).collect();
+    match identifier.starts_with(|c: char| c.is_ascii_digit()) == true
+    {
+        true => format!("_{}", identifier),
+        false => identifier
+    }
+}
+
+/* Calculate the SHA-256 digest of a file's contents, reading it in chunks
+   => path = path to file to hash
+      context = build context
+   <= returns the file's digest
+*/
+fn hash_file(path: &str, context: &Context) -> Result<Vec<u8>, MasonError>
+{
+    let mut file = match retry(Step::Read, context, || fs::File::open(context.host.path(path)))
+    {
+        Ok(f) => f,
+        Err(e) => return Err(MasonError::config(format!("Can't open {} to hash its contents: {}", path, e)))
+    };
+
+    let mut hasher = Sha256::new();
+    if let Err(e) = io::copy(&mut file, &mut hasher)
+    {
+        return Err(MasonError::host(format!("Can't read {} to hash its contents: {}", path, e)));
+    }
+
+    Ok(hasher.finalize().to_vec())
+}
+
+/* Declare a file as an input of the build, so that cargo reruns Mason if it changes
+   => path = path to file read by the build
+      context = build context
+*/
+fn declare_input(path: &str, context: &mut Context)
+{
+    /* files Mason generates itself, such as bootloader stages, aren't inputs */
+    let absolute = absolute_path(path, &context.project_dir);
+    if absolute.starts_with(absolute_path(&context.output_dir, &context.project_dir)) == true
+    {
+        return;
+    }
+
+    println!("cargo:rerun-if-changed={}", path);
+    context.inputs.insert(absolute);
+}
+
+/* Resolve a path to an absolute path, without requiring it to exist
+   => path = path to resolve
+      base = directory relative paths are relative to
+   <= returns the path, as an absolute path with symbolic links resolved if possible
+*/
+fn absolute_path(path: &str, base: &str) -> PathBuf
+{
+    let path = Path::new(base).join(path);
+    match fs::canonicalize(&path)
+    {
+        Ok(p) => p,
+        Err(_) => path
+    }
+}
+
+/* Bail out if any tool the plan will run can't be found or isn't executable, naming each missing tool and
+   the package likely to provide it, so that a missing toolchain is reported before anything is built,
+   rather than when the first command using it fails
+   => plan = plan to be carried out
+      config = parsed config file
+      inputs = files gathered from the config file
+      context = build context
+*/
+fn check_tools_present(plan: &Plan, config: &Config, inputs: &Inputs, context: &Context) -> Result<(), MasonError>
+{
+    let assemblers: Vec<Assembler> = plan.sources.iter().filter_map(|s| source_assembler(s, context)).collect();
+    let objcopied = inputs.include_settings.values()
+        .any(|s| s.transforms.iter().flatten().any(|t| matches!(t, Transform::Strip | Transform::Objcopy { .. })) == true);
+    let fixups = config.symbol_conflicts.iter().flatten().any(|c| c.policy != ConflictPolicy::Error);
+    let stages = plan.stages.len() > 0;
+
+    /* each tool, the environment variable overriding it, and whether the plan runs it */
+    let nasm = String::from(NASM_EXEC);
+    let cpp = String::from(CPP_EXEC);
+    let tools = [
+        (&context.as_exec, "MASON_AS", stages == true || assemblers.iter().any(|a| *a != Assembler::Nasm) == true),
+        (&nasm, "", assemblers.contains(&Assembler::Nasm)),
+        (&cpp, "", assemblers.contains(&Assembler::CppGas)),
+        (&context.ar_exec, "MASON_AR", context.builtin_ar == false || plan.lib_files.len() > 0),
+        (&context.ld_exec, "MASON_LD", stages),
+        (&context.oc_exec, "MASON_OBJCOPY", stages == true || objcopied == true || fixups == true),
+        (&context.nm_exec, "MASON_NM", scans_symbols(plan, config) == true || context.source_map == true)
+    ];
+
+    let mut missing = Vec::new();
+    let mut remedies = Vec::new();
+    for (tool, var, needed) in tools.iter()
+    {
+        let found = match tool.contains('/')
+        {
+            true => Some(PathBuf::from(tool)),
+            false => find_in_path(tool, &context.host)
+        };
+
+        if *needed == true && found.map_or(false, |path| is_executable(&path)) == false
+        {
+            missing.push(tool.to_string());
+            let remedy = match context.host.var(var).map(|v| v.len() > 0).unwrap_or(false)
+            {
+                true => format!("check {}, which names {}", var, tool),
+                false => format!("install {}", tool_package(tool, context))
+            };
+            if remedies.contains(&remedy) == false
+            {
+                remedies.push(remedy);
+            }
+        }
+    }
+
+    if missing.len() > 0
+    {
+        return Err(MasonError::missing_tool(format!("Can't find {} to build for target '{}': {}",
+            missing.join(", "), context.host.var("TARGET").unwrap_or(""), remedies.join("; "))));
+    }
+    Ok(())
+}
+
+/* Bail out if the target's assembler is older or newer than the versions of binutils the build supports, as
+   set by min_version and max_version, eg as binutils 2.38 split the Zicsr extension out of RISC-V's base ISA.
+   The version is taken from the first line of as --version, eg GNU assembler (GNU Binutils) 2.38
+   => context = build context
+*/
+fn check_binutils_version(context: &Context) -> Result<(), MasonError>
+{
+    let range = &context.binutils_versions;
+    if range.min.is_none() == true && range.max.is_none() == true
+    {
+        return Ok(());
+    }
+
+    let result = run_tool(Command::new(&context.as_exec).arg("--version"),
+        format!("Failed to execute command to check the version of {}", &context.as_exec), context)?;
+
+    let output = String::from_utf8_lossy(&result.stdout);
+    let line = output.lines().next().unwrap_or("");
+    let version = match tool_version(line)
+    {
+        Some(v) => v,
+        None => return Err(MasonError::missing_tool(format!("Can't find the version of {} in its --version output: {}", &context.as_exec, line)))
+    };
+    log_event(&context.host, LogLevel::Step, "toolchain", format!("{} is binutils {}", &context.as_exec, version_string(&version)));
+
+    if let Some(min) = &range.min
+    {
+        if version_cmp(&version, min, version.len().max(min.len())) == std::cmp::Ordering::Less
+        {
+            return Err(MasonError::missing_tool(format!("{} is binutils {}, which is too old: the config file's min_version is {}",
+                &context.as_exec, version_string(&version), version_string(min))));
+        }
+    }
+
+    if let Some(max) = &range.max
+    {
+        if version_cmp(&version, max, max.len()) == std::cmp::Ordering::Greater
+        {
+            return Err(MasonError::missing_tool(format!("{} is binutils {}, which is too new: the config file's max_version is {}",
+                &context.as_exec, version_string(&version), version_string(max))));
+        }
+    }
+    Ok(())
+}
+
+/* find a tool's version in the first line of its --version output. Distributions word this differently,
+   eg GNU assembler version 2.30-123.el8, or GNU assembler (Arm GNU Toolchain 13.2.rel1) 2.41.0.20231009,
+   so the number following 'version' is used if there is one, or else the line's last number
+   => line = first line of the tool's --version output
+   <= returns the version's numbered parts, or None if there's no version in the line */
+fn tool_version(line: &str) -> Option<Vec<u32>>
+{
+    let number = Regex::new(r"\d+(\.\d+)+").unwrap();
+    let found = match Regex::new(r"version (\d+(\.\d+)+)").unwrap().captures(line)
+    {
+        Some(caps) => caps.get(1).map(|m| m.as_str()),
+        None => number.find_iter(line).last().map(|m| m.as_str())
+    };
+
+    found.and_then(parse_version)
+}
+
+/* turn a version string into its numbered parts
+   => version = version to parse, eg 2.38.1
+   <= returns its parts, eg [2, 38, 1], or None if it isn't a dotted list of numbers */
+fn parse_version(version: &str) -> Option<Vec<u32>>
+{
+    version.trim().split('.').map(|part| part.parse::<u32>().ok()).collect()
+}
+
+/* compare two versions as far as a given number of parts, treating missing parts as zero
+   => a, b = versions to compare
+      parts = number of parts to compare
+   <= returns how a is ordered relative to b */
+fn version_cmp(a: &[u32], b: &[u32], parts: usize) -> std::cmp::Ordering
+{
+    (0..parts).map(|i| a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0)))
+        .find(|order| *order != std::cmp::Ordering::Equal)
+        .unwrap_or(std::cmp::Ordering::Equal)
+}
+
+/* give a version as it's usually written
+   => version = version's numbered parts
+   <= returns the version, eg 2.38 */
+fn version_string(version: &[u32]) -> String
+{
+    version.iter().map(|part| part.to_string()).collect::<Vec<String>>().join(".")
+}
+
+/* check whether a file can be run
+   => path = path to file
+   <= returns true if it's a file with execute permission, or just a file on hosts without permissions */
+fn is_executable(path: &Path) -> bool
+{
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path).map(|m| m.is_file() == true && m.permissions().mode() & 0o111 != 0).unwrap_or(false)
+    }
+
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/* name the package likely to provide a missing tool, for the error reporting it
+   => tool = tool's executable, eg riscv64-linux-gnu-as
+      context = build context
+   <= returns a description of the package, eg binutils-riscv64-linux-gnu on Debian and Ubuntu */
+fn tool_package(tool: &str, context: &Context) -> String
+{
+    if tool == NASM_EXEC || tool == CPP_EXEC
+    {
+        return format!("the host's {} package", tool);
+    }
+
+    let name = Path::new(tool).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or(tool.to_string());
+    match context.backend
+    {
+        _ if name == "clang" => String::from("the host's clang package"),
+        Backend::Llvm => String::from("the host's llvm and lld packages"),
+        Backend::Rustup => String::from("the llvm-tools component, with rustup component add llvm-tools"),
+        Backend::Gnu => match context.target.gnu_prefix.as_str()
+        {
+            "" => String::from("the host's binutils package"),
+            prefix if cfg!(target_os = "macos") == true => format!("{}-binutils, with brew install {}-binutils", prefix, prefix),
+            prefix if cfg!(target_os = "windows") == true => format!("{} binutils, with MSYS2's pacman, eg pacman -S mingw-w64-ucrt-x86_64-{}-binutils", prefix, prefix),
+            prefix => format!("binutils-{} on Debian and Ubuntu, or your distribution's {} binutils", prefix, prefix)
+        }
+    }
+}
+
+/* Bail out if a GNU ld or objcopy executable doesn't support the object format used to link stages.
+   This catches the host's tools being used for a cross target, which would otherwise produce objects that
+   only fail to link much later. The supported formats are taken from the tool's --help output
+   => tool = path to tool executable
+      context = build context
+*/
+fn check_tool_supports_format(tool: &String, context: &Context) -> Result<(), MasonError>
+{
+    let result = run_tool(Command::new(tool).arg("--help"),
+        format!("Failed to execute command to check the object formats supported by {}", tool), &context)?;
+
+    let help = String::from_utf8_lossy(&result.stdout);
+    let supported = help.lines()
+        .filter_map(|line| line.splitn(2, "supported targets:").nth(1))
+        .any(|formats| formats.split_whitespace().any(|format| format == context.object_format));
+
+    if supported == false
+    {
+        return Err(MasonError::missing_tool(format!("{} does not support the target's object format {}. Is it the host's tool rather than the target's?",
+            tool, &context.object_format)));
+    }
+    Ok(())
+}
+
+/* Add an object file, by its full path, to the list of objects to link with, noting the input it came from.
+   To avoid object collisions and overwrites, bail out if the given object path was already taken */
+fn register_object(path: &String, origin: &str, context: &mut Context) -> Result<(), MasonError>
+{
+    if let Some(other) = context.objects.iter().find(|obj| &obj.path == path)
+    {
+        return Err(MasonError::config(format!("Objects for {} and {} would both be named {}: set object_name in the config file, eg to \"{{dir}}_{{stem}}\", to tell them apart",
+            &other.origin, origin, &path)));
+    }
+
+    context.objects.push(Object
+    {
+        path: path.to_string(),
+        origin: origin.to_string()
+    });
+    Ok(())
+}
+
+/* return true if an object file, by its full path, is already in the list of objects to link with */
+fn object_registered(path: &String, context: &Context) -> bool
+{
+    context.objects.iter().any(|obj| &obj.path == path)
+}
+
+/* Run through a directory of assembly source code and gather up each file with an extension
+   that one of the build's assemblers handles, eg .s
+   => slurp_from = path of directory to scan for source files to assemble
+      allow_empty = false to bail out if the directory contains no source files to assemble
+      context = build context
+   <= returns paths of the source files to assemble
+*/
+fn scan_directory(slurp_from: String, allow_empty: bool, context: &Context) -> Result<Vec<String>, MasonError>
+{
+    /* no longer accept missing directories, and only accept empty directories if allowed */
+    let directory = match retry(Step::Read, &context, || fs::read_dir(context.host.path(&slurp_from)))
+    {
+        Ok(d) => d,
+        Err(e) => return Err(MasonError::host(format!("Cannot assembly directory {}: {}", &slurp_from, e)))
+    };
+
+    let mut sources = Vec::new();
+
+    for file in directory
+    {
+        if let Ok(file) = file
+        {
+            /* assume everything in the asm directory can be assembled if it is a file */
+            if let Ok(metadata) = file.metadata()
+            {
+                /* a file whose name isn't UTF-8 can't be named in the commands and files Mason generates */
+                let path = match Path::new(&slurp_from).join(file.file_name()).to_str()
+                {
+                    Some(path) => path.to_string(),
+                    None =>
+                    {
+                        if Path::new(&file.file_name()).extension().and_then(|e| e.to_str()).map(|e| context.assemblers.contains_key(e)) == Some(true)
+                        {
+                            log_event(&context.host, LogLevel::Warning, "scan", format!("Skipping {:?} in assembly directory {}: its name isn't UTF-8", file.file_name(), &slurp_from));
+                        }
+                        continue;
+                    }
+                };
+                if metadata.is_file() == true && source_leafname(&path).is_some() == true && source_assembler(&path, context).is_some() == true
+                {
+                    sources.push(path);
+                }
+            }
+        }
+    }
+
+    if sources.len() == 0 && allow_empty == false
+    {
+        return Err(MasonError::config(format!("Assembly directory {} contains no source files to assemble", &slurp_from)));
+    }
+
+    Ok(sources)
+}
+
+/* Work out where in a shared_dir the objects assembled by this build are kept, so that other crates
+   building the same sources for the same target can use them. The directory is named after a
+   fingerprint of everything that affects the objects: the target and its assembler options, the
+   assemblers used, the generated include files, and the contents of every file in the asm_dirs,
+   which include the files sources .include
+   => shared_dir = shared directory, from the config file
+      plan = build's plan
+      inputs = files gathered from the config file
+      context = build context
+   <= returns the path of the directory for this build's objects, which may not exist yet, or the failure */
+fn shared_objects_dir(shared_dir: &str, plan: &Plan, inputs: &Inputs, context: &Context) -> Result<PathBuf, MasonError>
+{
+    let mut hasher = assembly_options_hasher(plan, context);
+    let mut add = |field: &str| hasher.update(format!("{}\n", field).as_bytes());
+
+    let mut dirs: Vec<&String> = inputs.asm_dirs.keys().collect();
+    dirs.sort();
+    for dir in dirs
+    {
+        /* files whose names aren't UTF-8 are skipped, as they are by scan_directory() */
+        let mut files: Vec<String> = match fs::read_dir(context.host.path(dir))
+        {
+            Ok(entries) => entries.filter_map(|e| e.ok()).filter(|e| e.path().is_file() == true)
+                .filter_map(|e| Path::new(dir).join(e.file_name()).to_str().map(|p| p.to_string())).collect(),
+            Err(_) => Vec::new()
+        };
+        files.sort();
+
+        for file in files
+        {
+            let digest: String = hash_file(&file, context)?.iter().map(|b| format!("{:02x}", b)).collect();
+            add(&format!("{} {}", file_leafname(&file), digest));
+        }
+    }
+
+    let fingerprint: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(context.host.path(shared_dir).join(fingerprint))
+}
+
+/* start a fingerprint of the options every source file is assembled with: the target and its assembler
+   options, the assemblers used, and the generated include files
+   => plan = build's plan
+      context = build context
+   <= returns the hasher, for the caller to add the files it fingerprints to */
+fn assembly_options_hasher(plan: &Plan, context: &Context) -> Sha256
+{
+    let mut hasher = Sha256::new();
+    {
+        let mut add = |field: &str| hasher.update(format!("{}\n", field).as_bytes());
+        add(context.host.var("TARGET").unwrap_or(""));
+        add(&context.as_exec);
+        add(&context.target.as_flags.join(" "));
+        add(&format!("{} {}", context.target.llvm_target, context.target.clang_flags.join(" ")));
+        add(&format!("{} {}", context.target.ptr_width, context.target.fp_width));
+        add(&serde_json::to_string(&context.assemblers).unwrap());
+        add(&context.absent_symbols.join(" "));
+        add(&format!("{} {}", context.source_map, context.debug_compression.name()));
+        add(context.object_name.as_deref().unwrap_or(SOURCE_OBJECT_NAME));
+        add(&serde_json::to_string(&plan.asm_includes).unwrap());
+    }
+    hasher
+}
+
+/* Work out the keys the objects assembled from source files are cached under in OUT_DIR, so that a source is
+   only reassembled when something affecting its object changes: its path and contents, the options and
+   generated include files it's assembled with, the versions of the tools that assemble it, or the contents
+   of the asm_dirs' other files, such as those it .includes. Files included from elsewhere aren't covered
+   => jobs = each source's path, assembler, and object name
+      plan = build's plan
+      inputs = files gathered from the config file
+      context = build context
+   <= returns each source's key, as hex, in the same order, or the failure */
+fn assembly_cache_keys(jobs: &[(&String, Assembler, String)], plan: &Plan, inputs: &Inputs, context: &Context) -> Result<Vec<String>, MasonError>
+{
+    let mut common = assembly_options_hasher(plan, context);
+
+    /* debug info records the directory sources were assembled in, so objects with it can only be shared
+    by builds of the same crate */
+    if context.source_map == true
+    {
+        common.update(format!("{}\n", &context.project_dir).as_bytes());
+    }
+
+    /* the first line of each tool's version, so that upgrading a tool reassembles everything it built */
+    let mut tools = BTreeSet::new();
+    for (_, assembler, _) in jobs.iter()
+    {
+        match assembler
+        {
+            Assembler::Gas => { tools.insert(context.as_exec.as_str()); },
+            Assembler::CppGas => { tools.insert(context.as_exec.as_str()); tools.insert(CPP_EXEC); },
+            Assembler::Nasm => { tools.insert(NASM_EXEC); }
+        }
+    }
+    for tool in tools
+    {
+        let flag = if tool == NASM_EXEC { "-v" } else { "--version" };
+        let version = match host_command(&mut Command::new(tool), &context.host).arg(flag).output()
+        {
+            Ok(output) => String::from_utf8_lossy(&output.stdout).lines().next().unwrap_or("").to_string(),
+            Err(_) => String::new()
+        };
+        common.update(format!("{} {}\n", tool, version).as_bytes());
+    }
+
+    /* files in the asm_dirs that aren't sources, and so may be included by any of them */
+    let mut dirs: Vec<&String> = inputs.asm_dirs.keys().collect();
+    dirs.sort();
+    for dir in dirs
+    {
+        let mut files: Vec<String> = match fs::read_dir(context.host.path(dir))
+        {
+            Ok(entries) => entries.filter_map(|e| e.ok()).filter(|e| e.path().is_file() == true)
+                .filter_map(|e| Path::new(dir).join(e.file_name()).to_str().map(|p| p.to_string()))
+                .filter(|p| source_assembler(p, context).is_none() == true)
+                .collect(),
+            Err(_) => Vec::new()
+        };
+        files.sort();
+
+        for file in files
+        {
+            let digest: String = hash_file(&file, context)?.iter().map(|b| format!("{:02x}", b)).collect();
+            common.update(format!("{} {}\n", &file, digest).as_bytes());
+        }
+    }
+
+    jobs.iter().map(|(path, _, name)|
+    {
+        let mut hasher = common.clone();
+        let digest: String = hash_file(path, context)?.iter().map(|b| format!("{:02x}", b)).collect();
+        hasher.update(format!("{} {} {}\n", path, name, digest).as_bytes());
+        Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+    }).collect()
+}
+
+/* Copy an object another build assembled from a source file into the output directory, and add it to the bundle
+   => source = path to source file
+      dir = shared directory holding the object, from shared_objects_dir()
+      context = build context */
+fn use_shared_object(source: &String, dir: &Path, context: &mut Context) -> Result<(), MasonError>
+{
+    let name = format!("{}.o", object_name(source, SOURCE_OBJECT_NAME, context));
+    let object_file = format!("{}/{}", &context.output_dir, &name);
+    if let Err(e) = retry(Step::Write, context, || fs::copy(dir.join(&name), &object_file))
+    {
+        return Err(MasonError::host(format!("Can't copy shared object {} to {}: {}", dir.join(&name).display(), &object_file, e)));
+    }
+
+    context.file_times.insert(source.clone(), None);
+    declare_input(source, context);
+    register_object(&object_file, source, context)?;
+    Ok(())
+}
+
+/* Share the objects this build assembled with other builds, by copying them to the given shared directory.
+   They're gathered in a scratch directory that is then renamed into place, so that other builds never see
+   an incomplete set. If another build shares the same objects first, its copies are kept
+   => sources = paths to the source files assembled
+      dir = shared directory to hold the objects, from shared_objects_dir()
+      context = build context */
+fn share_objects(sources: &Vec<String>, dir: &Path, context: &Context)
+{
+    let scratch = dir.with_extension(format!("tmp{}", std::process::id()));
+    let _ = fs::remove_dir_all(&scratch);
+
+    let copy = || -> io::Result<()>
+    {
+        fs::create_dir_all(&scratch)?;
+        for source in sources
+        {
+            let name = format!("{}.o", object_name(source, SOURCE_OBJECT_NAME, context));
+            fs::copy(format!("{}/{}", &context.output_dir, &name), scratch.join(&name))?;
+        }
+        fs::write(scratch.join(SHARED_COMPLETE_FILE), b"")?;
+        fs::rename(&scratch, dir)
+    };
+
+    /* sharing is only an optimization, so a failure, eg because another build got there first, isn't fatal */
+    if let Err(e) = copy()
+    {
+        let _ = fs::remove_dir_all(&scratch);
+        if dir.join(SHARED_COMPLETE_FILE).exists() == false
+        {
+            log_event(&context.host, LogLevel::Warning, "shared", format!("Can't share assembled objects in {}: {}", dir.display(), e));
+        }
+    }
+}
+
+/* create name from source file's path - extract just the leafname and drop the
+   file extension. so extract 'start' from 'src/platform-blah/asm/start.s'
+   => path = path to source file
+   <= returns the leafname, or None if the path isn't that of a source file Mason can assemble */
+fn source_leafname(path: &str) -> Option<String>
+{
+    let re = Regex::new(r"(([A-Za-z0-9_]+)(/))+(?P<leaf>[A-Za-z0-9_]+)\.[A-Za-z0-9_]+$").unwrap();
+    match re.captures(&path)
+    {
+        Some(matches) => Some(matches["leaf"].to_string()),
+        None => None
+    }
+}
+
+/* find the assembler for a source file from its extension, eg .s
+   => path = path to source file
+      context = build context
+   <= returns the assembler, or None if no assembler handles the file's extension */
+fn source_assembler(path: &str, context: &Context) -> Option<Assembler>
+{
+    let extension = Path::new(path).extension()?.to_str()?;
+    context.assemblers.get(extension).cloned()
+}
+
+/* Load the time taken to assemble each file in previous builds from the output directory.
+   If there are no usable statistics, start afresh
+   => context = build context, into which the statistics are loaded
+*/
+fn load_build_stats(context: &mut Context)
+{
+    let stats_path = format!("{}/{}", &context.output_dir, BUILD_STATS_FILE);
+    if let Ok(stats) = fs::read_to_string(&stats_path)
+    {
+        if let Ok(stats) = serde_json::from_str(&stats)
+        {
+            context.build_stats = stats;
+        }
+    }
+}
+
+/* Save the time taken to assemble each file to the output directory for future builds
+   => context = build context
+*/
+fn save_build_stats(context: &Context) -> Result<(), MasonError>
+{
+    let stats_path = format!("{}/{}", &context.output_dir, BUILD_STATS_FILE);
+    let stats = serde_json::to_string_pretty(&context.build_stats).unwrap();
+    if let Err(e) = retry(Step::Write, context, || fs::write(&stats_path, &stats))
+    {
+        return Err(MasonError::host(format!("Cannot write build statistics {}: {}", &stats_path, e)));
+    }
+    Ok(())
+}
+
+/* Assemble source files into the output directory and add their objects to the bundle, timing each file.
+   Objects are cached in OUT_DIR by a key covering everything that affects them, and a source whose key is
+   unchanged since it was last assembled has its cached object used instead. Up to NUM_JOBS assemblers
+   run at once, each beyond the first taking a token from cargo's jobserver, if it passed one on, so that
+   Mason shares the host's CPUs with the rest of the build. Sources are started in the order given,
+   slowest first, and their objects registered in the order of their paths whatever order they finish in,
+   so that the bundle doesn't depend on how long each file took to assemble
+   => plan = build's plan, listing the sources to assemble
+      inputs = files gathered from the config file
+      context = build context
+*/
+fn assemble_sources(plan: &Plan, inputs: &Inputs, context: &mut Context) -> Result<(), MasonError>
+{
+    /* work out each object's name up front, skipping files without a leafname (sans extension) and an assembler */
+    let output_dir = context.output_dir.clone();
+    let jobs: Vec<(&String, Assembler, String)> = plan.sources.iter()
+        .filter_map(|path| match (source_leafname(path), source_assembler(path, context))
+        {
+            (Some(_), Some(assembler)) => Some((path, assembler, object_name(path, SOURCE_OBJECT_NAME, context))),
+            _ => None
+        })
+        .collect();
+
+    /* use the cached objects of sources that haven't changed, and assemble the rest */
+    let keys = assembly_cache_keys(&jobs, plan, inputs, context)?;
+    let cache_dir = match &context.cache_dir
+    {
+        Some(dir) => dir.clone(),
+        None => format!("{}/{}", &output_dir, ASM_CACHE_DIR)
+    };
+    let cached = |index: usize| format!("{}/{}.o", &cache_dir, &keys[index]);
+    let mut outcomes: Vec<Option<(Result<(), MasonError>, Option<f64>)>> = vec![None; jobs.len()];
+    let mut pending = Vec::new();
+    for (index, (path, _, name)) in jobs.iter().enumerate()
+    {
+        let object_file = format!("{}/{}.o", &output_dir, name);
+        match Path::new(&cached(index)).is_file() == true && retry(Step::Write, context, || fs::copy(cached(index), &object_file)).is_ok() == true
+        {
+            true =>
+            {
+                log_event(&context.host, LogLevel::Step, "assemble", format!("{} is unchanged, reusing its object", path));
+                outcomes[index] = Some((Ok(()), None));
+            },
+            false => pending.push(index)
+        }
+    }
+
+    let workers = assembly_jobs(context).min(pending.len()).max(1);
+    let client = match workers > 1
+    {
+        /* safe as nothing else in the build script uses the jobserver's file descriptors */
+        true => unsafe { jobserver::Client::from_env() },
+        false => None
+    };
+
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let shared: &Context = context;
+    let results: Vec<(usize, Result<(), MasonError>, f64)> = thread::scope(|scope|
+    {
+        let handles: Vec<_> = (0..workers).map(|worker|
+        {
+            let (jobs, pending, client, next, failed, output_dir) = (&jobs, &pending, &client, &next, &failed, &output_dir);
+            scope.spawn(move ||
+            {
+                let mut done = Vec::new();
+                while failed.load(Ordering::SeqCst) == false
+                {
+                    /* the first worker runs on the token cargo gave the build script. the others need their own */
+                    let token = match (worker, client)
+                    {
+                        (0, _) | (_, None) => None,
+                        (_, Some(client)) => match client.acquire()
+                        {
+                            Ok(token) => Some(token),
+                            Err(_) => break
+                        }
+                    };
+
+                    let index = match pending.get(next.fetch_add(1, Ordering::SeqCst))
+                    {
+                        Some(index) => *index,
+                        None => break
+                    };
+
+                    let (path, assembler, name) = &jobs[index];
+                    log_event(&shared.host, LogLevel::Step, "assemble", format!("assembling {}", path));
+                    let started = Instant::now();
+                    let object_file = format!("{}/{}.o", output_dir, name);
+                    let result = assemble_source(path, name, *assembler, &object_file, output_dir, shared);
+                    if result.is_err() == true
+                    {
+                        failed.store(true, Ordering::SeqCst);
+                    }
+                    done.push((index, result, started.elapsed().as_secs_f64()));
+                    drop(token);
+                }
+                done
+            })
+        }).collect();
+
+        handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+    });
+
+    for (index, result, seconds) in results
+    {
+        outcomes[index] = Some((result, Some(seconds)));
+    }
+
+    /* report the first failure in the sources' order, so the same error is given however the jobs ran */
+    if let Some(Err(e)) = outcomes.iter().flatten().map(|(result, _)| result).find(|result| result.is_err() == true)
+    {
+        return Err(e.clone());
+    }
+
+    /* cache the newly assembled objects. the cache is only an optimization, so failing to update it isn't
+    fatal. each object is copied in under a name of its own and then renamed into place, so that builds
+    sharing a persistent cache never see part of an object */
+    let _ = fs::create_dir_all(&cache_dir);
+    let mut order: Vec<usize> = (0..jobs.len()).collect();
+    order.sort_by(|a, b| jobs[*a].0.cmp(jobs[*b].0));
+    for index in order
+    {
+        let (path, _, name) = &jobs[index];
+        let object_file = format!("{}/{}.o", &output_dir, name);
+        let seconds = outcomes[index].as_ref().and_then(|(_, seconds)| *seconds);
+        context.file_times.insert(path.to_string(), seconds);
+        if let Some(seconds) = seconds
+        {
+            let scratch = format!("{}.tmp{}", cached(index), std::process::id());
+            if fs::copy(&object_file, &scratch).and_then(|_| fs::rename(&scratch, cached(index))).is_err() == true
+            {
+                let _ = fs::remove_file(&scratch);
+            }
+            context.build_stats.insert(path.to_string(), seconds);
+        }
+
+        declare_input(path, context);
+        register_object(&object_file, path, context)?;
+    }
+
+    /* forget objects no source uses any more, unless the cache is persistent, when other builds may use them */
+    if let (None, Ok(entries)) = (&context.cache_dir, fs::read_dir(&cache_dir))
+    {
+        let used: HashSet<String> = (0..jobs.len()).map(|index| cached(index)).collect();
+        for entry in entries.filter_map(|e| e.ok())
+        {
+            if used.contains(entry.path().to_string_lossy().as_ref()) == false
+            {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+    Ok(())
+}
+
+/* decide how many assemblers to run at once: NUM_JOBS, as set by cargo, or else one per CPU
+   => context = build context
+   <= returns the number of assemblers to run at once */
+fn assembly_jobs(context: &Context) -> usize
+{
+    match context.host.var("NUM_JOBS").and_then(|n| n.parse::<usize>().ok())
+    {
+        Some(jobs) if jobs > 0 => jobs,
+        _ => thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+    }
+}
+
+/* Assemble a source file into a .o object file in the given directory, without adding it to the bundle,
+   using the assembler for its extension
+   => path = path to source file to assemble
+      dir = directory to write the object file to
+      context = build context
+   <= returns the path of the object file, or None if the file was ignored
+*/
+fn assemble_into(path: &str, dir: &str, mut context: &mut Context) -> Result<Option<String>, MasonError>
+{
+    /* skip files without a leafname (sans extension) and an assembler */
+    let assembler = match (source_leafname(path), source_assembler(path, context))
+    {
+        (Some(_), Some(a)) => a,
+        _ => return Ok(None) /* skip non-conformant files */
+    };
+
+    /* build pathname for the target .o file */
+    let name = object_name(path, SOURCE_OBJECT_NAME, context);
+    let object_file = format!("{}/{}.o", dir, &name);
+    assemble_source(path, &name, assembler, &object_file, dir, context)?;
+
+    declare_input(&path, &mut context);
+    Ok(Some(object_file))
+}
+
+/* Build the command that runs a source file through the C preprocessor, with none of the host's macros defined
+   => path = path to source file to preprocess
+      output = path of preprocessed file to write
+      context = build context
+   <= returns the command to run */
+fn preprocess_command(path: &str, output: &str, context: &Context) -> Command
+{
+    let mut command = Command::new(CPP_EXEC);
+    command.arg("-I")
+        .arg(&context.output_dir)
+        .arg("-undef")
+        .arg("-x")
+        .arg("assembler-with-cpp")
+        .arg("-D__ASSEMBLER__")
+        .arg(format!("-Dptrwidth={}", &context.target.ptr_width))
+        .arg(format!("-Dfpwidth={}", &context.target.fp_width))
+        .arg("-o")
+        .arg(output)
+        .arg(path);
+    command
+}
+
+/* Build the command that assembles a source file, which has already been preprocessed if its assembler needs it
+   => source = path to source file to assemble
+      assembler = assembler to use
+      object_file = path of object file to write
+      context = build context
+   <= returns the command to run */
+fn assemble_command(source: &str, assembler: Assembler, object_file: &str, context: &Context) -> Command
+{
+    let mut command = match assembler
+    {
+        Assembler::Gas | Assembler::CppGas if context.backend != Backend::Gnu =>
+        {
+            let mut command = Command::new(&context.as_exec);
+            command.arg(format!("--target={}", &context.target.llvm_target))
+                .arg("-c")
+                .args(&context.target.clang_flags)
+                .arg("-I")
+                .arg(&context.output_dir)
+                .arg(format!("-Wa,-defsym,ptrwidth={}", &context.target.ptr_width))
+                .arg(format!("-Wa,-defsym,fpwidth={}", &context.target.fp_width));
+            for symbol in context.absent_symbols.iter()
+            {
+                command.arg(format!("-Wa,-defsym,{}=1", symbol));
+            }
+            if context.source_map == true
+            {
+                command.arg("-g").arg(format!("-gz={}", context.debug_compression.name()));
+            }
+            command
+        },
+        Assembler::Gas | Assembler::CppGas =>
+        {
+            let mut command = Command::new(&context.as_exec);
+            command.args(&context.target.as_flags)
+                .arg("-I")
+                .arg(&context.output_dir)
+                .arg("--defsym")
+                .arg(format!("ptrwidth={}", &context.target.ptr_width))
+                .arg("--defsym")
+                .arg(format!("fpwidth={}", &context.target.fp_width));
+            for symbol in context.absent_symbols.iter()
+            {
+                command.arg("--defsym").arg(format!("{}=1", symbol));
+            }
+            if context.source_map == true
+            {
+                command.arg("-g").arg(format!("--compress-debug-sections={}", context.debug_compression.name()));
+            }
+            command
+        },
+        Assembler::Nasm =>
+        {
+            let mut command = Command::new(NASM_EXEC);
+            command.arg("-f")
+                .arg(if context.target.ptr_width == 64 { "elf64" } else { "elf32" })
+                .arg(format!("-I{}/", &context.output_dir))
+                .arg(format!("-Dptrwidth={}", &context.target.ptr_width))
+                .arg(format!("-Dfpwidth={}", &context.target.fp_width));
+            for symbol in context.absent_symbols.iter()
+            {
+                command.arg(format!("-D{}=1", symbol));
+            }
+            if context.source_map == true
+            {
+                command.arg("-g").arg("-F").arg("dwarf");
+            }
+            command
+        }
+    };
+
+    command.arg("-o").arg(object_file).arg(source);
+    command
+}
+
+/* Check each assembly source file with the formatter or linter given by asm_lint, which is expected to exit
+   with a failure if the file needs reformatting or has other problems, as formatters do with --check. What the
+   tool prints about each failing file is passed on as warnings. In strict mode the build then fails
+   => sources = paths of source files to check
+      lint = the config file's asm_lint settings
+      context = build context */
+fn lint_sources(sources: &Vec<String>, lint: &AsmLint, context: &Context) -> Result<(), MasonError>
+{
+    /* check in order of path, rather than build time, so that the tool's reports are always in the same order */
+    let mut sources: Vec<&String> = sources.iter().collect();
+    sources.sort();
+
+    let mut failed = Vec::new();
+    for source in sources
+    {
+        let args: Vec<String> = lint.command.iter().map(|a| a.replace("{source}", source)).collect();
+        let result = run_tool(Command::new(&args[0]).args(&args[1..]),
+            format!("Couldn't run asm_lint command to check {}", source), context)?;
+
+        if result.status.success() == false
+        {
+            log_event(&context.host, LogLevel::Warning, "lint", format!("{} failed the asm_lint check", source));
+            forward_warnings(&context.host, "lint", &format!("{}{}", String::from_utf8_lossy(&result.stdout), String::from_utf8_lossy(&result.stderr)));
+            failed.push(source.clone());
+        }
+    }
+
+    if failed.len() > 0 && lint.strict.unwrap_or(false) == true
+    {
+        return Err(MasonError::check_failure(format!("{} source file(s) failed the asm_lint check: {}", failed.len(), failed.join(", "))));
+    }
+    Ok(())
+}
+
+/* Run a source file through its assembler, and the C preprocessor first if needed
+   => path = path to source file to assemble
+      leafname = name of source file's object, sans extension, to name intermediate files after
+      assembler = assembler to use
+      object_file = path of object file to write, or /dev/null to only check the source
+      dir = directory to write any intermediate files to
+      context = build context
+   <= returns Ok, or the failure if the source couldn't be assembled
+*/
+fn assemble_source(path: &str, leafname: &str, assembler: Assembler, object_file: &str, dir: &str, context: &Context) -> Result<(), MasonError>
+{
+    /* run the source through the C preprocessor first, if needed, with none of the host's macros defined */
+    let mut source = path.to_string();
+    if assembler == Assembler::CppGas
+    {
+        source = format!("{}/{}.i", dir, leafname);
+        let mut command = preprocess_command(path, &source, context);
+        let result = run_tool(&mut command, format!("Failed to execute command to preprocess {}", path), &context)?;
+
+        if result.status.success() != true
+        {
+            return Err(MasonError::tool_failure(format!("Preprocessing {} failed:\n{}\n{}",
+                &path, String::from_utf8_lossy(&result.stdout), String::from_utf8_lossy(&result.stderr)))
+                .in_stage("preprocess").with_file(path).with_command(&command, None));
+        }
+        forward_warnings(&context.host, "preprocess", &String::from_utf8_lossy(&result.stderr).to_string());
+    }
+
+    /* now let's try to assemble the source into an intermediate .o */
+    let mut command = assemble_command(&source, assembler, object_file, context);
+    let result = run_tool(&mut command, format!("Failed to execute command to assemble {}", path), &context)?;
+
+    if result.status.success() != true
+    {
+        return Err(MasonError::tool_failure(format!("Assembling {} failed:\n{}\n{}",
+            &path, String::from_utf8_lossy(&result.stdout), as_diagnostics(&String::from_utf8_lossy(&result.stderr).to_string(), context)))
+            .with_file(path).with_command(&command, None));
+    }
+
+    /* a successful assembler can still have something to say, eg about a deprecated directive */
+    forward_warnings(&context.host, "assemble", &as_diagnostics(&String::from_utf8_lossy(&result.stderr).to_string(), context));
+    Ok(())
+}
+
+/* Pass the messages a tool printed while succeeding to cargo as warnings, one per line, so that
+   developers see them rather than them being discarded with the rest of the tool's output
+   => host = environment the build runs in, which says how to log the messages
+      event = short name of what the messages are about, eg assemble
+      output = messages the tool printed, eg as rewritten by as_diagnostics() */
+fn forward_warnings(host: &HostEnv, event: &str, output: &String)
+{
+    for line in output.lines().filter(|l| l.trim().len() > 0)
+    {
+        log_event(host, LogLevel::Warning, event, line.to_string());
+    }
+}
+
+/* Add a prebuilt object file to the archive, without assembling or packaging it.
+   The object is copied into the output directory, so that its leafname can't clash
+   with any object generated by Mason, and checked that it was built for the target
+   => path = path to prebuilt object file
+      context = build context
+*/
+fn include_object(path: &String, mut context: &mut Context) -> Result<(), MasonError>
+{
+    let contents = match retry(Step::Read, &context, || fs::read(context.host.path(path)))
+    {
+        Ok(c) => c,
+        Err(e) => return Err(MasonError::config(format!("Can't read prebuilt object file {}: {}", &path, e)))
+    };
+
+    validate_object(&path, &contents, &context)?;
+
+    let leafname = file_leafname(path);
+    let object_file = format!("{}/{}", &context.output_dir, &leafname);
+    if object_registered(&object_file, &context) == true
+    {
+        return Err(MasonError::config(format!("Prebuilt object file {} clashes with an object already in the bundle", &path)));
+    }
+
+    if let Err(e) = retry(Step::Write, &context, || fs::write(&object_file, &contents))
+    {
+        return Err(MasonError::host(format!("Cannot copy prebuilt object file {} to {}: {}", &path, &object_file, e)));
+    }
+
+    declare_input(&path, &mut context);
+    register_object(&object_file, &path, &mut context)?;
+    Ok(())
+}
+
+/* Merge the members of a prebuilt static library into the archive.
+   Each member is extracted into the output directory, checked it was built for the target,
+   and renamed if its name is already taken by another object in the bundle. If only some
+   of the library's members were selected, eg from libgcc.a, just those are merged
+   => path = path to prebuilt static library
+      context = build context
+*/
+fn import_library(path: &String, mut context: &mut Context) -> Result<(), MasonError>
+{
+    let leafname = file_leafname(path);
+    let stem = Path::new(path).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or(leafname.clone());
+
+    /* ar extracts into its working directory, so it needs the library's absolute path */
+    let library = match fs::canonicalize(context.host.path(path))
+    {
+        Ok(p) => p,
+        Err(e) => return Err(MasonError::config(format!("Can't find prebuilt library {}: {}", &path, e)))
+    };
+
+    /* start with an empty scratch directory to extract the library's members into */
+    let extract_dir = format!("{}/{}.extract", &context.output_dir, &leafname);
+    let _ = fs::remove_dir_all(&extract_dir);
+    if let Err(e) = retry(Step::Write, &context, || fs::create_dir_all(&extract_dir))
+    {
+        return Err(MasonError::host(format!("Cannot create directory {} to extract {}: {}", &extract_dir, &path, e)));
+    }
+
+    let mut command = Command::new(&context.ar_exec);
+    command.args(&context.tool_flags.ar).arg("t").arg(&library);
+    let list = run_tool(&mut command, format!("Failed to execute command to list members of {}", &path), &context)?;
+
+    if list.status.success() != true
+    {
+        return Err(MasonError::tool_failure(format!("Listing members of {} failed:\n{}\n{}",
+            &path, String::from_utf8_lossy(&list.stdout), String::from_utf8_lossy(&list.stderr))).with_command(&command, None));
+    }
+
+    let listing = String::from_utf8_lossy(&list.stdout).into_owned();
+    let selected = context.library_members.get(path).cloned();
+    if let Some(members) = &selected
+    {
+        if let Some(missing) = members.iter().find(|m| listing.lines().any(|l| &l == m) == false)
+        {
+            return Err(MasonError::config(format!("Library {} has no member named {}", &path, missing)));
+        }
+    }
+
+    /* a library can contain more than one member with the same name, so
+    extract each member individually by its name and instance count */
+    let mut instances = HashMap::new();
+    for member in listing.lines().filter(|l| selected.as_ref().map(|m| m.iter().any(|s| s == l)).unwrap_or(true) == true)
+    {
+        let instance = instances.entry(member.to_string()).or_insert(0);
+        *instance = *instance + 1;
+
+        let mut command = Command::new(&context.ar_exec);
+        command.args(&context.tool_flags.ar).arg("xN").arg(instance.to_string()).arg(&library).arg(member).current_dir(&extract_dir);
+        let result = run_tool(&mut command, format!("Failed to execute command to extract {} from {}", member, &path), &context)?;
+
+        if result.status.success() != true
+        {
+            return Err(MasonError::tool_failure(format!("Extracting {} from {} failed:\n{}\n{}",
+                member, &path, String::from_utf8_lossy(&result.stdout), String::from_utf8_lossy(&result.stderr))).with_command(&command, None));
+        }
+
+        let extracted = format!("{}/{}", &extract_dir, member);
+        let contents = match retry(Step::Read, &context, || fs::read(&extracted))
+        {
+            Ok(c) => c,
+            Err(e) => return Err(MasonError::host(format!("Cannot read {} extracted from {}: {}", member, &path, e)))
+        };
+
+        validate_object(&format!("{}({})", &path, member), &contents, &context)?;
+
+        /* pick a name for the member that isn't already taken in the bundle */
+        let mut object_file = format!("{}/{}", &context.output_dir, member);
+        let mut attempt = 1;
+        while object_registered(&object_file, &context) == true
+        {
+            object_file = match attempt
+            {
+                1 => format!("{}/{}-{}", &context.output_dir, &stem, member),
+                n => format!("{}/{}-{}-{}", &context.output_dir, &stem, n, member)
+            };
+            attempt = attempt + 1;
+        }
+
+        if let Err(e) = retry(Step::Write, &context, || fs::rename(&extracted, &object_file))
+        {
+            return Err(MasonError::host(format!("Cannot move {} extracted from {} to {}: {}", member, &path, &object_file, e)));
+        }
+
+        register_object(&object_file, &format!("{}({})", &path, member), &mut context)?;
+    }
+
+    declare_input(&path, &mut context);
+    Ok(())
+}
+
+/* truncate a binary file's SHA-256 digest to fit the target's pointer width, to identify its version
+   => digest = SHA-256 digest of the binary's contents, as packaged
+      context = build context
+   <= returns the value of the binary's _hash symbol */
+fn payload_hash(digest: &[u8], context: &Context) -> u64
+{
+    match context.target.ptr_width
+    {
+        64 => u64::from_be_bytes([digest[0], digest[1], digest[2], digest[3], digest[4], digest[5], digest[6], digest[7]]),
+        16 => u16::from_be_bytes([digest[0], digest[1]]) as u64,
+        _ => u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) as u64
+    }
+}
+
+/* Write an object file holding a binary file's contents in the given section, with symbols pointing to
+   the start and end of the contents, and absolute symbols giving their size, hash and, if enabled, load
+   address. Like ld -r --format=binary, the object's ELF flags are left clear, as it holds no code. The
+   contents are copied into the object a chunk at a time, so the binary is never held in memory
+   => source = path to the contents to package
+      object_file = path of object file to write
+      section = name of the section to hold the contents
+      prefix = prefix of the payload's symbols, eg _binary_leafname_
+      hash = value of the payload's _hash symbol
+      load_address = address the payload expects to be copied to, if given
+      context = build context */
+fn write_payload_object(source: &str, object_file: &str, section: &str, prefix: &str, hash: u64, load_address: Option<u64>, context: &Context) -> Result<(), MasonError>
+{
+    let (mut contents, size) = match retry(Step::Read, context, || fs::File::open(context.host.path(source)).and_then(|f| Ok((f.metadata()?.len(), f))))
+    {
+        Ok((size, file)) => (file, size),
+        Err(e) => return Err(MasonError::host(format!("Can't read {} to package it: {}", source, e)))
+    };
+
+    let mut symbols = vec![
+        (format!("{}start", prefix), 0, false),
+        (format!("{}end", prefix), size, false),
+        (format!("{}size", prefix), size, true),
+        (format!("{}hash", prefix), hash, true)
+    ];
+    if let Some(address) = load_address
+    {
+        if context.load_address_symbols == true
+        {
+            symbols.push((format!("{}load_address", prefix), address, true));
+        }
+    }
+
+    write_data_object(object_file, section, &mut contents, size, &symbols, context).map_err(|e| e.with_file(source))
+}
+
+/* Write an ELF relocatable object file for the target holding the given contents in a read-only section
+   of its own, along with global symbols, each either an offset into the contents or an absolute value.
+   The object is laid out as the object crate's own writer would, but is written as it's generated, with
+   the contents read in chunks into the output, so they needn't fit in memory
+   => object_file = path of object file to write
+      section = name of the section to hold the contents
+      contents = reader of the section's contents
+      size = number of bytes of contents to read
+      symbols = name of each symbol, its value, and true if the value is absolute
+      context = build context
+   <= returns the failure, if the object can't be written */
+fn write_data_object(object_file: &str, section: &str, contents: &mut dyn Read, size: u64, symbols: &Vec<(String, u64, bool)>, context: &Context) -> Result<(), MasonError>
+{
+    let (architecture, endian) = payload_architecture(context)?;
+    let wide = architecture.address_size() == Some(AddressSize::U64);
+    let output = match retry(Step::Write, context, || fs::File::create(object_file))
+    {
+        Ok(file) => file,
+        Err(e) => return Err(MasonError::host(format!("Can't write object {}: {}", object_file, e)))
+    };
+
+    let mut buffer = write::StreamingBuffer::new(io::BufWriter::new(output));
+    let mut writer = write::elf::Writer::new(endian, wide, &mut buffer);
+
+    /* work out where everything goes: the header, the contents, the symbols and their names, then the section headers */
+    writer.reserve_file_header();
+    writer.reserve_null_section_index();
+    let section_index = writer.reserve_section_index();
+    let section_offset = writer.reserve(size as usize, 1);
+    let section_name = writer.add_section_name(section.as_bytes());
+    writer.reserve_null_symbol_index();
+    let names: Vec<_> = symbols.iter().map(|(name, _, absolute)|
+    {
+        writer.reserve_symbol_index(if *absolute == true { None } else { Some(section_index) });
+        writer.add_string(name.as_bytes())
+    }).collect();
+    writer.reserve_symtab_section_index();
+    writer.reserve_symtab();
+    writer.reserve_strtab_section_index();
+    writer.reserve_strtab();
+    writer.reserve_shstrtab_section_index();
+    writer.reserve_shstrtab();
+    writer.reserve_section_headers();
+
+    /* ELF32 objects give offsets and sizes in 32 bits, so the whole object must be smaller than 4 GiB */
+    if wide == false && writer.reserved_len() as u64 > u32::MAX as u64
+    {
+        drop(writer);
+        drop(buffer);
+        let _ = fs::remove_file(object_file);
+        return Err(MasonError::config(format!("Can't package {} bytes in ELF32 object {}: it must be smaller than 4 GiB", size, object_file)));
+    }
+
+    /* then write them out in that order */
+    if let Err(e) = writer.write_file_header(&write::elf::FileHeader
+    {
+        os_abi: elf::ELFOSABI_NONE,
+        abi_version: 0,
+        e_type: elf::ET_REL,
+        e_machine: context.target.elf_machine,
+        e_entry: 0,
+        e_flags: 0
+    })
+    {
+        return Err(MasonError::host(format!("Can't generate object {}: {}", object_file, e)));
+    }
+
+    let mut chunk = vec![0; STREAM_CHUNK_SIZE];
+    let mut remaining = size;
+    while remaining > 0
+    {
+        let length = remaining.min(chunk.len() as u64) as usize;
+        if let Err(e) = contents.read_exact(&mut chunk[..length])
+        {
+            return Err(MasonError::host(format!("Can't read contents of object {}: {}", object_file, e)));
+        }
+        writer.write(&chunk[..length]);
+        remaining = remaining - length as u64;
+    }
+
+    writer.write_null_symbol();
+    for ((_, value, absolute), name) in symbols.iter().zip(names)
+    {
+        writer.write_symbol(&write::elf::Sym
+        {
+            name: Some(name),
+            section: if *absolute == true { None } else { Some(section_index) },
+            st_info: (elf::STB_GLOBAL << 4) | elf::STT_NOTYPE,
+            st_other: elf::STV_DEFAULT,
+            st_shndx: if *absolute == true { elf::SHN_ABS } else { 0 },
+            st_value: *value,
+            st_size: 0
+        });
+    }
+    writer.write_strtab();
+    writer.write_shstrtab();
+
+    writer.write_null_section_header();
+    writer.write_section_header(&write::elf::SectionHeader
+    {
+        name: Some(section_name),
+        sh_type: elf::SHT_PROGBITS,
+        sh_flags: elf::SHF_ALLOC as u64,
+        sh_addr: 0,
+        sh_offset: section_offset as u64,
+        sh_size: size,
+        sh_link: 0,
+        sh_info: 0,
+        sh_addralign: 1,
+        sh_entsize: 0
+    });
+    writer.write_symtab_section_header(1);
+    writer.write_strtab_section_header();
+    writer.write_shstrtab_section_header();
+
+    /* the buffer notes the first failure to write, rather than each write returning it */
+    let written = buffer.result().and_then(|_| buffer.into_inner().into_inner().map(|_| ()).map_err(|e| e.into_error()));
+    match written
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(MasonError::host(format!("Can't write object {}: {}", object_file, e)))
+    }
+}
+
+/* Identify the build by the SHA-256 digest of the target and of each input file's path, relative to the
+   project directory, and its contents, and add an object holding the identifier to the bundle as
+   _mason_build_id. The identifier is also given in hex to the consuming crate as MASON_BUILD_ID
+   => context = build context
+*/
+fn write_build_id(context: &mut Context) -> Result<(), MasonError>
+{
+    let mut hasher = Sha256::new();
+    hasher.update(context.host.var("TARGET").unwrap_or("").as_bytes());
+
+    /* the declared inputs include files that don't exist, eg missing optional binaries, which can't be hashed */
+    let project_dir = absolute_path(&context.project_dir, &context.project_dir);
+    for path in context.inputs.iter().filter(|path| path.is_file() == true)
+    {
+        let relative = path.strip_prefix(&project_dir).unwrap_or(path);
+        hasher.update(b"\0");
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update(b"\0");
+        hasher.update(hash_file(&path.to_string_lossy(), context)?);
+    }
+
+    let id = hasher.finalize().to_vec();
+    let object_file = format!("{}/{}", &context.output_dir, BUILD_ID_OBJECT);
+    write_data_object(&object_file, BUILD_ID_SECTION, &mut &id[..], id.len() as u64, &vec![(BUILD_ID_SYMBOL.to_string(), 0, false)], context)?;
+
+    register_object(&object_file, "build id", context)?;
+    context.sections.push(BUILD_ID_SECTION.to_string());
+
+    let hex: String = id.iter().map(|b| format!("{:02x}", b)).collect();
+    log_event(&context.host, LogLevel::Step, "build_id", format!("build id is {}", &hex));
+    println!("cargo:rustc-env=MASON_BUILD_ID={}", hex);
+    Ok(())
+}
+
+/* Work out the architecture and byte order of the objects that binary files are packaged in from the
+   target's ELF machine and its object format, which gives the word size and endianness
+   => context = build context
+   <= returns the object crate's architecture and endianness for the target */
+fn payload_architecture(context: &Context) -> Result<(Architecture, Endianness), MasonError>
+{
+    let format = &context.object_format;
+    let wide = format.starts_with("elf64") == true;
+    let architecture = match (context.target.elf_machine, wide)
+    {
+        (243, true) => Architecture::Riscv64,
+        (243, false) => Architecture::Riscv32,
+        (62, true) => Architecture::X86_64,
+        (62, false) => Architecture::X86_64_X32,
+        (3, _) => Architecture::I386,
+        (21, _) => Architecture::PowerPc64,
+        (20, _) => Architecture::PowerPc,
+        (8, true) => Architecture::Mips64,
+        (8, false) => Architecture::Mips,
+        (258, _) => Architecture::LoongArch64,
+        (183, true) => Architecture::Aarch64,
+        (183, false) => Architecture::Aarch64_Ilp32,
+        (40, _) => Architecture::Arm,
+        (83, _) => Architecture::Avr,
+        (105, _) => Architecture::Msp430,
+        (machine, _) => return Err(MasonError::config(format!("Can't package binary files in {} objects: ELF machine {} isn't supported",
+            format, machine)))
+    };
+
+    /* the BFD names of big-endian PowerPC formats don't say so */
+    let endian = match format.contains("big") == true || format == "elf32-powerpc" || format == "elf64-powerpc"
+    {
+        true => Endianness::Big,
+        false => Endianness::Little
+    };
+
+    Ok((architecture, endian))
+}
+
+/* Check a prebuilt object's ELF header matches the target, or bail out
+   => path = description of the object's location, for error messages
+      contents = the object file's contents
+      context = build context
+*/
+fn validate_object(path: &String, contents: &Vec<u8>, context: &Context) -> Result<(), MasonError>
+{
+    /* check the ELF header: magic, class (pointer width), data encoding, and machine */
+    if contents.len() < 20 || &contents[0..4] != b"\x7fELF"
+    {
+        return Err(MasonError::config(format!("Prebuilt object file {} is not an ELF object", &path)));
+    }
+
+    let class_width = match contents[4]
+    {
+        1 => 32,
+        2 => 64,
+        _ => 0
+    };
+
+    let machine = match contents[5]
+    {
+        1 => u16::from_le_bytes([contents[18], contents[19]]),
+        2 => u16::from_be_bytes([contents[18], contents[19]]),
+        _ => 0
+    };
+
+    /* targets with pointers narrower than 32 bits still use 32-bit ELF objects */
+    let target_class_width = match context.target.ptr_width
+    {
+        64 => 64,
+        _ => 32
+    };
+
+    if machine != context.target.elf_machine || class_width != target_class_width
+    {
+        return Err(MasonError::config(format!("Prebuilt object file {} was built for ELF machine {} ({}-bit) but the target requires ELF machine {} ({}-bit)",
+            &path, machine, class_width, context.target.elf_machine, target_class_width)));
+    }
+    Ok(())
+}
+
+/* Check whether a build needs its objects scanned for symbol conflicts: only when the config file gives
+   policies for them, or prebuilt objects or libraries, such as libgcc.a, may bring in clashing symbols
+   => plan = plan to be carried out
+      config = parsed config file
+   <= returns true if resolve_symbol_conflicts() is to be run */
+fn scans_symbols(plan: &Plan, config: &Config) -> bool
+{
+    config.symbol_conflicts.is_some() == true || plan.object_files.len() > 0 || plan.lib_files.len() > 0
+}
+
+/* Find global symbols defined by more than one registered object and apply the configured
+   policy to each conflict. Objects are considered in the order they were registered, so that
+   the first definition is the one that's kept if the policy allows the conflict
+   => conflicts = symbol conflict policies from the config file, if any
+      context = build context
+*/
+fn resolve_symbol_conflicts(conflicts: &Option<Vec<SymbolConflict>>, context: &mut Context) -> Result<(), MasonError>
+{
+    /* compile the policies' patterns, anchored to match whole symbol names */
+    let mut policies = Vec::new();
+    if let Some(conflicts) = conflicts
+    {
+        for conflict in conflicts
+        {
+            /* patterns were checked when the config file was parsed */
+            let re = Regex::new(format!("^(?:{})$", &conflict.pattern).as_str()).unwrap();
+            policies.push((re, conflict.policy, conflict.prefix.clone()));
+        }
+    }
+
+    /* map each global symbol to the first object defining it, and
+    gather the objcopy arguments needed to fix up later definitions */
+    let mut owners: HashMap<String, String> = HashMap::new();
+    let mut fixups: Vec<(String, Vec<String>)> = Vec::new();
+
+    for obj in context.objects.iter().map(|obj| &obj.path)
+    {
+        let mut args = Vec::new();
+
+        for symbol in defined_symbols(obj, true, context)?
+        {
+            let owner = match owners.get(&symbol)
+            {
+                Some(owner) => owner.clone(),
+                None =>
+                {
+                    owners.insert(symbol, obj.clone());
+                    continue;
+                }
+            };
+
+            match policies.iter().find(|(re, _, _)| re.is_match(&symbol))
+            {
+                Some((_, ConflictPolicy::First, _)) => args.push(format!("--localize-symbol={}", &symbol)),
+                Some((_, ConflictPolicy::Rename, Some(prefix))) => args.push(format!("--redefine-sym={}={}{}", &symbol, prefix, &symbol)),
+                _ => return Err(MasonError::config(format!("Symbol {} is defined by both {} and {}", &symbol, &owner, &obj)))
+            }
+        }
+
+        if args.len() > 0
+        {
+            fixups.push((obj.clone(), args));
+        }
+    }
+
+    for (obj, args) in fixups
+    {
+        let mut command = Command::new(&context.oc_exec);
+        command.args(&context.tool_flags.objcopy).args(&args).arg(&obj);
+        let result = run_tool(&mut command, format!("Couldn't run command to resolve symbol conflicts in {}", &obj), &context)?;
+
+        if result.status.success() != true
+        {
+            return Err(MasonError::tool_failure(format!("Resolving symbol conflicts in {} failed:\n{}\n{}",
+                &obj, String::from_utf8_lossy(&result.stdout), String::from_utf8_lossy(&result.stderr))).with_command(&command, None));
+        }
+    }
+    Ok(())
+}
+
+/* List the strong symbols defined by an object file.
+   Weak and common symbols are skipped, as these can't conflict
+   => path = path to object file to inspect
+      extern_only = true to list only global symbols, or false to include local ones
+      context = build context
+   <= returns names of the symbols
+*/
+fn defined_symbols(path: &String, extern_only: bool, context: &Context) -> Result<Vec<String>, MasonError>
+{
+    let mut nm = Command::new(&context.nm_exec);
+    nm.arg("--defined-only");
+    if extern_only == true
+    {
+        nm.arg("--extern-only");
+    }
+
+    nm.arg("--portability").arg(&path);
+    let result = run_tool(&mut nm, format!("Couldn't run command to list symbols in {}", &path), &context)?;
+
+    if result.status.success() != true
+    {
+        return Err(MasonError::tool_failure(format!("Listing symbols in {} failed:\n{}\n{}",
+            &path, String::from_utf8_lossy(&result.stdout), String::from_utf8_lossy(&result.stderr))).with_command(&nm, None));
+    }
+
+    /* each line of portable output is: name type [value [size]] */
+    let mut symbols = Vec::new();
+    for line in String::from_utf8_lossy(&result.stdout).lines()
+    {
+        let mut fields = line.split_whitespace();
+        if let (Some(name), Some(kind)) = (fields.next(), fields.next())
+        {
+            match kind
+            {
+                "w" | "W" | "v" | "V" | "C" => (),
+                _ => symbols.push(name.to_string())
+            }
+        }
+    }
+
+    Ok(symbols)
+}
+
+/* Rewrite GNU as messages of the form 'file.s:123: Error: msg' in the style of rustc's diagnostics,
+   with the source file's absolute path, so that IDEs and terminals can link to the offending line.
+   'Assembler messages:' headers are dropped, and any other lines are passed through unchanged
+   => output = assembler's stderr output
+      context = build context
+   <= returns rewritten output
+*/
+fn as_diagnostics(output: &String, context: &Context) -> String
+{
+    let re = Regex::new(r"^(?P<file>[^:]+):(?P<line>[0-9]+): (?P<level>Error|Warning|Info): (?P<msg>.*)$").unwrap();
+    let mut diagnostics = String::new();
+
+    for line in output.lines()
+    {
+        if line.ends_with(": Assembler messages:") == true
+        {
+            continue;
+        }
+
+        match re.captures(line)
+        {
+            Some(m) =>
+            {
+                let file = match fs::canonicalize(context.host.path(&m["file"]))
+                {
+                    Ok(p) => p.to_string_lossy().into_owned(),
+                    Err(_) => m["file"].to_string()
+                };
+
+                diagnostics.push_str(format!("{}: {}\n  --> {}:{}\n", &m["level"].to_lowercase(), &m["msg"], file, &m["line"]).as_str());
+            },
+            None =>
+            {
+                diagnostics.push_str(line);
+                diagnostics.push('\n');
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/* Create an archive containing all registered .o files and link with this archive */
+fn link_archive(context: &mut Context) -> Result<(), MasonError>
+{
+    let archive_name = String::from(ARCHIVE_NAME);
+    let archive_path = format!("{}/{}", &context.output_dir, ARCHIVE_FILE);
+
+    /* add the objects in order of their member names, so that the same objects always give the same archive */
+    let mut objects: Vec<&String> = context.objects.iter().map(|obj| &obj.path).collect();
+    objects.sort_by(|a, b| (archive_member_name(a), a).cmp(&(archive_member_name(b), b)));
+
+    /* write the archive ourselves if asked, or else create it from the .o files with ar */
+    if context.builtin_ar == true
+    {
+        let mut members = Vec::new();
+        for obj in objects.iter()
+        {
+            match retry(Step::Read, context, || fs::read(obj))
+            {
+                Ok(contents) => members.push((archive_member_name(obj), contents)),
+                Err(e) => return Err(MasonError::host(format!("Can't read {} to archive it: {}", obj, e)))
+            }
+        }
+
+        write_archive(&archive_path, &members, context).map_err(MasonError::host)?;
+    }
+    else
+    {
+        archive_with_ar(&archive_path, &objects, context)?;
+    }
+
+    /* tell the linker where to find our archive, and ensure anything relying on it is rebuilt as necessary */
+    println!("cargo:rustc-link-search={}", &context.output_dir);
+    println!("cargo:rustc-link-lib=static={}", &archive_name);
+    Ok(())
+}
+
+/* Create the bundle's archive from the objects to link with by running ar in deterministic mode, so that
+   members have zero timestamps and owners, and the same objects always give the same archive
+   => archive_path = path of archive to create
+      objects = paths of objects to archive, in order
+      context = build context
+*/
+fn archive_with_ar(archive_path: &String, objects: &Vec<&String>, context: &Context) -> Result<(), MasonError>
+{
+    /* start afresh rather than updating the last build's archive, which may hold objects no longer in the bundle */
+    if let Err(e) = fs::remove_file(&archive_path)
+    {
+        if e.kind() != io::ErrorKind::NotFound
+        {
+            return Err(MasonError::host(format!("Can't remove old archive {}: {}", &archive_path, e)));
+        }
+    }
+
+    let mut cmd = Command::new(&context.ar_exec);
+    cmd.args(&context.tool_flags.ar).arg("crsD").arg(&archive_path);
+
+    /* add list of object files generated */
+    for obj in objects.iter()
+    {
+        cmd.arg(obj);
+    }
+
+    /* run command */
+    let result = run_tool(&mut cmd, format!("Failed to execute command to archive {}", &archive_path), &context)?;
+
+    if result.status.success() != true
+    {
+        return Err(MasonError::tool_failure(format!("Archiving {} failed:\n{}\n{}",
+            &archive_path, String::from_utf8_lossy(&result.stdout), String::from_utf8_lossy(&result.stderr))).with_command(&cmd, None));
+    }
+    Ok(())
+}
+
+/* generate the name an object is stored under in an archive, which is its leafname, as ar stores it
+   => path = path to object file
+   <= returns the member name */
+fn archive_member_name(path: &str) -> String
+{
+    file_leafname(path)
+}
+
+/* Write a GNU-format static archive holding the given members, in order, with a symbol index for the linker.
+   Members have zero timestamps and owners, so that the same members always give the same archive
+   => archive_path = path of archive to write
+      members = name and contents of each member
+      context = build context
+   <= returns a description of the failure, if the archive can't be written */
+fn write_archive(archive_path: &str, members: &Vec<(String, Vec<u8>)>, context: &Context) -> Result<(), String>
+{
+    let new_members: Vec<NewArchiveMember> = members.iter()
+        .map(|(name, contents)| NewArchiveMember::new(contents, &DEFAULT_OBJECT_READER, name.clone()))
+        .collect();
+
+    let mut archive = io::Cursor::new(Vec::new());
+    if let Err(e) = write_archive_to_stream(&mut archive, &new_members, ArchiveKind::Gnu, false, false)
+    {
+        return Err(format!("Can't generate archive {}: {}", archive_path, e));
+    }
+
+    match retry(Step::Write, context, || fs::write(archive_path, archive.get_ref()))
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Can't write archive {}: {}", archive_path, e))
+    }
+}
+
+/* Replace an archive's member stored under an object's name with the object, or add the object to the end of
+   the archive if it has no such member, as ar r does, and rewrite the archive's symbol index
+   => archive_path = path of archive to update
+      object_file = path of object to store in the archive
+      context = build context
+   <= returns a description of the failure, if the archive can't be updated */
+fn replace_archive_member(archive_path: &str, object_file: &str, context: &Context) -> Result<(), String>
+{
+    let data = match retry(Step::Read, context, || fs::read(archive_path))
+    {
+        Ok(data) => data,
+        Err(e) => return Err(format!("Can't read archive {}: {}", archive_path, e))
+    };
+    let object = match retry(Step::Read, context, || fs::read(object_file))
+    {
+        Ok(object) => object,
+        Err(e) => return Err(format!("Can't read {} to archive it: {}", object_file, e))
+    };
+
+    let archive = match ArchiveFile::parse(data.as_slice())
+    {
+        Ok(archive) => archive,
+        Err(e) => return Err(format!("Can't parse archive {}: {}", archive_path, e))
+    };
+
+    let name = archive_member_name(object_file);
+    let mut members = Vec::new();
+    let mut replaced = false;
+    for member in archive.members()
+    {
+        let member = match member
+        {
+            Ok(member) => member,
+            Err(e) => return Err(format!("Can't read a member of archive {}: {}", archive_path, e))
+        };
+
+        let member_name = String::from_utf8_lossy(member.name()).to_string();
+        if member_name == name
+        {
+            members.push((name.clone(), object.clone()));
+            replaced = true;
+            continue;
+        }
+
+        match member.data(data.as_slice())
+        {
+            Ok(contents) => members.push((member_name, contents.to_vec())),
+            Err(e) => return Err(format!("Can't read member {} of archive {}: {}", member_name, archive_path, e))
+        }
+    }
+
+    if replaced == false
+    {
+        members.push((name, object));
+    }
+
+    write_archive(archive_path, &members, context)
+}
+
+/* Write a list of the archive's objects, one per line, each line being the object's path and the input
+   it came from separated by a tab, to the output directory. The consuming crate can find this list from
+   the MASON_OBJECTS environment variable at compile time, eg using env!("MASON_OBJECTS")
+   => context = build context
+*/
+fn write_object_list(context: &Context) -> Result<(), MasonError>
+{
+    let mut list = String::new();
+    for obj in context.objects.iter()
+    {
+        list.push_str(format!("{}\t{}\n", &obj.path, &obj.origin).as_str());
+    }
+
+    let list_path = format!("{}/{}", &context.output_dir, OBJECT_LIST_FILE);
+    if let Err(e) = retry(Step::Write, context, || fs::write(&list_path, &list))
+    {
+        return Err(MasonError::host(format!("Cannot write object list {}: {}", &list_path, e)));
+    }
+
+    println!("cargo:rustc-env=MASON_OBJECTS={}", &list_path);
+    Ok(())
+}
+
+/* a symbol defined by an assembly source file, and the lines of source it covers */
+#[derive(Serialize)]
+struct SourceMapEntry
+{
+    symbol: String,  /* name of the symbol, as in the object's symbol table */
+    object: String,  /* path of the object defining it */
+    source: String,  /* path of the source file it was assembled from */
+    first_line: usize, /* line of the symbol's label, counting from 1 */
+    last_line: usize /* last line before the next symbol's label, or the end of the file */
+}
+
+/* Write a map of the symbols defined by each assembled source file to the source lines covering them, to the
+   output directory, so that panic and backtrace decoders can attribute an address in assembly code, eg in an
+   early-boot crash, to its source: find the symbol containing the address, and then its lines. A symbol's
+   lines run from its label to the line before the next symbol's label in the same file. The MASON_SOURCE_MAP
+   environment variable is set to the map's path when compiling the consuming crate
+   => sources = paths to the source files assembled
+      context = build context
+*/
+fn write_source_map(sources: &Vec<String>, context: &Context) -> Result<(), MasonError>
+{
+    let label = Regex::new(r"^\s*([A-Za-z_.$][A-Za-z0-9_.$]*):").unwrap();
+    let mut entries = Vec::new();
+
+    for obj in context.objects.iter().filter(|o| sources.contains(&o.origin) == true)
+    {
+        let contents = match retry(Step::Read, context, || fs::read_to_string(context.host.path(&obj.origin)))
+        {
+            Ok(c) => c,
+            Err(e) => return Err(MasonError::host(format!("Cannot read source file {} to map its symbols: {}", &obj.origin, e)))
+        };
+
+        /* only labels that made it into the object's symbol table can be found by decoders */
+        let symbols: HashSet<String> = defined_symbols(&obj.path, false, context)?.into_iter().collect();
+        let labels: Vec<(usize, String)> = contents.lines().enumerate()
+            .filter_map(|(n, line)| label.captures(line).map(|c| (n + 1, c[1].to_string())))
+            .filter(|(_, name)| symbols.contains(name) == true)
+            .collect();
+
+        let line_count = contents.lines().count();
+        for (i, (line, name)) in labels.iter().enumerate()
+        {
+            entries.push(SourceMapEntry
+            {
+                symbol: name.clone(),
+                object: obj.path.clone(),
+                source: obj.origin.clone(),
+                first_line: *line,
+                last_line: labels.get(i + 1).map(|(next, _)| next - 1).unwrap_or(line_count)
+            });
+        }
+    }
+
+    let map_path = format!("{}/{}", &context.output_dir, SOURCE_MAP_FILE);
+    let json = serde_json::to_string_pretty(&entries).unwrap();
+    if let Err(e) = retry(Step::Write, context, || fs::write(&map_path, &json))
+    {
+        return Err(MasonError::host(format!("Cannot write source map {}: {}", &map_path, e)));
+    }
+
+    println!("cargo:rustc-env=MASON_SOURCE_MAP={}", &map_path);
+    Ok(())
+}
+
+/* Write Rust source to the output directory exposing each packaged binary as a static MasonBlob,
+   which dereferences to the binary's contents as a [u8] slice. The static's name is the binary's
+   leafname in upper case with non-alphanumeric characters replaced by _, eg FW_BIN for fw.bin, and _
+   prepended if it starts with a digit. The binary's symbols are declared under Rust identifiers made
+   the same way, and linked by their real names, as these can hold characters Rust doesn't allow, eg -.
+   A plain &[u8] can't be used as the binary's address and size are only known at link time
+   => guests = guest virtual machines from the config file, if any
+      context = build context
+   <= returns the failure, eg if two binaries would have statics of the same name
+*/
+fn write_rust_shim(guests: &Option<BTreeMap<String, Guest>>, context: &Context) -> Result<(), MasonError>
+{
+    let mut shim = String::from("/* generated by Mason: do not edit. include!() this file to access packaged binaries */\n\n");
+    shim.push_str("pub struct MasonBlob\n{\n    start: &'static [u8; 0],\n    end: &'static [u8; 0],\n    pub hash: u64,\n    pub load_address: Option<usize>\n}\n\n");
+    shim.push_str("impl core::ops::Deref for MasonBlob\n{\n    type Target = [u8];\n\n");
+    shim.push_str("    fn deref(&self) -> &[u8]\n    {\n");
+    shim.push_str("        let start = self.start.as_ptr();\n");
+    shim.push_str("        unsafe { core::slice::from_raw_parts(start, self.end.as_ptr() as usize - start as usize) }\n");
+    shim.push_str("    }\n}\n");
+
+    let mut table = Vec::new();
+    let mut statics: HashMap<String, &String> = HashMap::new();
+    for payload in context.payloads.iter()
+    {
+        let leafname = file_leafname(&payload.path);
+        let name = payload_symbols(&payload.path, &context.symbol_namespace).rust_static;
+        if let Some(other) = statics.insert(name.clone(), &payload.path)
+        {
+            return Err(MasonError::config(format!("Binary files {} and {} would both be {} in {}: rename one of them",
+                other, &payload.path, &name, RUST_SHIM_FILE)));
+        }
+
+        let load_address = match payload.load_address
+        {
+            Some(address) => format!("Some(0x{:x})", address),
+            None => String::from("None")
+        };
+
+        shim.push_str(format!("\n/* {} */\n", &payload.path).as_str());
+        let (start, end) = (format!("{}start", &payload.symbol_prefix), format!("{}end", &payload.symbol_prefix));
+        let (start_name, end_name) = (rust_identifier(&start), rust_identifier(&end));
+        shim.push_str(format!("unsafe extern \"C\"\n{{\n    #[link_name = \"{}\"]\n    static {}: [u8; 0];\n    #[link_name = \"{}\"]\n    static {}: [u8; 0];\n}}\n\n",
+            start.escape_default(), &start_name, end.escape_default(), &end_name).as_str());
+        shim.push_str(format!("pub static {}: MasonBlob = MasonBlob {{ start: unsafe {{ &{} }}, end: unsafe {{ &{} }}, hash: 0x{:x}, load_address: {} }};\n",
+            name, &start_name, &end_name, payload.hash, load_address).as_str());
+        table.push(format!("    (\"{}\", &{})", leafname.escape_default(), name));
+    }
+
+    /* list every payload, so that loaders can find them without knowing their names in advance */
+    shim.push_str(format!("\n/* every packaged binary, by leafname */\npub static MASON_BLOBS: [(&str, &MasonBlob); {}] =\n[\n{}\n];\n",
+        table.len(), table.join(",\n")).as_str());
+
+    /* describe each guest virtual machine, so that a hypervisor can create them at boot */
+    shim.push_str("\npub struct MasonGuest\n{\n    pub name: &'static str,\n    pub image: &'static MasonBlob,\n");
+    shim.push_str("    pub ram: u64,\n    pub vcpus: u32,\n    pub boot_args: &'static str\n}\n");
+
+    let mut catalog = Vec::new();
+    for (name, guest) in guests.iter().flatten()
+    {
+        let image = match context.payloads.iter().find(|payload| payload.path == guest.image)
+        {
+            Some(payload) => payload_symbols(&payload.path, &context.symbol_namespace).rust_static,
+            None => return Err(MasonError::config(format!("Image {} of guest {} wasn't packaged", &guest.image, name)))
+        };
+
+        catalog.push(format!("    MasonGuest {{ name: \"{}\", image: &{}, ram: 0x{:x}, vcpus: {}, boot_args: \"{}\" }}",
+            name, image, guest.ram, guest.vcpus, guest.boot_args.as_deref().unwrap_or("").escape_default()));
+    }
+
+    shim.push_str(format!("\n/* every guest virtual machine, by name */\npub static MASON_GUESTS: [MasonGuest; {}] =\n[\n{}\n];\n",
+        catalog.len(), catalog.join(",\n")).as_str());
+
+    let shim_path = format!("{}/{}", &context.output_dir, RUST_SHIM_FILE);
+    if let Err(e) = retry(Step::Write, context, || fs::write(&shim_path, &shim))
+    {
+        return Err(MasonError::host(format!("Cannot write Rust shim {}: {}", &shim_path, e)));
+    }
+    Ok(())
+}
+
+/* Update a repackaged binary's hash in the Rust source written by an earlier build
+   => payload = description of the repackaged binary
+      context = build context
+   <= returns Ok, or a description of why the Rust source couldn't be updated */
+fn update_shim_hash(payload: &Payload, context: &Context) -> Result<(), String>
+{
+    let shim_path = format!("{}/{}", &context.output_dir, RUST_SHIM_FILE);
+    let shim = match fs::read_to_string(&shim_path)
+    {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Can't read Rust shim {}: {}", &shim_path, e))
+    };
+
+    let name = payload_symbols(&payload.path, &context.symbol_namespace).rust_static;
+    let re = Regex::new(&format!(r"(?m)^(pub static {}: MasonBlob = .*hash: )0x[0-9a-f]+", regex::escape(&name))).unwrap();
+    if re.is_match(&shim) == false
+    {
+        return Err(format!("Rust shim {} doesn't describe {}: run a full build first", &shim_path, &payload.path));
+    }
+
+    let updated = re.replace(&shim, format!("${{1}}0x{:x}", payload.hash).as_str());
+    match retry(Step::Write, context, || fs::write(&shim_path, updated.as_bytes()))
+    {
+        Ok(()) => Ok(()),
+        Err(e) => Err(format!("Can't update Rust shim {}: {}", &shim_path, e))
+    }
+}
+
+/* Write a linker script fragment to the output directory describing the sections Mason generated.
+   Each section is wrapped in KEEP() so it survives --gc-sections, and the start and end of the whole
+   group are marked with PROVIDE()d symbols __mason_start and __mason_end. Each payload group gets an
+   output section of its own, holding its payloads' sections in order, marked in the same way. The fragment
+   is made of output section descriptions, meant to be INCLUDEd within a SECTIONS block of the crate's own
+   linker script
+   => auto = true to also write a wrapper script inserting the fragment after .rodata, and pass it to the linker
+      groups = payload groups from the config file, if any
+      context = build context
+*/
+fn write_link_script(auto: bool, groups: &Option<Vec<PayloadGroup>>, context: &mut Context) -> Result<(), MasonError>
+{
+    let mut script = String::from("/* generated by Mason: do not edit. INCLUDE this within a SECTIONS block */\n");
+
+    let mut grouped = HashSet::new();
+    if let Some(groups) = groups
+    {
+        for group in groups
+        {
+            script.push_str(format!(".mason.{} : ALIGN({})\n{{\n", &group.name, group.align.unwrap_or(8)).as_str());
+            script.push_str(format!("    PROVIDE(__mason_{}_start = .);\n", &group.name).as_str());
+
+            for file in group.files.iter()
+            {
+                let section = payload_section(file);
+                script.push_str(format!("    KEEP(*({}))\n", &section).as_str());
+                grouped.insert(section);
+            }
+
+            script.push_str(format!("    PROVIDE(__mason_{}_end = .);\n", &group.name).as_str());
+            script.push_str("}\n");
+        }
+    }
+
+    script.push_str(".mason : ALIGN(8)\n{\n");
+    script.push_str("    PROVIDE(__mason_start = .);\n");
+
+    /* sort the sections so the fragment is the same from one build to the next */
+    context.sections.sort();
+    for section in context.sections.iter().filter(|section| grouped.contains(*section) == false)
+    {
+        script.push_str(format!("    KEEP(*({}))\n", section).as_str());
+    }
+
+    script.push_str("    PROVIDE(__mason_end = .);\n");
+    script.push_str("}\n");
+
+    let script_path = format!("{}/{}", &context.output_dir, LINK_SCRIPT_FILE);
+    if let Err(e) = retry(Step::Write, &context, || fs::write(&script_path, &script))
+    {
+        return Err(MasonError::host(format!("Cannot write linker script fragment {}: {}", &script_path, e)));
+    }
+
+    if auto == true
+    {
+        let wrapper = format!("/* generated by Mason: do not edit */\nSECTIONS\n{{\n    INCLUDE {}\n}}\nINSERT AFTER .rodata;\n", LINK_SCRIPT_FILE);
+        let wrapper_path = format!("{}/{}", &context.output_dir, LINK_SCRIPT_WRAPPER_FILE);
+        if let Err(e) = retry(Step::Write, &context, || fs::write(&wrapper_path, &wrapper))
+        {
+            return Err(MasonError::host(format!("Cannot write linker script {}: {}", &wrapper_path, e)));
+        }
+
+        println!("cargo:rustc-link-arg=-T{}", &wrapper_path);
+    }
+    Ok(())
+}
+
+/* add a bootloader stage to a build order after the stages it embeds, if it isn't already in it
+   => name = name of stage to add
+      stages = stages from the config file, by name
+      visiting = stages whose embedded stages are being ordered, to detect cycles
+      order = build order to add to
+   <= returns Ok, or a description of why the stage can't be ordered */
+fn order_stage(name: &String, stages: &HashMap<String, &Stage>, visiting: &mut Vec<String>, order: &mut Vec<String>) -> Result<(), String>
+{
+    if order.contains(name) == true
+    {
+        return Ok(());
+    }
+
+    if visiting.contains(name) == true
+    {
+        return Err(format!("Stages {} embed each other", visiting.join(", ")));
+    }
+
+    let stage = match stages.get(name)
+    {
+        Some(s) => s,
+        None => return Err(format!("Stage {} embeds stage {}, which isn't defined", visiting.last().unwrap_or(name), name))
+    };
+
+    visiting.push(name.clone());
+    for embedded in stage.embeds.iter().flatten()
+    {
+        order_stage(embedded, stages, visiting, order)?;
+    }
+    visiting.pop();
+
+    order.push(name.clone());
+    Ok(())
+}
+
+/* turn a path within the initrd into the name it is stored under, without a leading /
+   => path = path within the initrd, from the config file
+   <= returns the name, or None if the path is empty or has . or .. parts */
+fn initrd_path(path: &str) -> Option<String>
+{
+    let parts: Vec<&str> = path.split('/').filter(|p| p.len() > 0).collect();
+    if parts.len() == 0 || parts.iter().any(|p| *p == "." || *p == "..") == true
+    {
+        return None;
+    }
+
+    Some(parts.join("/"))
+}
+
+/* Build the initrd as a newc-format cpio archive, as unpacked by Linux and others at boot. Each file's
+   parent directories are added ahead of it. Timestamps and owners are zeroed so the archive only changes
+   when its files do
+   => initrd = initrd settings from the config file
+      context = build context
+*/
+fn build_initrd(initrd: &Initrd, mut context: &mut Context) -> Result<(), MasonError>
+{
+    let mut archive = Vec::new();
+    let mut inode = 0;
+    let mut add = |archive: &mut Vec<u8>, name: &str, mode: u32, data: &[u8]|
+    {
+        inode = inode + 1;
+        let fields = [inode, mode, 0, 0, if mode & 0o040000 != 0 { 2 } else { 1 }, 0, data.len() as u32, 0, 0, 0, 0, name.len() as u32 + 1, 0];
+        archive.extend_from_slice(b"070701");
+        for field in fields.iter()
+        {
+            archive.extend_from_slice(format!("{:08x}", field).as_bytes());
+        }
+
+        archive.extend_from_slice(name.as_bytes());
+        archive.push(0);
+        while archive.len() % 4 != 0 { archive.push(0); }
+        archive.extend_from_slice(data);
+        while archive.len() % 4 != 0 { archive.push(0); }
+    };
+
+    let mut directories = BTreeSet::new();
+    for (path, file) in initrd.files.iter()
+    {
+        let name = initrd_path(path).unwrap();
+        let (source, mode) = match file
+        {
+            InitrdFile::Path(source) => (source, 0o644),
+            InitrdFile::Entry { source, mode } => (source, mode.unwrap_or(0o644))
+        };
+
+        let contents = match retry(Step::Read, &context, || fs::read(context.host.path(source)))
+        {
+            Ok(c) => c,
+            Err(e) => return Err(MasonError::config(format!("Can't read initrd file {}: {}", source, e)))
+        };
+        declare_input(source, &mut context);
+
+        let mut parent = String::new();
+        for part in name.split('/').rev().skip(1).collect::<Vec<&str>>().iter().rev()
+        {
+            parent = if parent.len() == 0 { part.to_string() } else { format!("{}/{}", parent, part) };
+            if directories.insert(parent.clone()) == true
+            {
+                add(&mut archive, &parent, 0o040755, &[]);
+            }
+        }
+
+        add(&mut archive, &name, 0o100000 | mode, &contents);
+    }
+
+    add(&mut archive, "TRAILER!!!", 0, &[]);
+
+    let output = format!("{}/{}", &context.output_dir, INITRD_FILE);
+    if let Err(e) = retry(Step::Write, &context, || fs::write(&output, &archive))
+    {
+        return Err(MasonError::host(format!("Cannot write initrd {}: {}", &output, e)));
+    }
+    Ok(())
+}
+
+/* return true if a name can be used as a symbol by the assemblers, ie it's made of letters, digits, _, . and $,
+   and doesn't start with a digit
+   => name = name to check */
+fn asm_symbol_valid(name: &str) -> bool
+{
+    let mut chars = name.chars();
+    match chars.next()
+    {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.' || c == '$' => (),
+        _ => return false
+    }
+
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$')
+}
+
+/* Write an assembly include file to the output directory, which is on each assembler's include path.
+   Constants become .set directives, or %define for nasm, and each table becomes a label followed by
+   the table's words, sized to the target's pointer width
+   => file = leafname of include file
+      include = constants and tables to write
+      context = build context
+*/
+fn write_asm_include(file: &str, include: &AsmInclude, context: &Context) -> Result<(), MasonError>
+{
+    if file.len() == 0 || file.contains('/') == true
+    {
+        return Err(MasonError::config(format!("Invalid assembly include file name {:?}: use a leafname", file)));
+    }
+
+    let nasm = include.syntax == Assembler::Nasm;
+    let comment = if nasm == true { ";" } else { "#" };
+    let (directive, mask) = match (context.target.ptr_width, nasm)
+    {
+        (64, false) => (".quad", u64::MAX),
+        (32, false) => (".long", 0xffff_ffff),
+        (_, false) => (".short", 0xffff),
+        (64, true) => ("dq", u64::MAX),
+        (32, true) => ("dd", 0xffff_ffff),
+        (_, true) => ("dw", 0xffff)
+    };
+
+    let mut text = format!("{} generated by Mason: do not edit\n", comment);
+    for (name, value) in include.constants.iter()
+    {
+        if asm_symbol_valid(name) == false
+        {
+            return Err(MasonError::config(format!("Invalid constant name {:?} in assembly include file {}", name, file)));
+        }
+
+        match nasm
+        {
+            true => text.push_str(format!("%define {} {}\n", name, value).as_str()),
+            false => text.push_str(format!(".set {}, {}\n", name, value).as_str())
+        }
+    }
+
+    for (label, values) in include.tables.iter()
+    {
+        if asm_symbol_valid(label) == false
+        {
+            return Err(MasonError::config(format!("Invalid table label {:?} in assembly include file {}", label, file)));
+        }
+
+        text.push_str(format!("{}:\n", label).as_str());
+        for chunk in values.chunks(8)
+        {
+            let words: Vec<String> = chunk.iter().map(|v| format!("0x{:x}", v & mask)).collect();
+            text.push_str(format!("    {} {}\n", directive, words.join(", ")).as_str());
+        }
+    }
+
+    let path = format!("{}/{}", &context.output_dir, file);
+    if let Err(e) = retry(Step::Write, context, || fs::write(&path, &text))
+    {
+        return Err(MasonError::host(format!("Cannot write assembly include file {}: {}", &path, e)));
+    }
+    Ok(())
+}
+
+/* generate the path of the raw binary a bootloader stage is linked into
+   => name = name of stage
+      context = build context
+   <= returns path to the stage's binary */
+fn stage_output(name: &str, context: &Context) -> String
+{
+    format!("{}/{}/{}.bin", &context.output_dir, STAGES_DIR, name)
+}
+
+/* Build a bootloader stage: assemble its source, package its binary files and the stages it
+   embeds, link the lot using its linker script, if any, and convert the result to a raw binary
+   => stage = stage to build
+      context = build context
+*/
+fn build_stage(stage: &Stage, mut context: &mut Context) -> Result<(), MasonError>
+{
+    let stage_dir = format!("{}/{}/{}", &context.output_dir, STAGES_DIR, &stage.name);
+    if let Err(e) = retry(Step::Write, &context, || fs::create_dir_all(&stage_dir))
+    {
+        return Err(MasonError::host(format!("Cannot create directory {} for stage {}: {}", &stage_dir, &stage.name, e)));
+    }
+
+    let mut objects = Vec::new();
+    for dir in stage.asm_dirs.iter().flatten()
+    {
+        for source in scan_directory(dir.clone(), true, &context)?
+        {
+            if let Some(object_file) = assemble_into(&source, &stage_dir, &mut context)?
+            {
+                objects.push(object_file);
+            }
+        }
+    }
+
+    let mut binaries: Vec<String> = stage.include_files.iter().flatten().cloned().collect();
+    binaries.extend(stage.embeds.iter().flatten().map(|name| stage_output(name, &context)));
+    for binary in binaries.iter()
+    {
+        let object_file = format!("{}/{}.o", &stage_dir, object_name(binary, PAYLOAD_OBJECT_NAME, &context));
+        make_payload_object(binary, &object_file, None, &mut context)?;
+        objects.push(object_file);
+    }
+
+    /* link the stage's objects */
+    let elf = format!("{}/{}/{}.elf", &context.output_dir, STAGES_DIR, &stage.name);
+    let mut ld = Command::new(&context.ld_exec);
+    ld.args(&context.target.ld_flags);
+    if let Some(script) = &stage.link_script
+    {
+        ld.arg("-T").arg(script);
+        declare_input(script, &mut context);
+    }
+
+    ld.arg("-o").arg(&elf).args(&objects);
+    let result = run_tool(&mut ld, format!("Couldn't run command to link stage {}", &stage.name), &context)?;
+
+    if result.status.success() != true
+    {
+        return Err(MasonError::tool_failure(format!("Linking stage {} failed:\n{}\n{}",
+            &stage.name, String::from_utf8_lossy(&result.stdout), String::from_utf8_lossy(&result.stderr))).with_command(&ld, None));
+    }
+
+    /* and convert it to a raw binary, ready to be embedded */
+    let output = stage_output(&stage.name, &context);
+    let mut objcopy = Command::new(&context.oc_exec);
+    objcopy.args(&context.tool_flags.objcopy).arg("-O").arg("binary").arg(&elf).arg(&output);
+    let result = run_tool(&mut objcopy, format!("Couldn't run command to convert stage {} to a raw binary", &stage.name), &context)?;
+
+    if result.status.success() != true
+    {
+        return Err(MasonError::tool_failure(format!("Converting stage {} to a raw binary failed:\n{}\n{}",
+            &stage.name, String::from_utf8_lossy(&result.stdout), String::from_utf8_lossy(&result.stderr))).with_command(&objcopy, None));
+    }
+    Ok(())
+}
+
+/* find, read, and parse the config file
+   => triple = target triple string to select settings for
+      host = environment the build runs in
+   <= returns the config file's settings for the target, or the failure */
+fn parse_config_file(triple: &String, host: &HostEnv) -> Result<Manifest, MasonError>
+{
+    let config_location = match search_for_config(CONFIG_FILE, host)
+    {
+        Some(p) => p,
+        None => return Err(MasonError::config(format!("Can't find configuration file {:?} in host file system", CONFIG_FILE)))
+    };
+    let file = config_location.to_string_lossy().to_string();
+
+    let mut files = Vec::new();
+    let value = match load_config_value(&config_location, &mut Vec::new(), &mut files)
+    {
+        Ok(v) => v,
+        Err(e) => return Err(MasonError::config(e).in_stage("config").with_file(&file))
+    };
+
+    let config: Config = match value.try_into()
+    {
+        Ok(c) => c,
+        Err(e) => return Err(MasonError::config(format!("Can't parse configuration file {:?}: {}", config_location, e))
+            .in_stage("config").with_file(&file))
+    };
+
+    match manifest_from_config(config, triple.as_str())
+    {
+        Ok(mut m) =>
+        {
+            m.config_files = files.iter().map(|f| f.to_string_lossy().to_string()).collect();
+            Ok(m)
+        },
+        Err(e) => Err(MasonError::config(format!("Can't parse configuration file {:?}: {}", config_location, e))
+            .in_stage("config").with_file(&file))
+    }
+}
+
+/* read a config file and, if it extends another, merge it over the settings of that file
+   => path = path of config file to read
+      chain = paths of the config files that extend this one, to detect cycles
+      files = paths of the config files read so far, to add this one and those it extends to
+   <= returns the file's merged settings, or a description of why they couldn't be read */
+fn load_config_value(path: &Path, chain: &mut Vec<PathBuf>, files: &mut Vec<PathBuf>) -> Result<toml::Value, String>
+{
+    let absolute = fs::canonicalize(path).unwrap_or(path.to_path_buf());
+    if chain.contains(&absolute) == true
+    {
+        return Err(format!("Configuration file {:?} extends itself", path));
+    }
+
+    let contents = match fs::read_to_string(path)
+    {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Can't read configuration file {:?} in host file system: {}", path, e))
+    };
+    files.push(path.to_path_buf());
+
+    let mut value: toml::Value = match toml::from_str(&contents)
+    {
+        Ok(v) => v,
+        Err(e) => return Err(format!("Can't parse configuration file {:?}: {}", path, e))
+    };
+
+    let base = match value.as_table_mut().and_then(|t| t.remove("extends"))
+    {
+        Some(toml::Value::String(base)) => base,
+        Some(_) => return Err(format!("Configuration file {:?} has an extends setting that isn't a pathname", path)),
+        None => return Ok(value)
+    };
+
+    /* the file extended is found relative to the file extending it */
+    let base_path = match path.parent()
+    {
+        Some(dir) => dir.join(&base),
+        None => PathBuf::from(&base)
+    };
+
+    chain.push(absolute);
+    let base_value = load_config_value(&base_path, chain, files)?;
+    chain.pop();
+
+    Ok(merge_config_values(base_value, value))
+}
+
+/* merge the settings of a config file over those of the file it extends. Tables are merged key by key,
+   arrays are joined, with the base file's entries first, and other values are replaced
+   => base = settings of the file extended
+      over = settings of the file extending it
+   <= returns the merged settings */
+fn merge_config_values(base: toml::Value, over: toml::Value) -> toml::Value
+{
+    match (base, over)
+    {
+        (toml::Value::Table(mut base), toml::Value::Table(over)) =>
+        {
+            for (key, value) in over
+            {
+                let merged = match base.remove(&key)
+                {
+                    Some(existing) => merge_config_values(existing, value),
+                    None => value
+                };
+                base.insert(key, merged);
+            }
+            toml::Value::Table(base)
+        },
+        (toml::Value::Array(mut base), toml::Value::Array(over)) =>
+        {
+            base.extend(over);
+            toml::Value::Array(base)
+        },
+        (_, over) => over
+    }
+}
+
+/* parse and check the contents of a config file, and merge its defaults with the target's entry.
+   this never panics nor exits, whatever it is given, so that it can be fuzzed, as by fuzz/. extends is
+   ignored, as it would need the file system: parse_config_file() merges extended files
+   => contents = text of the config file
+      triple = target triple string to select settings for
+   <= returns the config file's settings for the target, or a description of what's wrong with it */
+pub fn parse_manifest(contents: &str, triple: &str) -> Result<Manifest, String>
+{
+    match toml::from_str(contents)
+    {
+        Ok(config) => manifest_from_config(config, triple),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/* check the settings of a config file, and merge them for the given target
+   => config = settings read from the config file, and any it extends
+      triple = target triple to merge the settings for
+   <= returns the target's settings, or a description of what's wrong with them */
+fn manifest_from_config(config: Config, triple: &str) -> Result<Manifest, String>
+{
+    if let Some(namespace) = &config.symbol_namespace
+    {
+        if namespace.len() == 0 || namespace.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') == false
+        {
+            return Err(format!("Invalid symbol_namespace {:?}: use only letters, digits and underscores", namespace));
+        }
+    }
+
+    if let Some(conflicts) = &config.symbol_conflicts
+    {
+        for conflict in conflicts
+        {
+            if let Err(e) = Regex::new(format!("^(?:{})$", &conflict.pattern).as_str())
+            {
+                return Err(format!("Invalid symbol_conflicts pattern {:?}: {}", &conflict.pattern, e));
+            }
+
+            if conflict.policy == ConflictPolicy::Rename && conflict.prefix.is_none() == true
+            {
+                return Err(format!("symbol_conflicts pattern {:?} uses the rename policy but has no prefix", &conflict.pattern));
+            }
+        }
+    }
+
+    if let Some(groups) = &config.payload_groups
+    {
+        let mut grouped = HashSet::new();
+        for group in groups
+        {
+            if group.name.len() == 0 || group.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') == false
+            {
+                return Err(format!("Invalid payload group name {:?}: use only letters, digits and underscores", &group.name));
+            }
+
+            if let Some(align) = group.align
+            {
+                if align.is_power_of_two() == false
+                {
+                    return Err(format!("Invalid alignment {} for payload group {}: use a power of two", align, &group.name));
+                }
+            }
+
+            for file in group.files.iter()
+            {
+                if grouped.insert(file) == false
+                {
+                    return Err(format!("Binary file {} is in more than one payload group", file));
+                }
+            }
+        }
+    }
+
+    if let Some(assemblers) = &config.assemblers
+    {
+        for extension in assemblers.keys()
+        {
+            if extension.len() == 0 || extension.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') == false
+            {
+                return Err(format!("Invalid assembler file extension {:?}: use only letters, digits and underscores, without a leading .", extension));
+            }
+        }
+    }
+
+    if let Some(constants) = &config.asm_constants
+    {
+        for name in constants.keys()
+        {
+            if asm_symbol_valid(name) == false
+            {
+                return Err(format!("Invalid asm_constants name {:?}: use letters, digits, underscores, . and $, not starting with a digit", name));
+            }
+        }
+    }
+
+    if let Some(guests) = &config.guests
+    {
+        for (name, guest) in guests.iter()
+        {
+            if name.len() == 0 || name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') == false
+            {
+                return Err(format!("Invalid guest name {:?}: use only letters, digits and underscores", name));
+            }
+
+            if guest.ram == 0 || guest.vcpus == 0
+            {
+                return Err(format!("Guest {} must have a non-zero ram size and number of vcpus", name));
+            }
+        }
+    }
+
+    if let Some(initrd) = &config.initrd
+    {
+        if initrd.format.as_deref().unwrap_or(INITRD_FORMAT) != INITRD_FORMAT
+        {
+            return Err(format!("Invalid initrd format {:?}: use {:?}", initrd.format.as_ref().unwrap(), INITRD_FORMAT));
+        }
+
+        /* paths that differ only by slashes, eg /bin/sh and bin/sh, are stored under the same name */
+        let mut names: BTreeMap<String, &String> = BTreeMap::new();
+        for (path, file) in initrd.files.iter()
+        {
+            let name = match initrd_path(path)
+            {
+                Some(name) => name,
+                None => return Err(format!("Invalid initrd path {:?}: use a path to a file with no . or .. parts", path))
+            };
+            if let Some(other) = names.insert(name.clone(), path)
+            {
+                return Err(format!("Initrd paths {:?} and {:?} both name the file {}", other, path, name));
+            }
+
+            if let InitrdFile::Entry { mode: Some(mode), .. } = file
+            {
+                if *mode > 0o7777
+                {
+                    return Err(format!("Invalid mode 0o{:o} for initrd file {}: use permission bits only", mode, path));
+                }
+            }
+        }
+
+        /* a file's parent directories are created for it, so no other file can be stored at one of them */
+        for name in names.keys()
+        {
+            let mut parent = String::new();
+            for part in name.split('/').rev().skip(1).collect::<Vec<&str>>().iter().rev()
+            {
+                parent = if parent.len() == 0 { part.to_string() } else { format!("{}/{}", parent, part) };
+                if let Some(path) = names.get(&parent)
+                {
+                    return Err(format!("Initrd path {:?} names a file, but it's also the directory holding {}", path, name));
+                }
+            }
+        }
+    }
+
+    if let Some(template) = &config.object_name
+    {
+        let placeholders = Regex::new(r"\{[a-z]*\}").unwrap();
+        let rest = placeholders.replace_all(template, |c: &regex::Captures| match &c[0]
+        {
+            "{dir}" | "{stem}" | "{ext}" | "{name}" => String::from("x"),
+            other => other.to_string()
+        });
+
+        if template.len() == 0 || rest.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.') == false
+        {
+            return Err(format!("Invalid object_name {:?}: use {{dir}}, {{stem}}, {{ext}}, {{name}}, letters, digits, _, - and .", template));
+        }
+    }
+
+    if let Some(lint) = &config.asm_lint
+    {
+        if lint.command.len() == 0 || lint.command.iter().any(|a| a.contains("{source}")) == false
+        {
+            return Err(String::from("asm_lint command must be given the source file to check as {source}"));
+        }
+    }
+
+    for required in config.requires_env.iter().flatten()
+    {
+        if required.name().len() == 0 || required.name().contains('=') == true || required.name().contains('\0') == true
+        {
+            return Err(format!("Invalid requires_env name {:?}: use a name without = or NUL characters", required.name()));
+        }
+    }
+
+    if config.defaults.as_ref().and_then(|d| d.aliases.as_ref()).is_some() == true
+    {
+        return Err(String::from("aliases can only be given in a target section, not in defaults"));
+    }
+
+    /* each alias must select exactly one target section */
+    if let Some(targets) = &config.target
+    {
+        let mut aliased = HashMap::new();
+        for (triple, entry) in targets.iter()
+        {
+            for alias in entry.aliases.iter().flatten()
+            {
+                if targets.contains_key(alias) == true
+                {
+                    return Err(format!("Target {} lists {} as an alias, but it has its own target section", triple, alias));
+                }
+
+                if let Some(other) = aliased.insert(alias, triple)
+                {
+                    return Err(format!("{} is listed as an alias of both targets {} and {}", alias, other, triple));
+                }
+            }
+        }
+    }
+
+    if let Some(targets) = &config.targets
+    {
+        for (triple, entry) in targets.iter()
+        {
+            if [16, 32, 64].contains(&entry.ptr_width) == false
+            {
+                return Err(format!("Invalid ptr_width {} for target {}: use 16, 32, or 64", entry.ptr_width, triple));
+            }
+
+            if [0, 32, 64, 128].contains(&entry.fp_width) == false
+            {
+                return Err(format!("Invalid fp_width {} for target {}: use 0, 32, 64, or 128", entry.fp_width, triple));
+            }
+        }
+    }
+
+    /* order the bootloader stages so that each is built after those it embeds */
+    let mut stages = Vec::new();
+    if let Some(list) = &config.stages
+    {
+        let mut named = HashMap::new();
+        for stage in list
+        {
+            if stage.name.len() == 0 || stage.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') == false
+            {
+                return Err(format!("Invalid stage name {:?}: use only letters, digits and underscores", &stage.name));
+            }
+
+            if named.insert(stage.name.clone(), stage).is_some() == true
+            {
+                return Err(format!("Stage {} is defined more than once", &stage.name));
+            }
+        }
+
+        for stage in list
+        {
+            order_stage(&stage.name, &named, &mut Vec::new(), &mut stages)?;
+        }
+    }
+
+    /* populate tables with paths of files to include and assemble, from the defaults and then the target */
+    let mut inputs = Inputs
+    {
+        include_files: HashSet::new(),
+        include_settings: HashMap::new(),
+        asm_dirs: HashMap::new(),
+        object_files: HashSet::new(),
+        lib_files: HashSet::new(),
+        stages: stages
+    };
+
+    let target = target_entry(&config, triple);
+
+    if let Some(defaults) = &config.defaults
+    {
+        add_file_paths_from_config(defaults, &mut inputs);
+    }
+
+    if let Some(entry) = target
+    {
+        add_file_paths_from_config(entry, &mut inputs);
+    }
+
+    if let Some(groups) = &config.payload_groups
+    {
+        for group in groups
+        {
+            inputs.include_files.extend(group.files.iter().cloned());
+        }
+    }
+
+    if let Some(guests) = &config.guests
+    {
+        inputs.include_files.extend(guests.values().map(|guest| guest.image.clone()));
+    }
+
+    /* objects and other files generated from an input are named after its leafname, so each input needs one */
+    let mut listed: Vec<&String> = inputs.include_files.iter().chain(inputs.object_files.iter()).chain(inputs.lib_files.iter()).collect();
+    listed.sort();
+    if let Some(path) = listed.iter().find(|path| Path::new(path.as_str()).file_name().is_none() == true)
+    {
+        return Err(format!("Invalid input file path {:?}: it has no leafname, eg it's empty or ends in ..", path));
+    }
+
+    for settings in inputs.include_settings.values()
+    {
+        for transform in settings.transforms.iter().flatten()
+        {
+            match transform
+            {
+                Transform::Compress { tool: Some(tool), .. } if compressor_args(tool).is_none() == true =>
+                    return Err(format!("Unknown compress tool {:?} for {}: use gzip, xz, bzip2, or zstd", tool, &settings.path)),
+                Transform::Compress { tool, dictionary: Some(true) } if tool.as_deref() != Some("zstd") =>
+                    return Err(format!("Compress transform for {} uses the shared dictionary, which needs tool = \"zstd\"", &settings.path)),
+                Transform::Sign { command } if command.len() == 0 || command.iter().any(|a| a.contains("{output}")) == false =>
+                    return Err(format!("Sign command for {} must write the signed file to {{output}}", &settings.path)),
+                Transform::Objcopy { output_target: Some(target), .. } if target.len() == 0 =>
+                    return Err(format!("Empty objcopy output_target for {}: give an object format, eg binary", &settings.path)),
+                Transform::Objcopy { only_sections, remove_sections, .. }
+                    if only_sections.iter().chain(remove_sections.iter()).flatten().any(|s| s.len() == 0) == true =>
+                    return Err(format!("Empty section name in objcopy transform for {}", &settings.path)),
+                Transform::Pad { align: Some(align), .. } if align.is_power_of_two() == false =>
+                    return Err(format!("Invalid pad alignment {} for {}: use a power of two", align, &settings.path)),
+                _ => ()
+            }
+        }
+    }
+
+    let object_format = entry_setting(&config.defaults, target, |e| &e.object_format);
+
+    let ptr_width = entry_setting(&config.defaults, target, |e| &e.ptr_width);
+    if let Some(width) = ptr_width
+    {
+        if [16, 32, 64].contains(&width) == false
+        {
+            return Err(format!("Invalid ptr_width {}: use 16, 32, or 64", width));
+        }
+    }
+
+    let fp_width = entry_setting(&config.defaults, target, |e| &e.fp_width);
+    if let Some(width) = fp_width
+    {
+        if [0, 32, 64, 128].contains(&width) == false
+        {
+            return Err(format!("Invalid fp_width {}: use 0, 32, 64, or 128", width));
+        }
+    }
+
+    let float_abi = entry_setting(&config.defaults, target, |e| &e.float_abi);
+    let gnu_prefix = entry_setting(&config.defaults, target, |e| &e.gnu_prefix);
+
+    let tool_flags = ToolFlags
+    {
+        ar: entry_flags(&config.defaults, target, |e| &e.ar_flags),
+        objcopy: entry_flags(&config.defaults, target, |e| &e.objcopy_flags)
+    };
+    let ld_r_flags = entry_setting(&config.defaults, target, |e| &e.ld_r_flags).is_some();
+
+    let libgcc = entry_setting(&config.defaults, target, |e| &e.libgcc);
+    let libgcc_flags = entry_flags(&config.defaults, target, |e| &e.libgcc_flags);
+
+    let mut binutils_versions = VersionRange::default();
+    for (key, version, limit) in [("min_version", entry_setting(&config.defaults, target, |e| &e.min_version), &mut binutils_versions.min),
+                                  ("max_version", entry_setting(&config.defaults, target, |e| &e.max_version), &mut binutils_versions.max)]
+    {
+        if let Some(version) = version
+        {
+            match parse_version(&version)
+            {
+                Some(parts) => *limit = Some(parts),
+                None => return Err(format!("Invalid {} '{}': use a version number, eg 2.38", key, version))
+            }
+        }
+    }
+
+    if let (Some(min), Some(max)) = (&binutils_versions.min, &binutils_versions.max)
+    {
+        if version_cmp(min, max, max.len()) == std::cmp::Ordering::Greater
+        {
+            return Err(format!("min_version {} is newer than max_version {}", version_string(min), version_string(max)));
+        }
+    }
+
+    Ok(Manifest
+    {
+        config: config,
+        inputs: inputs,
+        object_format: object_format,
+        ptr_width: ptr_width,
+        fp_width: fp_width,
+        float_abi: float_abi,
+        gnu_prefix: gnu_prefix,
+        tool_flags: tool_flags,
+        ld_r_flags: ld_r_flags,
+        libgcc: libgcc,
+        libgcc_flags: libgcc_flags,
+        binutils_versions: binutils_versions,
+        config_files: Vec::new()
+    })
+}
+
+/* starting in the build's working directory, check for the presence of the
+   required config file, and if it's not there, check inside the parent.
+   continue up the host file system tree until after hitting the root of the
+   cargo workspace, if cargo metadata can describe it, or else the root node.
+   this function gives up after SEARCH_MAX iterations to avoid infinite loops.
+   => leafname = config file leafname to look for
+      host = environment the build runs in
+   <= returns filename of found config file, or None if unsuccessful */
+fn search_for_config(leafname: &str, host: &HostEnv) -> Option<PathBuf>
+{
+    let mut path = PathBuf::from(&host.current_dir);
+
+    /* a config file outside the workspace belongs to another project, unless the build isn't in the workspace */
+    let canonical = |p: &Path| fs::canonicalize(p).unwrap_or(p.to_path_buf());
+    let root = host.workspace().map(|w| canonical(&w.root)).filter(|root| canonical(&path).starts_with(root) == true);
+
+    for _ in 0..SEARCH_MAX
+    {
+        let mut attempt = path.clone();
+        attempt.push(leafname);
+        if attempt.exists() == true
+        {
+            return Some(attempt);
+        }
+
+        if root.as_ref().map(|root| canonical(&path) == *root) == Some(true)
+        {
+            return None; /* don't look beyond the workspace */
+        }
+
+        path = match path.parent()
+        {
+            Some(p) => p.to_path_buf(),
+            None => return None /* give up if we can't go any higher in the tree */
+        }
+    }
+
+    None
+}
+
+/* look up the config file entry for a target
+   => config = parsed config file
+      triple = target triple string
+   <= returns the target's entry, or that of the target it's an alias of, or None if there isn't one */
+fn target_entry<'a>(config: &'a Config, triple: &str) -> Option<&'a ConfigEntry>
+{
+    match &config.target
+    {
+        Some(targets) => targets.get(triple).or_else(|| targets.values()
+            .find(|entry| entry.aliases.as_ref().map_or(false, |aliases| aliases.iter().any(|a| a == triple)))),
+        None => None
+    }
+}
+
+/* look up a setting that can be given in the defaults and overridden for the target
+   => defaults = defaults config entry, if any
+      target = target's config entry, if any
+      get = function returning the setting from a config entry
+   <= returns the target's setting if present, or else the defaults' setting if present, or None
+*/
+fn entry_setting<T: Clone>(defaults: &Option<ConfigEntry>, target: Option<&ConfigEntry>, get: fn(&ConfigEntry) -> &Option<T>) -> Option<T>
+{
+    if let Some(entry) = target
+    {
+        if let Some(setting) = get(entry)
+        {
+            return Some(setting.clone());
+        }
+    }
+
+    match defaults
+    {
+        Some(entry) => get(entry).clone(),
+        None => None
+    }
+}
+
+/* gather a list of options that can be given in the defaults and added to for the target
+   => defaults = defaults config entry, if any
+      target = target's config entry, if any
+      get = function returning the options from a config entry
+   <= returns the defaults' options followed by the target's, either of which may be absent
+*/
+fn entry_flags(defaults: &Option<ConfigEntry>, target: Option<&ConfigEntry>, get: fn(&ConfigEntry) -> &Option<Vec<String>>) -> Vec<String>
+{
+    defaults.as_ref().into_iter().chain(target).filter_map(|e| get(e).clone()).flatten().collect()
+}
+
+/* parse a ConfigEntry structure and add any found file paths to the given tables
+   => entry = ConFigEntry structure to parse
+      inputs = tables to which 'include_files', 'asm_dirs', 'object_files', and 'lib_files' string entries will be added
+*/
+fn add_file_paths_from_config(entry: &ConfigEntry, inputs: &mut Inputs)
+{
+    /* a binary file given as a table overrides the settings of any earlier listing of it */
+    match &entry.include_files
+    {
+        Some(files) => for file in files
+        {
+            match file
+            {
+                IncludeFile::Path(path) =>
+                {
+                    inputs.include_files.insert(path.to_string());
+                },
+                IncludeFile::Entry(settings) =>
+                {
+                    inputs.include_files.insert(settings.path.to_string());
+                    inputs.include_settings.insert(settings.path.to_string(), settings.clone());
+                }
+            }
+        },
+        None => ()
+    }
+
+    /* a directory may be empty only if every entry listing it allows this */
+    let allow_empty = entry.allow_empty.unwrap_or(true);
+    match &entry.asm_dirs
+    {
+        Some(files) => for file in files
+        {
+            let permitted = inputs.asm_dirs.entry(file.to_string()).or_insert(allow_empty);
+            *permitted = *permitted && allow_empty;
+        },
+        None => ()
+    }
+
+    match &entry.object_files
+    {
+        Some(files) => for file in files
+        {
+            inputs.object_files.insert(file.to_string());
+        },
+        None => ()
+    }
+
+    match &entry.lib_files
+    {
+        Some(files) => for file in files
+        {
+            inputs.lib_files.insert(file.to_string());
+        },
+        None => ()
+    }
+}
+
+/* give a command the environment the build runs in rather than the process's: its variables, with any the
+   command sets or removes itself applied on top, and its directory, unless the command has its own
+   => cmd = command to set up
+      host = environment the build runs in
+   <= returns the command */
+fn host_command<'c>(cmd: &'c mut Command, host: &HostEnv) -> &'c mut Command
+{
+    let overrides: Vec<(OsString, Option<OsString>)> = cmd.get_envs()
+        .map(|(var, value)| (var.to_os_string(), value.map(|v| v.to_os_string())))
+        .collect();
+
+    cmd.env_clear().envs(&host.vars);
+    for (var, value) in overrides
+    {
+        match value
+        {
+            Some(value) => cmd.env(var, value),
+            None => cmd.env_remove(var)
+        };
+    }
+
+    if cmd.get_current_dir().is_none() == true
+    {
+        cmd.current_dir(&host.current_dir);
+    }
+    cmd
+}
+
+/* Describe a command as a line that a POSIX shell would run the same way, quoting any of its
+   program and arguments that contain characters the shell would otherwise interpret
+   => cmd = command to describe
+   <= returns the command line */
+fn shell_command_line(cmd: &Command) -> String
+{
+    let mut words = vec![shell_word(&cmd.get_program().to_string_lossy())];
+    words.extend(cmd.get_args().map(|a| shell_word(&a.to_string_lossy())));
+    words.join(" ")
+}
+
+/* quote a word of a command line, if it contains characters a POSIX shell would otherwise interpret
+   => word = program or argument to quote
+   <= returns the word as it's to be given to the shell */
+fn shell_word(word: &str) -> String
+{
+    let plain = word.len() > 0 && word.chars().all(|c| c.is_ascii_alphanumeric() == true || "-_./=:,+@%".contains(c) == true);
+    match plain
+    {
+        true => word.to_string(),
+        false => format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/* Run an external tool and capture its output
+   => cmd = command to run
+      what = description of the command, for error messages
+      context = build context
+   <= returns the tool's output and exit status, which the caller must check, or the failure
+      if the tool can't be found or run
+*/
+fn run_tool(cmd: &mut Command, what: String, context: &Context) -> Result<Output, MasonError>
+{
+    host_command(cmd, &context.host);
+
+    /* in verbose mode, print the command line so that it can be pasted into a shell to rerun the tool */
+    if context.verbose == true
+    {
+        let line = shell_command_line(cmd);
+        match cmd.get_current_dir()
+        {
+            Some(dir) if dir != Path::new(&context.project_dir) =>
+                println!("cargo:warning=Mason: running (in {}) {}", dir.to_string_lossy(), line),
+            _ => println!("cargo:warning=Mason: running {}", line)
+        }
+    }
+
+    /* when auditing with strace, run the tool under strace, logging the files it opens to a file of its
+    own, so that tools run at once, eg by parallel assemblers, don't overwrite each other's traces */
+    let log = format!("{}/mason-audit-{}-{}.log", &context.output_dir, std::process::id(), TRACED_RUNS.fetch_add(1, Ordering::SeqCst));
+    let mut traced = Command::new("strace");
+    let cmd = match &context.audit
+    {
+        Some(audit) if audit.strace == true =>
+        {
+            traced.arg("-f").arg("-qq").arg("-e").arg("trace=open,openat").arg("-o").arg(&log);
+            traced.arg(cmd.get_program()).args(cmd.get_args());
+            for (var, value) in cmd.get_envs()
+            {
+                match value
+                {
+                    Some(value) => traced.env(var, value),
+                    None => traced.env_remove(var)
+                };
+            }
+            if let Some(dir) = cmd.get_current_dir()
+            {
+                traced.current_dir(dir);
+            }
+            &mut traced
+        },
+        _ => cmd
+    };
+
+    if let Some(allowed) = &context.sandbox_env
+    {
+        sandbox_command(cmd, allowed, context)?;
+    }
+
+    /* without a timeout, wait for as long as the tool takes. otherwise, kill
+    the tool if it runs out of time, and try again if retries are allowed */
+    let result = match context.tool_timeout
+    {
+        None => retry(Step::Spawn, context, || cmd.output()),
+        Some(timeout) =>
+        {
+            let mut attempt = 0;
+            loop
+            {
+                attempt = attempt + 1;
+                match retry(Step::Spawn, context, || run_with_timeout(cmd, timeout))
+                {
+                    Ok(Some(output)) => break Ok(output),
+                    Ok(None) => if attempt > context.tool_retries
+                    {
+                        return Err(MasonError::tool_failure(format!("{}: {:?} timed out after {} seconds, {} time(s)",
+                            what, cmd.get_program(), timeout.as_secs(), attempt)).with_command(cmd, None));
+                    }
+                    else
+                    {
+                        log_event(&context.host, LogLevel::Warning, "retry", format!("{:?} timed out after {} seconds, retrying", cmd.get_program(), timeout.as_secs()));
+                    },
+                    Err(e) => break Err(e)
+                }
+            }
+        }
+    };
+
+    match result
+    {
+        Ok(output) =>
+        {
+            if let Some(audit) = &context.audit
+            {
+                record_accesses(cmd, audit, &log, context);
+            }
+            Ok(output)
+        },
+        Err(e) => match e.kind()
+        {
+            ErrorKind::NotFound => Err(MasonError::missing_tool(format!("{}: {:?} not found", what, cmd.get_program()))),
+            _ => Err(MasonError::tool_failure(format!("{}: {}", what, e)).with_command(cmd, None))
+        }
+    }
+}
+
+/* Perform an operation, retrying it with an exponentially increasing delay if it fails with a possibly
+   transient error, such as an NFS stale file handle or a busy executable, up to the number of times
+   configured for the type of step the operation is part of
+   => step = type of step the operation is part of
+      context = build context
+      op = operation to perform
+   <= returns the operation's result, or its final error, noting how many attempts were made
+*/
+fn retry<T, F: FnMut() -> io::Result<T>>(step: Step, context: &Context, mut op: F) -> io::Result<T>
+{
+    let retries = match step
+    {
+        Step::Read => context.retry.read,
+        Step::Write => context.retry.write,
+        Step::Spawn => context.retry.spawn
+    };
+
+    let mut delay = context.retry.backoff;
+    let mut attempt = 0;
+    loop
+    {
+        attempt = attempt + 1;
+        match op()
+        {
+            Ok(result) => return Ok(result),
+            Err(e) =>
+            {
+                /* these errors won't go away by trying again */
+                let permanent = match e.kind()
+                {
+                    ErrorKind::NotFound | ErrorKind::PermissionDenied | ErrorKind::AlreadyExists |
+                    ErrorKind::InvalidInput | ErrorKind::InvalidData => true,
+                    _ => false
+                };
+
+                if permanent == true || attempt > retries
+                {
+                    return match attempt
+                    {
+                        1 => Err(e),
+                        n => Err(io::Error::new(e.kind(), format!("{} (gave up after {} attempts)", e, n)))
+                    };
+                }
+
+                log_event(&context.host, LogLevel::Warning, "retry", format!("{} step failed ({}), retrying in {} ms", step.name(), e, delay.as_millis()));
+                thread::sleep(delay);
+                delay = delay * 2;
+            }
+        }
+    }
+}
+
+/* types of step that can be retried on failure */
+#[derive(Clone, Copy)]
+enum Step
+{
+    Read,  /* reading input files */
+    Write, /* writing to the output directory */
+    Spawn  /* starting external tools */
+}
+
+impl Step
+{
+    pub fn name(&self) -> &'static str
+    {
+        match self
+        {
+            Step::Read => "read",
+            Step::Write => "write",
+            Step::Spawn => "spawn"
+        }
+    }
+}
+
+/* Run an external tool, killing it if it runs for longer than the given time
+   => cmd = command to run
+      timeout = maximum time to allow the tool to run
+   <= returns the tool's output, or None if it timed out, or an error if it couldn't be run
+*/
+fn run_with_timeout(cmd: &mut Command, timeout: Duration) -> io::Result<Option<Output>>
+{
+    let mut child = cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+
+    /* drain the tool's output as it runs so that it can't block on a full pipe */
+    let mut stdout = child.stdout.take().unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+    let stdout_reader = thread::spawn(move ||
+    {
+        let mut buffer = Vec::new();
+        let _ = stdout.read_to_end(&mut buffer);
+        buffer
+    });
+    let stderr_reader = thread::spawn(move ||
+    {
+        let mut buffer = Vec::new();
+        let _ = stderr.read_to_end(&mut buffer);
+        buffer
+    });
+
+    let started = Instant::now();
+    loop
+    {
+        if let Some(status) = child.try_wait()?
+        {
+            return Ok(Some(Output
+            {
+                status: status,
+                stdout: stdout_reader.join().unwrap(),
+                stderr: stderr_reader.join().unwrap()
+            }));
+        }
+
+        if started.elapsed() >= timeout
+        {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/* Record the files an external tool read, for the hermeticity audit
+   => cmd = command that was run, including strace if used
+      audit = audit record to update
+      log = path to strace's log file, if strace was used
+      context = build context
+*/
+fn record_accesses(cmd: &Command, audit: &Audit, log: &String, context: &Context)
+{
+    let working_dir = match cmd.get_current_dir()
+    {
+        Some(dir) => dir.to_string_lossy().into_owned(),
+        None => context.project_dir.clone()
+    };
+
+    let args: Vec<String> = cmd.get_args().map(|arg| arg.to_string_lossy().to_string()).collect();
+    let mut paths = Vec::new();
+    let tool;
+
+    if audit.strace == true
+    {
+        /* the traced tool follows strace's own seven arguments */
+        tool = args[6].clone();
+
+        /* pick out successful opens for reading, eg: 123 openat(AT_FDCWD, "start.s", O_RDONLY) = 3 */
+        let re = Regex::new(r#"open(at)?\((AT_FDCWD, )?"(?P<path>[^"]*)", (?P<flags>[^)]*)\) = [0-9]+"#).unwrap();
+        let trace = fs::read_to_string(log).unwrap_or(String::new());
+        for line in trace.lines()
+        {
+            if let Some(m) = re.captures(line)
+            {
+                if m["flags"].contains("O_WRONLY") == false && m["flags"].contains("O_DIRECTORY") == false
+                {
+                    paths.push(m["path"].to_string());
+                }
+            }
+        }
+        let _ = fs::remove_file(log);
+    }
+    else
+    {
+        tool = cmd.get_program().to_string_lossy().to_string();
+
+        /* without strace, assume any argument naming a file is read */
+        for arg in args.iter()
+        {
+            if Path::new(&working_dir).join(arg).is_file() == true
+            {
+                paths.push(arg.clone());
+            }
+        }
+    }
+
+    let mut accessed = audit.accessed.lock().unwrap();
+    for path in paths
+    {
+        accessed.entry(absolute_path(&path, &working_dir)).or_insert(tool.clone());
+    }
+}
+
+/* Warn about files read by external tools from within the project that
+   are not declared inputs of the build, if auditing is enabled
+   => context = build context
+*/
+fn report_audit(context: &Context)
+{
+    let audit = match &context.audit
+    {
+        Some(audit) => audit,
+        None => return
+    };
+
+    let project_dir = absolute_path(".", &context.project_dir);
+    let output_dir = absolute_path(".", &context.output_dir);
+
+    for (path, tool) in audit.accessed.lock().unwrap().iter()
+    {
+        if path.starts_with(&project_dir) == true && path.starts_with(&output_dir) == false &&
+            context.inputs.contains(path) == false
+        {
+            log_event(&context.host, LogLevel::Warning, "audit", format!("audit: {} read {}, which is not a declared input of the build", tool, path.display()));
+        }
+    }
+}
+
+/* an in-toto statement of a build's provenance, using the SLSA provenance predicate */
+#[derive(Serialize)]
+struct ProvenanceStatement
+{
+    #[serde(rename = "_type")]
+    statement_type: &'static str,
+    subject: Vec<ResourceDescriptor>,   /* files the build produced */
+    #[serde(rename = "predicateType")]
+    predicate_type: &'static str,
+    predicate: ProvenancePredicate
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProvenancePredicate
+{
+    build_definition: BuildDefinition,
+    run_details: RunDetails
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BuildDefinition
+{
+    build_type: &'static str,
+    external_parameters: BTreeMap<String, String>, /* what the build was asked to do, eg the target */
+    resolved_dependencies: Vec<ResourceDescriptor> /* files the build read */
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RunDetails
+{
+    builder: Builder
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Builder
+{
+    id: &'static str,
+    builder_dependencies: Vec<ResourceDescriptor> /* tools the build ran */
+}
+
+/* a file named in a provenance statement, with its SHA-256 digest */
+#[derive(Serialize)]
+struct ResourceDescriptor
+{
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri: Option<String>,
+    digest: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    annotations: BTreeMap<String, String>
+}
+
+/* Describe a file for a provenance statement
+   => path = path to the file
+      name = name to give the file, if any, rather than its path
+      context = build context
+   <= returns the file's description, including the hex SHA-256 digest of its contents, or the failure */
+fn resource_descriptor(path: &Path, name: Option<String>, context: &Context) -> Result<ResourceDescriptor, MasonError>
+{
+    let digest: String = hash_file(&path.to_string_lossy(), context)?.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(ResourceDescriptor
+    {
+        uri: match name
+        {
+            Some(_) => None,
+            None => Some(format!("file://{}", path.display()))
+        },
+        name: name,
+        digest: [(String::from("sha256"), digest)].iter().cloned().collect(),
+        annotations: BTreeMap::new()
+    })
+}
+
+/* Write an in-toto statement of the build's SLSA provenance to the output directory, so that release
+   pipelines can attest how the bundle was made. It names the files the build produced and read, with
+   their hashes, and the tools it ran, with their paths, hashes, and versions
+   => context = build context
+*/
+fn write_provenance(context: &Context) -> Result<(), MasonError>
+{
+    let mut subject = Vec::new();
+    for file in [ARCHIVE_FILE, RUST_SHIM_FILE, LINK_SCRIPT_FILE].iter()
+    {
+        let path = PathBuf::from(format!("{}/{}", &context.output_dir, file));
+        subject.push(resource_descriptor(&path, Some(file.to_string()), context)?);
+    }
+
+    /* the declared inputs include files that don't exist, eg missing optional binaries, which can't be hashed */
+    let dependencies: Vec<ResourceDescriptor> = context.inputs.iter()
+        .filter(|path| path.is_file() == true)
+        .map(|path| resource_descriptor(path, None, context))
+        .collect::<Result<_, MasonError>>()?;
+
+    /* the binutils are always used. other assemblers are only used if sources are mapped to them */
+    let mut tools = vec![&context.as_exec, &context.ar_exec, &context.ld_exec, &context.oc_exec, &context.nm_exec];
+    let nasm = String::from(NASM_EXEC);
+    let cpp = String::from(CPP_EXEC);
+    if context.assemblers.values().any(|a| *a == Assembler::Nasm) == true
+    {
+        tools.push(&nasm);
+    }
+    if context.assemblers.values().any(|a| *a == Assembler::CppGas) == true
+    {
+        tools.push(&cpp);
+    }
+
+    let mut builder_dependencies = Vec::new();
+    for tool in tools
+    {
+        let path = match find_in_path(tool, &context.host)
+        {
+            Some(p) => p,
+            None => continue
+        };
+
+        /* nasm gives its version with -v, and the rest with --version. only the first line is kept */
+        let flag = if tool == &nasm { "-v" } else { "--version" };
+        let mut descriptor = resource_descriptor(&path, Some(tool.clone()), context)?;
+        descriptor.uri = Some(format!("file://{}", path.display()));
+        if let Ok(output) = host_command(&mut Command::new(&path), &context.host).arg(flag).output()
+        {
+            if let Some(line) = String::from_utf8_lossy(&output.stdout).lines().next()
+            {
+                descriptor.annotations.insert(String::from("version"), line.to_string());
+            }
+        }
+        builder_dependencies.push(descriptor);
+    }
+
+    let mut parameters = BTreeMap::new();
+    parameters.insert(String::from("target"), context.host.var("TARGET").unwrap_or("").to_string());
+
+    let statement = ProvenanceStatement
+    {
+        statement_type: PROVENANCE_STATEMENT_TYPE,
+        subject: subject,
+        predicate_type: PROVENANCE_PREDICATE_TYPE,
+        predicate: ProvenancePredicate
+        {
+            build_definition: BuildDefinition
+            {
+                build_type: PROVENANCE_BUILD_TYPE,
+                external_parameters: parameters,
+                resolved_dependencies: dependencies
+            },
+            run_details: RunDetails
+            {
+                builder: Builder
+                {
+                    id: PROVENANCE_BUILDER_ID,
+                    builder_dependencies: builder_dependencies
+                }
+            }
+        }
+    };
+
+    let path = format!("{}/{}", &context.output_dir, PROVENANCE_FILE);
+    let contents = serde_json::to_string_pretty(&statement).unwrap();
+    if let Err(e) = retry(Step::Write, context, || fs::write(&path, &contents))
+    {
+        return Err(MasonError::host(format!("Cannot write provenance statement {}: {}", &path, e)));
+    }
+    Ok(())
+}
+
+/* a machine-readable report of the files a build processed */
+#[derive(Serialize)]
+struct BuildReport<'a>
+{
+    target: &'a str,            /* target triple the build was for */
+    seconds: f64,               /* time taken to carry out the build's plan */
+    files: Vec<ReportFile<'a>>  /* each object in the bundle, with the input it came from */
+}
+
+/* an object in a build report, and how it was made */
+#[derive(Serialize)]
+struct ReportFile<'a>
+{
+    input: &'a str,                 /* file the object was generated or taken from */
+    step: &'static str,             /* what was done to the input: assemble, package, object, library, or generate */
+    commands: Vec<&'a Vec<String>>, /* command lines that assemble the input, if it is a source file */
+    seconds: Option<f64>,           /* time taken to assemble or package the input, if known */
+    cached: bool,                   /* true if an earlier build's object was reused rather than rebuilt */
+    object: &'a str                 /* path of the object in the output directory */
+}
+
+/* Write a report of the files a build processed to the output directory, so that CI can track how long each
+   source file takes to assemble and what each input produced. Every object in the bundle is listed, with its
+   input, the commands run to build it, the time they took, and whether a cached object was used instead
+   => plan = plan that was carried out
+      commands = command lines for the plan, from Build::commands()
+      seconds = time taken to carry out the plan
+      context = build context */
+fn write_report(plan: &Plan, commands: &Vec<ToolCommand>, seconds: f64, context: &Context) -> Result<(), MasonError>
+{
+    let mut files = Vec::new();
+    for obj in context.objects.iter()
+    {
+        let step = match obj.origin.as_str()
+        {
+            origin if plan.sources.iter().any(|s| s == origin) == true => "assemble",
+            origin if plan.include_files.iter().any(|f| f == origin) == true => "package",
+            origin if plan.object_files.iter().any(|f| f == origin) == true => "object",
+            origin if plan.lib_files.iter().any(|f| f == origin) == true => "library",
+            _ => "generate"
+        };
+
+        let time = context.file_times.get(&obj.origin);
+        files.push(ReportFile
+        {
+            input: &obj.origin,
+            step: step,
+            commands: commands.iter().filter(|c| c.input == obj.origin).map(|c| &c.argv).collect(),
+            seconds: time.cloned().flatten(),
+            cached: matches!(time, Some(None)),
+            object: &obj.path
+        });
+    }
+
+    let report = BuildReport
+    {
+        target: context.host.var("TARGET").unwrap_or(""),
+        seconds: seconds,
+        files: files
+    };
+
+    let path = format!("{}/{}", &context.output_dir, REPORT_FILE);
+    if let Err(e) = retry(Step::Write, context, || fs::write(&path, serde_json::to_string_pretty(&report).unwrap()))
+    {
+        return Err(MasonError::host(format!("Cannot write build report {}: {}", &path, e)));
+    }
+    Ok(())
+}
+
+/* an entry in the inventory of the binary files packaged into the bundle */
+#[derive(Serialize)]
+struct BundleEntry<'a>
+{
+    path: &'a str,                 /* binary file, as listed in the config file */
+    symbols: Vec<String>,          /* symbols defined for the contents, starting with the _start symbol */
+    rust_static: String,           /* name of the contents' MasonBlob in mason.rs */
+    size: u64,                     /* size in bytes of the contents packaged */
+    sha256: &'a str,               /* SHA-256 digest of the contents packaged, as hex */
+    load_address: Option<u64>,     /* address the contents expect to be copied to, if given */
+    origin: &'a str                /* where the contents came from, and how they were prepared */
+}
+
+/* Write an inventory of the binary files packaged into the bundle to the output directory, so that teams can
+   keep an up-to-date record of what each release carries. Each is listed with its symbols, size, digest, and
+   origin. Nothing that changes from one build of the same inputs to the next, eg the time, is included, so
+   that the inventory can be committed alongside the release without churning
+   => format = whether to write a Markdown table or JSON
+      context = build context */
+fn write_bundle_doc(format: BundleDoc, context: &Context) -> Result<(), MasonError>
+{
+    let mut entries = Vec::new();
+    for payload in context.payloads.iter()
+    {
+        let names = payload_symbols(&payload.path, &context.symbol_namespace);
+        let mut symbols = vec![format!("{}start", &payload.symbol_prefix), format!("{}end", &payload.symbol_prefix),
+            format!("{}size", &payload.symbol_prefix), format!("{}hash", &payload.symbol_prefix)];
+        if context.load_address_symbols == true && payload.load_address.is_some() == true
+        {
+            symbols.push(format!("{}load_address", &payload.symbol_prefix));
+        }
+
+        entries.push(BundleEntry
+        {
+            path: &payload.path,
+            symbols: symbols,
+            rust_static: names.rust_static,
+            size: payload.size,
+            sha256: &payload.digest,
+            load_address: payload.load_address,
+            origin: &payload.origin
+        });
+    }
+
+    let contents = match format
+    {
+        BundleDoc::Json => serde_json::to_string_pretty(&entries).unwrap(),
+        BundleDoc::Markdown =>
+        {
+            /* pipes would end a table cell early, so escape them in anything taken from the config file */
+            let cell = |text: &str| text.replace('|', "\\|");
+            let mut doc = format!("# Bundle contents for {}\n\n", context.host.var("TARGET").unwrap_or("unknown target"));
+            doc.push_str("<!-- generated by Mason: do not edit -->\n\n");
+            if entries.len() == 0
+            {
+                doc.push_str("No binary files are packaged into this bundle.\n");
+            }
+            else
+            {
+                doc.push_str("| File | Symbols | Rust static | Size (bytes) | SHA-256 | Load address | Origin |\n");
+                doc.push_str("|---|---|---|---:|---|---|---|\n");
+            }
+            for entry in entries.iter()
+            {
+                let symbols: Vec<String> = entry.symbols.iter().map(|s| format!("`{}`", s)).collect();
+                let load_address = match entry.load_address
+                {
+                    Some(address) => format!("0x{:x}", address),
+                    None => String::from("-")
+                };
+                doc.push_str(format!("| {} | {} | `{}` | {} | `{}` | {} | {} |\n", cell(entry.path), symbols.join("<br>"),
+                    &entry.rust_static, entry.size, entry.sha256, load_address, cell(entry.origin)).as_str());
+            }
+            doc
+        }
+    };
+
+    let path = format!("{}/{}", &context.output_dir, format.file());
+    if let Err(e) = retry(Step::Write, context, || fs::write(&path, &contents))
+    {
+        return Err(MasonError::host(format!("Cannot write bundle inventory {}: {}", &path, e)));
+    }
+    Ok(())
+}
+
+/* Restrict the environment an external tool runs in, so that the build doesn't depend on
+   whatever happens to be set on the host. The tool's environment is cleared, except for the
+   allowed variables, its temporary files are kept in the output directory, and its working
+   directory must be within the project or output directory
+   => cmd = command to restrict
+      allowed = names of environment variables to pass through to the tool
+      context = build context
+*/
+fn sandbox_command(cmd: &mut Command, allowed: &Vec<String>, context: &Context) -> Result<(), MasonError>
+{
+    cmd.env_clear();
+    for var in allowed
+    {
+        if let Some(value) = context.host.var(var)
+        {
+            cmd.env(var, value);
+        }
+    }
+
+    cmd.env("TMPDIR", &context.output_dir);
+
+    let project_dir = PathBuf::from(&context.project_dir);
+    let working_dir = match cmd.get_current_dir()
+    {
+        Some(dir) => project_dir.join(dir),
+        None => project_dir.clone()
+    };
+
+    if working_dir.starts_with(&project_dir) == false && working_dir.starts_with(&context.output_dir) == false
+    {
+        return Err(MasonError::config(format!("Sandboxed command {:?} cannot run in {:?}, outside the project and output directories",
+            cmd.get_program(), working_dir)));
+    }
+
+    cmd.current_dir(working_dir);
+    Ok(())
+}
+
+/* a section of a linked ELF image */
+struct ElfSection
+{
+    name: String,
+    kind: u32,
+    flags: u64,
+    addr: u64,
+    offset: u64,
+    size: u64,
+    link: u64,
+    entsize: u64
+}
+
+/* a symbol in an ELF image's symbol table */
+struct ElfSymbol
+{
+    name: String,
+    global: bool, /* true for global and weak symbols */
+    defined: bool /* true if the symbol is defined by this image, rather than referenced */
+}
+
+/* a segment of a linked ELF image, as described by its program headers */
+struct ElfSegment
+{
+    kind: u32,
+    flags: u32,
+    vaddr: u64,
+    memsz: u64
+}
+
+/* the parts of an ELF image that Mason checks */
+struct ElfImage
+{
+    sections: Vec<ElfSection>,
+    segments: Vec<ElfSegment>,
+    symbols: Vec<ElfSymbol>
+}
+
+/* ELF section, segment, and symbol flags and types used when checking images */
+const SHT_SYMTAB: u32 = 2;
+const SHT_NOBITS: u32 = 8;
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const PT_LOAD: u32 = 1;
+const PF_X: u32 = 0x1;
+const PF_W: u32 = 0x2;
+
+/* read fields from an ELF image, checking they fall within its bounds */
+struct ElfReader<'a>
+{
+    contents: &'a [u8],
+    wide: bool,  /* true for 64-bit ELF */
+    little: bool /* true for little-endian ELF */
+}
+
+impl<'a> ElfReader<'a>
+{
+    /* read an unsigned integer of the given number of bytes from the given offset
+       <= returns the value, or None if it's outside the image */
+    fn uint(&self, offset: u64, bytes: usize) -> Option<u64>
+    {
+        let start = offset as usize;
+        let field = self.contents.get(start..start.checked_add(bytes)?)?;
+        let mut value = 0u64;
+        for n in 0..bytes
+        {
+            let byte = match self.little
+            {
+                true => field[bytes - 1 - n],
+                false => field[n]
+            };
+            value = (value << 8) | byte as u64;
+        }
+        Some(value)
+    }
+
+    /* read an address-sized field: 8 bytes on 64-bit ELF, or 4 bytes on 32-bit ELF */
+    fn addr(&self, offset: u64) -> Option<u64>
+    {
+        match self.wide
+        {
+            true => self.uint(offset, 8),
+            false => self.uint(offset, 4)
+        }
+    }
+
+    /* read a NUL-terminated string from the given offset, or None if it's outside the image */
+    fn string(&self, offset: u64) -> Option<String>
+    {
+        let tail = self.contents.get(offset as usize..)?;
+        let end = tail.iter().position(|&b| b == 0)?;
+        Some(String::from_utf8_lossy(&tail[..end]).into_owned())
+    }
+}
+
+/* parse the sections, segments, and symbols of an ELF image
+   => contents = the image's contents
+   <= returns the parsed image, or a description of why it can't be parsed */
+fn parse_elf_image(contents: &[u8]) -> Result<ElfImage, String>
+{
+    if contents.len() < 6 || &contents[0..4] != b"\x7fELF"
+    {
+        return Err(String::from("not an ELF file"));
+    }
+
+    let elf = ElfReader
+    {
+        contents: contents,
+        wide: contents[4] == 2,
+        little: contents[5] == 1
+    };
+
+    let truncated = || String::from("truncated or corrupt ELF file");
+
+    /* locate the program and section header tables from the ELF header */
+    let (phoff, shoff, sizes) = match elf.wide
+    {
+        true => (elf.uint(0x20, 8), elf.uint(0x28, 8), 0x36),
+        false => (elf.uint(0x1c, 4), elf.uint(0x20, 4), 0x2a)
+    };
+    let phoff = phoff.ok_or_else(truncated)?;
+    let shoff = shoff.ok_or_else(truncated)?;
+    let phentsize = elf.uint(sizes, 2).ok_or_else(truncated)?;
+    let phnum = elf.uint(sizes + 2, 2).ok_or_else(truncated)?;
+    let shentsize = elf.uint(sizes + 4, 2).ok_or_else(truncated)?;
+    let shnum = elf.uint(sizes + 6, 2).ok_or_else(truncated)?;
+    let shstrndx = elf.uint(sizes + 8, 2).ok_or_else(truncated)?;
+
+    let mut segments = Vec::new();
+    for n in 0..phnum
+    {
+        let ph = phoff.checked_add(n * phentsize).ok_or_else(truncated)?;
+        let kind = elf.uint(ph, 4).ok_or_else(truncated)? as u32;
+        let (flags, vaddr, memsz) = match elf.wide
+        {
+            true => (elf.uint(ph.wrapping_add(4), 4), elf.uint(ph.wrapping_add(0x10), 8), elf.uint(ph.wrapping_add(0x28), 8)),
+            false => (elf.uint(ph.wrapping_add(0x18), 4), elf.uint(ph.wrapping_add(0x8), 4), elf.uint(ph.wrapping_add(0x14), 4))
+        };
+
+        segments.push(ElfSegment
+        {
+            kind: kind,
+            flags: flags.ok_or_else(truncated)? as u32,
+            vaddr: vaddr.ok_or_else(truncated)?,
+            memsz: memsz.ok_or_else(truncated)?
+        });
+    }
+
+    /* section names are held in the string table section named by the ELF header */
+    let (flags_at, addr_at, offset_at, size_at, link_at, entsize_at) = match elf.wide
+    {
+        true => (0x8, 0x10, 0x18, 0x20, 0x28, 0x38),
+        false => (0x8, 0xc, 0x10, 0x14, 0x18, 0x24)
+    };
+    let strtab = shoff.checked_add(shstrndx * shentsize).ok_or_else(truncated)?;
+    let strtab = elf.addr(strtab.wrapping_add(offset_at)).ok_or_else(truncated)?;
+
+    let mut sections = Vec::new();
+    for n in 0..shnum
+    {
+        let sh = shoff.checked_add(n * shentsize).ok_or_else(truncated)?;
+        let name = elf.uint(sh, 4).ok_or_else(truncated)?;
+
+        sections.push(ElfSection
+        {
+            name: elf.string(strtab.checked_add(name).ok_or_else(truncated)?).ok_or_else(truncated)?,
+            kind: elf.uint(sh.wrapping_add(4), 4).ok_or_else(truncated)? as u32,
+            flags: elf.addr(sh.wrapping_add(flags_at)).ok_or_else(truncated)?,
+            addr: elf.addr(sh.wrapping_add(addr_at)).ok_or_else(truncated)?,
+            offset: elf.addr(sh.wrapping_add(offset_at)).ok_or_else(truncated)?,
+            size: elf.addr(sh.wrapping_add(size_at)).ok_or_else(truncated)?,
+            link: elf.uint(sh.wrapping_add(link_at), 4).ok_or_else(truncated)?,
+            entsize: elf.addr(sh.wrapping_add(entsize_at)).ok_or_else(truncated)?
+        });
+    }
+
+    /* gather the symbols from the symbol table, whose names are held in the string table it links to */
+    let mut symbols = Vec::new();
+    for symtab in sections.iter().filter(|s| s.kind == SHT_SYMTAB && s.entsize > 0)
+    {
+        let names = sections.get(symtab.link as usize).ok_or_else(truncated)?.offset;
+        let (info_at, shndx_at) = match elf.wide
+        {
+            true => (4, 6),
+            false => (12, 14)
+        };
+
+        for n in 0..(symtab.size / symtab.entsize)
+        {
+            let sym = symtab.offset.checked_add(n * symtab.entsize).ok_or_else(truncated)?;
+            let name = elf.uint(sym, 4).ok_or_else(truncated)?;
+            let info = elf.uint(sym.wrapping_add(info_at), 1).ok_or_else(truncated)?;
+            let shndx = elf.uint(sym.wrapping_add(shndx_at), 2).ok_or_else(truncated)?;
+
+            symbols.push(ElfSymbol
+            {
+                name: elf.string(names.checked_add(name).ok_or_else(truncated)?).ok_or_else(truncated)?,
+                global: info >> 4 == 1 || info >> 4 == 2,
+                defined: shndx != 0
+            });
+        }
+    }
+
+    Ok(ElfImage
+    {
+        sections: sections,
+        segments: segments,
+        symbols: symbols
+    })
+}
+
+/* read the target from TARGET and its settings from the config file
+   => host = environment the build runs in
+   <= returns the config file's settings for the target, and the target, or the failure */
+fn configure(host: &HostEnv) -> Result<(Manifest, Target), MasonError>
+{
+    /* determine which CPU and platform we're building for from target triple */
+    let target_string = match host.var("TARGET")
+    {
+        Some(t) => t.to_string(),
+        None => return Err(MasonError::config(String::from("Missing target triple, use --target with cargo")))
+    };
+
+    configure_target(target_string, host)
+}
+
+/* Bail out if any of the environment variables the config file requires are unset or empty, listing every
+   missing variable, with its description if it has one, rather than stopping at the first. cargo is told to
+   rerun the build if any of them change
+   => required = variables listed by the config file's requires_env, if any
+      host = environment the build runs in
+*/
+fn check_required_env(required: &Option<Vec<RequiredEnv>>, host: &HostEnv) -> Result<(), MasonError>
+{
+    let mut missing = Vec::new();
+    let mut descriptions = String::new();
+    for var in required.iter().flatten()
+    {
+        println!("cargo:rerun-if-env-changed={}", var.name());
+        if host.var(var.name()).map_or(true, |value| value.len() == 0) == true
+        {
+            missing.push(var.name());
+            if let RequiredEnv::Described { name, description: Some(description) } = var
+            {
+                descriptions.push_str(format!("\n  {}: {}", name, description).as_str());
+            }
+        }
+    }
+
+    if missing.len() > 0
+    {
+        return Err(MasonError::config(format!("Environment variable(s) required by the config file are unset or empty: {}{}",
+            missing.join(", "), descriptions)));
+    }
+    Ok(())
+}
+
+/* read the given target's settings from the config file
+   => target_string = target triple, or path of a custom target spec file
+      host = environment the build runs in
+   <= returns the config file's settings for the target, and the target, or the failure */
+fn configure_target(target_string: String, host: &HostEnv) -> Result<(Manifest, Target), MasonError>
+{
+    /* a custom target spec file is known to the config file by its name, eg my-os for my-os.json,
+    as it is to cargo, and to the architecture definitions by the triple derived from it */
+    let spec = match target_string.ends_with(".json")
+    {
+        true => Some(load_target_spec(&target_string, host)?),
+        false => None
+    };
+
+    let name = match &spec
+    {
+        Some(spec) => spec.name.clone(),
+        None => target_string.clone()
+    };
+
+    /* get parsed contents of the config file */
+    let manifest = parse_config_file(&name, host)?;
+
+    /* make sure the environment the config file relies on is there before doing anything with it */
+    check_required_env(&manifest.config.requires_env, host)?;
+    let triple = match &spec
+    {
+        Some(spec) if manifest.config.targets.as_ref().map_or(false, |t| t.contains_key(&name)) == false => spec.triple.clone(),
+        _ => name
+    };
+    let mut target = Target::with_host(&triple, &manifest.config.arch_files, &manifest.config.targets, manifest.float_abi, host)?;
+
+    if let Some(spec) = spec
+    {
+        if let Some(width) = spec.ptr_width
+        {
+            target.ptr_width = width;
+        }
+        target.spec_file = Some(target_string);
+    }
+
+    /* use the binutils the config file names, eg riscv64-unknown-elf-as, rather than probing for them */
+    if let Some(prefix) = &manifest.gnu_prefix
+    {
+        target.gnu_prefix = prefix.clone();
+    }
+
+    /* apply any unusual widths the config file asks for */
+    if let Some(width) = manifest.ptr_width
+    {
+        target.ptr_width = width;
+    }
+    if let Some(width) = manifest.fp_width
+    {
+        target.fp_width = width;
+    }
+
+    Ok((manifest, target))
+}
+
+/* describe what Mason needs from a custom target spec file */
+struct TargetSpec
+{
+    name: String,             /* name cargo knows the target by, ie the file's leafname without .json */
+    triple: String,           /* triple to find the target's architecture definition with */
+    ptr_width: Option<usize>  /* pointer width in bits, if the spec gives it */
+}
+
+/* read a custom target spec file, as cargo accepts for --target, and derive a triple from it
+   that the architecture definitions cover. this is its llvm-target, adjusted to match the spec's
+   endianness and, for RISC-V, with the ISA spelled out from its features, eg riscv64gc
+   => path = path of the spec file, ending with .json
+      host = environment the build runs in
+   <= returns what Mason needs from the spec */
+fn load_target_spec(path: &String, host: &HostEnv) -> Result<TargetSpec, MasonError>
+{
+    let contents = match fs::read_to_string(host.path(path))
+    {
+        Ok(c) => c,
+        Err(e) => return Err(MasonError::config(format!("Can't read target spec file {}: {}", path, e)))
+    };
+
+    let spec: serde_json::Value = match serde_json::from_str(&contents)
+    {
+        Ok(s) => s,
+        Err(e) => return Err(MasonError::config(format!("Can't parse target spec file {}: {}", path, e)))
+    };
+
+    let llvm_target = match spec.get("llvm-target").and_then(|t| t.as_str())
+    {
+        Some(t) => t,
+        None => return Err(MasonError::config(format!("Target spec file {} has no llvm-target", path)))
+    };
+
+    let mut parts: Vec<String> = llvm_target.split('-').map(|p| p.to_string()).collect();
+    let features: Vec<&str> = spec.get("features").and_then(|f| f.as_str()).unwrap_or("").split(',')
+        .filter_map(|f| f.strip_prefix('+'))
+        .collect();
+    parts[0] = triple_arch(&parts[0], spec.get("target-endian").and_then(|e| e.as_str()), &features);
+
+    /* the pointer width is a string in older specs and an integer in newer ones */
+    let ptr_width = match spec.get("target-pointer-width")
+    {
+        Some(serde_json::Value::String(w)) => w.parse().ok(),
+        Some(serde_json::Value::Number(w)) => w.as_u64().map(|w| w as usize),
+        _ => None
+    };
+
+    if let Some(width) = ptr_width
+    {
+        if [16, 32, 64].contains(&width) == false
+        {
+            return Err(MasonError::config(format!("Invalid target-pointer-width {} in target spec file {}", width, path)));
+        }
+    }
+
+    Ok(TargetSpec
+    {
+        name: Path::new(path).file_stem().unwrap().to_string_lossy().to_string(),
+        triple: parts.join("-"),
+        ptr_width: ptr_width
+    })
+}
+
+/* give the first part of the triples the architecture definitions cover for an architecture described
+   by name, endianness, and features, as in target specs and cargo's CARGO_CFG_TARGET_* variables
+   => arch = architecture name, eg riscv64 or mips
+      endian = target's endianness, little or big, if known
+      features = names of the target features enabled, eg m, a, and c
+   <= returns the first part of the triple, eg riscv64imac or mipsel */
+fn triple_arch(arch: &str, endian: Option<&str>, features: &[&str]) -> String
+{
+    match (arch, endian)
+    {
+        ("mips", Some("little")) => String::from("mipsel"),
+        ("mipsel", Some("big")) => String::from("mips"),
+        ("powerpc64", Some("little")) => String::from("powerpc64le"),
+        ("powerpc64le", Some("big")) => String::from("powerpc64"),
+
+        /* RISC-V triples name the ISA, eg riscv64gc, whereas the architecture just names the base,
+        and the extensions are listed in the features, eg m, a, f, d, and c. g stands for imafd */
+        ("riscv32", _) | ("riscv64", _) =>
+        {
+            let mut isa: String = "imafdc".chars().filter(|c| *c == 'i' || features.contains(&c.to_string().as_str()) == true).collect();
+            if isa.starts_with("imafd") == true
+            {
+                isa = isa.replacen("imafd", "g", 1);
+            }
+            format!("{}{}", arch, isa)
+        },
+        (arch, _) => arch.to_string()
+    }
+}
+
+/* check that every assembly source file the config file describes for TARGET assembles, without writing
+   anything to OUT_DIR, for lint jobs and pre-commit hooks. this is run as: <build script> check-asm
+   => args = command line arguments following check-asm
+*/
+fn check_asm(args: &[String]) -> Result<(), MasonError>
+{
+    if args.len() != 0
+    {
+        return Err(MasonError::config(String::from("Usage: check-asm")));
+    }
+
+    let host = HostEnv::from_process()?;
+    let (manifest, target) = configure(&host)?;
+
+    /* keep intermediate files, such as preprocessed source, out of OUT_DIR */
+    let scratch = env::temp_dir().join(format!("mason-check-{}", std::process::id()));
+    if let Err(e) = fs::create_dir_all(&scratch)
+    {
+        return Err(MasonError::host(format!("Can't create scratch directory {}: {}", scratch.display(), e)));
+    }
+
+    let check = || -> Result<(Vec<String>, Vec<String>), MasonError>
+    {
+        let mut build = Build::with_host(manifest, &target, scratch.to_string_lossy().to_string(), host.clone())?;
+        let plan = build.resolve()?;
+        build.check(&plan)
+    };
+
+    /* remove the scratch directory whether or not the check got as far as assembling anything */
+    let checked = check();
+    let _ = fs::remove_dir_all(&scratch);
+    let (sources, failures) = checked?;
+
+    for failure in failures.iter()
+    {
+        log_event(&host, LogLevel::Error, "check_asm", failure.clone());
+    }
+
+    if failures.len() > 0
+    {
+        return Err(MasonError::check_failure(format!("{} of {} assembly source file(s) failed to assemble", failures.len(), sources.len())));
+    }
+
+    log_event(&host, LogLevel::Info, "check_asm", format!("{} assembly source file(s) assembled cleanly", sources.len()));
+    Ok(())
 }
 
-impl Target
+/* show how the config file's settings differ between two targets, once resolved for each, to help keep
+   multi-architecture config files consistent. settings only the first target has are shown in red and
+   prefixed with -, and those only the second has in green, prefixed with +. this is run as:
+   <build script> diff-config <target A> <target B>
+   => args = command line arguments following diff-config
+*/
+fn diff_config(args: &[String]) -> Result<(), MasonError>
 {
-    /* create a target object from a full build triple string, taking the CPU arch from the first part of the triple  */
-    pub fn new(triple: &String) -> Target
+    if args.len() != 2
+    {
+        return Err(MasonError::config(String::from("Usage: diff-config <target A> <target B>")));
+    }
+
+    let host = HostEnv::from_process()?;
+    let a = resolved_settings(&args[0], &host)?;
+    let b = resolved_settings(&args[1], &host)?;
+
+    /* only color the output for people, not for scripts or those who've asked for none */
+    let color = std::io::stdout().is_terminal() == true && env::var_os("NO_COLOR").is_none() == true;
+    let paint = |code: &str, text: String| if color == true { format!("\x1b[{}m{}\x1b[0m", code, text) } else { text };
+
+    println!("{}", paint("31", format!("--- {}", &args[0])));
+    println!("{}", paint("32", format!("+++ {}", &args[1])));
+
+    let mut differences = 0;
+    for ((name, a_values), (_, b_values)) in a.iter().zip(b.iter())
     {
-        match triple.split('-').next().expect("Badly formatted target triple").as_ref()
+        if a_values == b_values
         {
-            "riscv64imac" => Target
-            {
-                cpu_arch: String::from("rv64imac"),
-                gnu_prefix: String::from("riscv64"),
-                platform: String::from("riscv"),
-                ptr_width: 64,
-                fp_width: 0,
-                abi: String::from("lp64")
-            },
-            "riscv64gc" => Target
-            {
-                cpu_arch: String::from("rv64gc"),
-                gnu_prefix: String::from("riscv64"),
-                platform: String::from("riscv"),
-                ptr_width: 64,
-                fp_width: 64,
-                abi: String::from("lp64d")
-            },
-            unknown_target => panic!("Unsupported target '{}'", &unknown_target)
+            continue;
+        }
+
+        differences = differences + 1;
+        println!("{}", paint("1", format!("{}:", name)));
+        for value in a_values.iter().filter(|v| b_values.contains(v) == false)
+        {
+            println!("{}", paint("31", format!("- {}", value)));
+        }
+        for value in b_values.iter().filter(|v| a_values.contains(v) == false)
+        {
+            println!("{}", paint("32", format!("+ {}", value)));
         }
     }
+
+    log_event(&host, LogLevel::Info, "diff_config", format!("{} setting(s) differ between {} and {}", differences, &args[0], &args[1]));
+    Ok(())
 }
 
-/* shared context of this build run */
-pub struct Context<'a>
+/* resolve the config file's settings for a target, in a form that can be compared with another target's
+   => target_string = target triple, or path of a custom target spec file
+      host = environment the build runs in
+   <= returns the name of each setting, and its values, in the same order for every target, or the failure */
+fn resolved_settings(target_string: &String, host: &HostEnv) -> Result<Vec<(&'static str, Vec<String>)>, MasonError>
 {
-    /* defined by the host environment */
-    output_dir: String,       /* where we're outputting object code on the host */
-    objects: HashSet<String>, /* set of objects to link, referenced by their full path */
-    as_exec: String,          /* path to target's GNU assembler executable */
-    ar_exec: String,          /* path to target's GNU archiver executable */
-    ld_exec: String,          /* path to target's GNU linker executable */
-    oc_exec: String,          /* path to the target's GNU objcopy executable */
-    target: &'a Target        /* describe the build target */
+    let (manifest, target) = configure_target(target_string.clone(), host)?;
+    let inputs = &manifest.inputs;
+    let sorted = |mut values: Vec<String>|
+    {
+        values.sort();
+        values
+    };
+
+    Ok(vec![
+        ("include_files", sorted(inputs.include_files.iter().cloned().collect())),
+        ("asm_dirs", sorted(inputs.asm_dirs.keys().cloned().collect())),
+        ("object_files", sorted(inputs.object_files.iter().cloned().collect())),
+        ("lib_files", sorted(inputs.lib_files.iter().cloned().collect())),
+        ("stages", inputs.stages.clone()),
+        ("cpu_arch", vec![target.cpu_arch.clone()]),
+        ("abi", vec![target.abi.clone()]),
+        ("gnu_prefix", vec![target.gnu_prefix.clone()]),
+        ("ptr_width", vec![target.ptr_width.to_string()]),
+        ("fp_width", vec![target.fp_width.to_string()]),
+        ("object_format", vec![manifest.object_format.clone().unwrap_or(target.bfd_format.clone())]),
+        ("as_flags", target.as_flags.clone()),
+        ("llvm_target", vec![target.llvm_target.clone()]),
+        ("clang_flags", target.clang_flags.clone()),
+        ("ld_flags", target.ld_flags.clone()),
+        ("ar_flags", manifest.tool_flags.ar.clone()),
+        ("objcopy_flags", manifest.tool_flags.objcopy.clone()),
+        ("libgcc", match &manifest.libgcc
+        {
+            Some(Libgcc::All(all)) => vec![all.to_string()],
+            Some(Libgcc::Members(members)) => members.clone(),
+            None => Vec::new()
+        }),
+        ("libgcc_flags", manifest.libgcc_flags.clone())
+    ])
 }
 
-fn main()
+/* check that the payload sections in a linked image are read-only data: neither the sections
+   nor the segments loading them may be writable or executable. this is run after the crate
+   is linked, as: <build script> check-rodata <image>
+   => args = command line arguments following check-rodata
+*/
+fn check_rodata(args: &[String]) -> Result<(), MasonError>
 {
-    /* determine which CPU and platform we're building for from target triple */
-    let target_string = env::var("TARGET").expect("Missing target triple, use --target with cargo");
-    let target = Target::new(&target_string);
+    let path = match args.first()
+    {
+        Some(p) => p,
+        None => return Err(MasonError::config(String::from("Usage: check-rodata <linked ELF image>")))
+    };
 
-    /* create a shared context describing this build */
-    let mut context = Context
+    let contents = match fs::read(path)
     {
-        output_dir: env::var("OUT_DIR").expect("No output directory specified"),
-        objects: HashSet::new(),
-        as_exec: String::from(format!("{}-linux-gnu-as", target.gnu_prefix)),
-        ar_exec: String::from(format!("{}-linux-gnu-ar", target.gnu_prefix)),
-        ld_exec: String::from(format!("{}-linux-gnu-ld", target.gnu_prefix)),
-        oc_exec: String::from(format!("{}-linux-gnu-objcopy", target.gnu_prefix)),
-        target: &target
+        Ok(c) => c,
+        Err(e) => return Err(MasonError::config(format!("Can't read linked image {}: {}", path, e)))
     };
 
-    /* get parsed contents of the config file, or bail out if this cannot be obtained */
-    let config = parse_config_file();
+    let image = match parse_elf_image(&contents)
+    {
+        Ok(i) => i,
+        Err(e) => return Err(MasonError::config(format!("Can't parse linked image {}: {}", path, e)))
+    };
 
-    /* populate tables with paths of files to include and assemble from the config file */
-    let mut include_files = HashSet::new();
-    let mut asm_dirs = HashSet::new();
+    /* payloads are placed in .mason and .mason.<group> by the linker script fragment, or
+    left in their .rodata.mason.<leafname> sections if it wasn't used */
+    let payload_sections: Vec<&ElfSection> = image.sections.iter()
+        .filter(|s| s.name == ".mason" || s.name.starts_with(".mason.") == true || s.name.starts_with(".rodata.mason.") == true)
+        .collect();
 
-    /* include the defaults */
-    if let Some(defaults) = config.defaults
+    if payload_sections.len() == 0
     {
-        add_file_paths_from_config(&defaults, &mut include_files, &mut asm_dirs);
+        return Err(MasonError::check_failure(format!("Linked image {} has no Mason payload sections: is mason.ld included in its linker script?", path)));
     }
 
-    /* select architecture's settings from the given target */
-    if let Some(targets) = config.target
+    let mut problems = Vec::new();
+    for section in payload_sections.iter().filter(|s| s.size > 0)
     {
-        match targets.get(&target_string)
+        if section.flags & (SHF_WRITE | SHF_EXECINSTR) != 0
         {
-            Some(arch) => add_file_paths_from_config(&arch, &mut include_files, &mut asm_dirs),
-            None => ()
+            problems.push(format!("section {} is writable or executable", &section.name));
         }
-    }
 
-    /* package up individual binary files */
-    for f in include_files
-    {
-        package_binary(&String::from(f), &mut context);
+        match image.segments.iter().find(|seg| seg.kind == PT_LOAD && section.addr >= seg.vaddr && section.addr - seg.vaddr < seg.memsz)
+        {
+            Some(seg) => if seg.flags & (PF_W | PF_X) != 0
+            {
+                problems.push(format!("section {} at 0x{:x} is loaded by a{}{} segment at 0x{:x}", &section.name, section.addr,
+                    if seg.flags & PF_W != 0 { " writable" } else { "" },
+                    if seg.flags & PF_X != 0 { " executable" } else { "" }, seg.vaddr));
+            },
+            None => problems.push(format!("section {} at 0x{:x} isn't loaded by any segment", &section.name, section.addr))
+        }
     }
 
-    /* assemble all asm code in each of these directories */
-    for dir in asm_dirs
+    if problems.len() > 0
     {
-        assemble_directory(String::from(dir), &mut context);
+        return Err(MasonError::check_failure(format!("Payloads in linked image {} aren't read-only data:\n{}", path, problems.join("\n"))));
     }
 
-    /* package up all the generated object files into an archive and link against it */
-    link_archive(&mut context);
+    let host = HostEnv::from_process()?;
+    log_event(&host, LogLevel::Info, "check_rodata", format!("{} payload section(s) in {} are read-only data", payload_sections.len(), path));
+    Ok(())
 }
 
-/* Turn a binary file into a linkable .o object file.
-   the following symbols will be defined pointing to the start and end
-   of the object when it is located in memory, and its size in bytes:
+/* sizes of the parts of a linked image, as written to and read from a baseline file */
+#[derive(Serialize, Deserialize, Default)]
+struct SizeReport
+{
+    payloads: BTreeMap<String, u64>, /* bytes of each packaged binary, by its path */
+    bundle: BTreeMap<String, u64>,   /* bytes of each other object from the bundle linked in, by its origin */
+    rust: u64,                       /* bytes of everything else in the image, mostly Rust code and data */
+    total: u64                       /* bytes of the image's allocated sections */
+}
 
-    _binary_leafname_start
-    _binary_leafname_end
-    _binary_leafname_size
-   
-   where leafname is the leafname of the binary file
+/* count the bytes an ELF image's allocated sections occupy in the image, not counting zero-filled ones
+   => image = parsed ELF image
+      filter = function returning true for sections to count
+   <= returns the number of bytes */
+fn allocated_bytes(image: &ElfImage, filter: fn(&ElfSection) -> bool) -> u64
+{
+    image.sections.iter()
+        .filter(|s| s.flags & SHF_ALLOC != 0 && s.kind != SHT_NOBITS && filter(s) == true)
+        .map(|s| s.size)
+        .sum()
+}
 
-   => binary_path = path to binary file to convert
-      context    = build context
+/* describe the change in a size from a baseline
+   => now = current size
+      then = baseline size, or None if there's no baseline for it
+   <= returns a description of the change, or an empty string if there's no baseline */
+fn size_change(now: u64, then: Option<u64>) -> String
+{
+    match then
+    {
+        Some(then) if now == then => String::from(" (unchanged)"),
+        Some(then) => format!(" ({:+} bytes)", now as i64 - then as i64),
+        None => String::new()
+    }
+}
+
+/* report how much of a linked image is taken up by payloads, the rest of the Mason bundle, and
+   everything else, mostly Rust code, using the object list Mason wrote to the build's output directory.
+   the report is written to the output directory as mason-size.json, and if a baseline, ie an earlier
+   mason-size.json, is given, each size is compared with it. this is run after the crate is linked,
+   as: <build script> size-report <image> [<output directory>] [<baseline>]. If the output directory
+   is left out, it's found with discover_out_dir()
+   => args = command line arguments following size-report
 */
-fn package_binary(binary_path: &String, mut context: &mut Context)
+fn size_report(args: &[String]) -> Result<(), MasonError>
 {
-    /* generate path to output .o object file for this given binary */
-    let leafname = String::from(Path::new(binary_path).file_name().unwrap().to_str().unwrap());
-    let object_file = format!("{}/{}.o", &context.output_dir, &leafname);
-
-    /* generate an intemediate .o object file from the given binary file */
-    let result = Command::new(&context.ld_exec)
-        .arg("-r")
-        .arg("--format=binary")
-        .arg(&binary_path)
-        .arg("-o")
-        .arg(&object_file)
-        .output()
-        .expect(format!("Couldn't run command to convert {} into linkable object file", &binary_path).as_str());
+    if args.len() < 1 || args.len() > 3
+    {
+        return Err(MasonError::config(String::from("Usage: size-report <linked ELF image> [<output directory>] [<baseline>]")));
+    }
 
-    if result.status.success() != true
+    let path = &args[0];
+    let (output_dir, baseline) = match (args.get(1), args.get(2))
+    {
+        (Some(dir), baseline) if Path::new(dir).is_dir() == true => (dir.clone(), baseline),
+        (Some(dir), Some(_)) => return Err(MasonError::config(format!("Output directory {} isn't a directory", dir))),
+        (baseline, _) => (discover_out_dir(path)?, baseline)
+    };
+
+    let image = match fs::read(path)
+    {
+        Ok(contents) => match parse_elf_image(&contents)
+        {
+            Ok(i) => i,
+            Err(e) => return Err(MasonError::config(format!("Can't parse linked image {}: {}", path, e)))
+        },
+        Err(e) => return Err(MasonError::config(format!("Can't read linked image {}: {}", path, e)))
+    };
+
+    let baseline: Option<SizeReport> = match baseline
+    {
+        Some(baseline) => match fs::read_to_string(baseline)
+        {
+            Ok(contents) => match serde_json::from_str(&contents)
+            {
+                Ok(b) => Some(b),
+                Err(e) => return Err(MasonError::config(format!("Can't parse size report baseline {}: {}", baseline, e)))
+            },
+            Err(e) => return Err(MasonError::config(format!("Can't read size report baseline {}: {}", baseline, e)))
+        },
+        None => None
+    };
+
+    let list_path = format!("{}/{}", output_dir, OBJECT_LIST_FILE);
+    let list = match fs::read_to_string(&list_path)
+    {
+        Ok(l) => l,
+        Err(e) => return Err(MasonError::config(format!("Can't read object list {}: {}", &list_path, e)))
+    };
+
+    /* an object from the bundle is counted only if the image defines one of its global symbols, as
+    the linker won't have pulled it from the archive otherwise. a payload's object holds nothing but its
+    payload section, so it's counted as a payload */
+    let linked: HashSet<&String> = image.symbols.iter().filter(|s| s.global == true && s.defined == true).map(|s| &s.name).collect();
+    let mut report = SizeReport::default();
+
+    for line in list.lines()
     {
-        panic!("Conversion of {} to object {} failed:\n{}\n{}",
-            &binary_path, &object_file, String::from_utf8(result.stdout).unwrap(), String::from_utf8(result.stderr).unwrap());
+        let (object, origin) = match line.split_once('\t')
+        {
+            Some(entry) => entry,
+            None => continue
+        };
+
+        let contents = match fs::read(object)
+        {
+            Ok(c) => c,
+            Err(e) => return Err(MasonError::config(format!("Can't read bundle object {}: {}", object, e)))
+        };
+
+        let parsed = match parse_elf_image(&contents)
+        {
+            Ok(p) => p,
+            Err(e) => return Err(MasonError::config(format!("Can't parse bundle object {}: {}", object, e)))
+        };
+
+        if parsed.symbols.iter().any(|s| s.global == true && s.defined == true && linked.contains(&s.name) == true) == false
+        {
+            continue;
+        }
+
+        let payload = allocated_bytes(&parsed, |s| s.name.starts_with(".rodata.mason.") == true);
+        let other = allocated_bytes(&parsed, |s| s.name.starts_with(".rodata.mason.") == false);
+        match payload > 0 && other == 0
+        {
+            true => *report.payloads.entry(String::from(origin)).or_insert(0) += payload,
+            false => *report.bundle.entry(String::from(origin)).or_insert(0) += payload + other
+        }
     }
 
-    /* when we use ld, it defines the _start, _end, _size symbols using the full filename
-    of the binary file, which pollutes the symbol with the architecture and project layout.
+    report.total = allocated_bytes(&image, |_| true);
+    let mason: u64 = report.payloads.values().chain(report.bundle.values()).sum();
+    report.rust = report.total.saturating_sub(mason);
 
-    rename the symbols so they can be accessed generically just by their component name.
-    we need to convert the '/' and '.' in the path to _ FIXME: this very Unix/Linux-y */
-    let symbol_prefix = format!("_binary_{}_", &binary_path.replace("/", "_").replace(".", "_"));
-    let renamed_prefix = format!("_binary_{}_", &leafname.replace(".", "_"));
+    /* describe the report, comparing it with the baseline, if any */
+    let then = |table: fn(&SizeReport) -> &BTreeMap<String, u64>, name: &String| match &baseline
+    {
+        Some(b) => Some(table(b).get(name).cloned().unwrap_or(0)),
+        None => None
+    };
 
-    /* select correct executable */
-    let rename = Command::new(&context.oc_exec)
-        .arg("--redefine-sym")
-        .arg(format!("{}start={}start", &symbol_prefix, &renamed_prefix))
-        .arg("--redefine-sym")
-        .arg(format!("{}end={}end", &symbol_prefix, &renamed_prefix))
-        .arg("--redefine-sym")
-        .arg(format!("{}size={}size", &symbol_prefix, &renamed_prefix))
-        .arg(&object_file)
-        .output()
-        .expect(format!("Couldn't run command to rename symbols for {}", &binary_path).as_str());
+    let mut summary = vec![format!("size report for {}:", path)];
+    summary.push(format!("    payloads: {} bytes{}", report.payloads.values().sum::<u64>(),
+        size_change(report.payloads.values().sum(), baseline.as_ref().map(|b| b.payloads.values().sum()))));
+    for (name, size) in report.payloads.iter()
+    {
+        summary.push(format!("        {}: {} bytes{}", name, size, size_change(*size, then(|r| &r.payloads, name))));
+    }
+    summary.push(format!("    bundle: {} bytes{}", report.bundle.values().sum::<u64>(),
+        size_change(report.bundle.values().sum(), baseline.as_ref().map(|b| b.bundle.values().sum()))));
+    for (name, size) in report.bundle.iter()
+    {
+        summary.push(format!("        {}: {} bytes{}", name, size, size_change(*size, then(|r| &r.bundle, name))));
+    }
+    summary.push(format!("    rust and other code and data: {} bytes{}", report.rust, size_change(report.rust, baseline.as_ref().map(|b| b.rust))));
+    summary.push(format!("    total: {} bytes{}", report.total, size_change(report.total, baseline.as_ref().map(|b| b.total))));
 
-    if rename.status.success() != true
+    if let Some(baseline) = &baseline
     {
-        panic!("Symbol rename for {} in {} failed:\n{}\n{}",
-            &binary_path, &object_file, String::from_utf8(result.stdout).unwrap(), String::from_utf8(result.stderr).unwrap());
+        for name in baseline.payloads.keys().filter(|n| report.payloads.contains_key(*n) == false)
+        {
+            summary.push(format!("    payload {} is no longer in the image", name));
+        }
+        for name in baseline.bundle.keys().filter(|n| report.bundle.contains_key(*n) == false)
+        {
+            summary.push(format!("    bundle object from {} is no longer in the image", name));
+        }
     }
 
-    println!("cargo:rerun-if-changed={}", &binary_path);
-    register_object(&object_file, &mut context);
+    let host = HostEnv::from_process()?;
+    log_event(&host, LogLevel::Info, "size_report", summary.join("\n"));
+
+    let report_path = format!("{}/{}", output_dir, SIZE_REPORT_FILE);
+    if let Err(e) = fs::write(&report_path, serde_json::to_string_pretty(&report).unwrap())
+    {
+        return Err(MasonError::host(format!("Can't write size report {}: {}", &report_path, e)));
+    }
+    Ok(())
 }
 
-/* Add an object file, by its full path, to the list of objects to link with.
-   To avoid object collisions and overwrites, bail out if the given object path was already taken */
-fn register_object(path: &String, context: &mut Context)
+/* print the OUT_DIR Mason built the bundle linked into an image in, as found by discover_out_dir(), for
+   scripts run after the crate is linked. this is run as: <build script> out-dir <image>
+   => args = command line arguments following out-dir
+*/
+fn out_dir(args: &[String]) -> Result<(), MasonError>
 {
-    if context.objects.insert(path.to_string()) == false
+    match args
     {
-        panic!("Cannot register object {} - an object already exists in that location", &path);
+        [image] => println!("{}", discover_out_dir(image)?),
+        _ => return Err(MasonError::config(String::from("Usage: out-dir <linked ELF image>")))
     }
+    Ok(())
 }
 
-/* Run through a directory of .s assembly source code,
-   add each .s file to the project, and assemble each file using the appropriate tools
-   => slurp_from = path of directory to scan for .s files to assemble
-      context = build context
-*/
-fn assemble_directory(slurp_from: String, context: &mut Context)
+/* Find the OUT_DIR cargo gave Mason when building the crate a linked image came from, for steps run after
+   the crate is linked, outside cargo. cargo names each build script's output directory
+   <target dir>/[<triple>/]<profile>/build/<package>-<hash>/out, and the hash covers the compiler and the
+   package's settings, so it can't be worked out without cargo. Instead, cargo metadata, run in the working
+   directory, gives the target directory and the workspace's packages with build scripts, narrowed to the
+   one the working directory is in, if any. The build directory of the profile the image was linked in is searched
+   for those packages' output directories holding Mason's object list, or if the image isn't in a profile
+   directory, eg it's been copied elsewhere, those of every profile in the target directory. If more than one
+   output directory is found, the one whose object list was written last is used, as the most recent build
+   => image = path of linked image
+   <= returns the output directory */
+fn discover_out_dir(image: &str) -> Result<String, MasonError>
 {
-    /* no longer accept missing directories, though don't fail empty directories */
-    let directory = match fs::read_dir(&slurp_from)
+    let host = HostEnv::from_process()?;
+    let workspace = match host.workspace()
     {
-        Ok(d) => d,
-        Err(e) => panic!("Cannot assembly directory {}: {}", &slurp_from, e)
+        Some(w) => w,
+        None => return Err(MasonError::config(format!("cargo metadata can't describe the workspace in {}, so give the output directory after the image",
+            &host.current_dir)))
     };
+    let target_dir = &workspace.target_dir;
 
-    for file in directory
+    /* packages with build scripts, narrowed to the one the working directory is in, if any */
+    let packages: Vec<&WorkspaceMember> = workspace.members.iter().filter(|m| m.build_script == true).collect();
+    let working_dir = PathBuf::from(&host.current_dir);
+    let names: Vec<&String> = match packages.iter().filter(|m| working_dir.starts_with(&m.dir) == true).max_by_key(|m| m.dir.components().count())
     {
-        if let Ok(file) = file
+        Some(member) => vec![&member.name],
+        None => packages.iter().map(|m| &m.name).collect()
+    };
+
+    /* the image is linked in its profile's directory, or a subdirectory of it, eg examples */
+    let image_path = fs::canonicalize(image).unwrap_or(PathBuf::from(image));
+    let mut build_dirs: Vec<PathBuf> = image_path.ancestors().skip(1).take(3)
+        .map(|dir| dir.join("build"))
+        .filter(|dir| dir.is_dir() == true)
+        .take(1)
+        .collect();
+
+    if build_dirs.len() == 0
+    {
+        /* profiles are in the target directory, or in a directory per target triple within it */
+        let subdirs = |dir: &Path| -> Vec<PathBuf>
         {
-            /* assume everything in the asm directory can be assembled if it is a file */
-            if let Ok(metadata) = file.metadata()
+            match fs::read_dir(dir)
             {
-                if metadata.is_file() == true
-                {
-                    assemble(file.path().to_str().unwrap(), context);
-                }
+                Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_dir() == true).collect(),
+                Err(_) => Vec::new()
             }
+        };
+
+        for dir in subdirs(target_dir)
+        {
+            build_dirs.push(dir.join("build"));
+            build_dirs.extend(subdirs(&dir).iter().map(|d| d.join("build")));
         }
     }
-}
 
-/* Attempt to assemble a given .s source file into a .o object file
-   => path = path to .s file to assemble. non-.s files are silently ignored
-      context = build context
-*/
-fn assemble(path: &str, mut context: &mut Context)
-{
-    /* create name from .s source file's path - extract just the leafname and drop the
-    file extension. so extract 'start' from 'src/platform-blah/asm/start.s' */
-    let re = Regex::new(r"(([A-Za-z0-9_]+)(/))+(?P<leaf>[A-Za-z0-9_]+)(\.s)").unwrap();
-    let matches = re.captures(&path);
-    if matches.is_none() == true
+    /* output directories are named after their package with a hash of 16 hex digits appended */
+    let mut found = Vec::new();
+    for build_dir in build_dirs.iter()
     {
-        return; /* skip non-conformant files */
-    }
+        let entries = match fs::read_dir(build_dir)
+        {
+            Ok(entries) => entries,
+            Err(_) => continue
+        };
 
-    /* extract leafname (sans .s extension) from the path */
-    let leafname = &(matches.unwrap())["leaf"];
+        for entry in entries.filter_map(|e| e.ok())
+        {
+            let leafname = entry.file_name().to_string_lossy().to_string();
+            let ours = names.iter().any(|name| leafname.strip_prefix(name.as_str()).and_then(|rest| rest.strip_prefix('-'))
+                .map(|hash| hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()) == true) == Some(true));
 
-    /* build pathname for the target .o file */
-    let object_file = format!("{}/{}.o", &context.output_dir, &leafname);
-
-    /* now let's try to assemble the .s into an intermediate .o */
-    let result = Command::new(&context.as_exec)
-        .arg("-march")
-        .arg(&context.target.cpu_arch)
-        .arg("-mabi")
-        .arg(&context.target.abi)
-        .arg("--defsym")
-        .arg(format!("ptrwidth={}", &context.target.ptr_width))
-        .arg("--defsym")
-        .arg(format!("fpwidth={}", &context.target.fp_width))
-        .arg("-o")
-        .arg(&object_file)
-        .arg(path)
-        .output()
-        .expect(format!("Failed to execute command to assemble {}", path).as_str());
+            let out = entry.path().join("out");
+            if ours == true
+            {
+                if let Ok(written) = fs::metadata(out.join(OBJECT_LIST_FILE)).and_then(|m| m.modified())
+                {
+                    found.push((written, out));
+                }
+            }
+        }
+    }
 
-    if result.status.success() != true
+    found.sort();
+    match found.pop()
     {
-        panic!("Assembling {} failed:\n{}\n{}",
-            &path, String::from_utf8(result.stdout).unwrap(), String::from_utf8(result.stderr).unwrap());
+        Some((_, dir)) =>
+        {
+            log_event(&host, LogLevel::Step, "out_dir", format!("using output directory {}", dir.display()));
+            Ok(dir.to_string_lossy().to_string())
+        },
+        None => Err(MasonError::config(format!("Can't find the output directory of a Mason build for {} under {}: give it after the image",
+            image, target_dir.display())))
     }
+}
 
-    println!("cargo:rerun-if-changed={}", &path);
-    register_object(&object_file, &mut context);
+/* why a build or command failed, by class of failure, each distinguished by its exit code, with what's
+   known about where it happened. Failures are passed back up to main() to be reported once */
+#[derive(Clone, Debug)]
+pub enum MasonError
+{
+    Host(ErrorContext),        /* host environment unusable */
+    Config(ErrorContext),      /* config file or build environment invalid */
+    MissingTool(ErrorContext), /* required tool not found */
+    ToolFailure(ErrorContext), /* tool failed or couldn't be run */
+    CheckFailure(ErrorContext) /* check, such as check-rodata or check-asm, found a problem */
 }
 
-/* Create an archive containing all registered .o files and link with this archive */
-fn link_archive(context: &mut Context)
+/* what's known about a failure */
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ErrorContext
 {
-    let archive_name = String::from("mason-bundle");
-    let archive_path = format!("{}/lib{}.a", &context.output_dir, &archive_name);
+    pub message: String,              /* description of the failure */
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stage: Option<String>,        /* step of the build that failed, eg assemble */
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,         /* file being processed, if any */
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<Vec<String>>, /* command line of the tool that failed, if any */
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<String>        /* what the tool printed to stderr, if it's not already in the message */
+}
 
-    /* create archive from .o files in the output directory */
-    let mut cmd = Command::new(&context.ar_exec);
-    cmd.arg("crus").arg(&archive_path);
+impl MasonError
+{
+    pub fn host(message: String) -> MasonError { MasonError::Host(ErrorContext { message: message, ..Default::default() }) }
+    pub fn config(message: String) -> MasonError { MasonError::Config(ErrorContext { message: message, ..Default::default() }) }
+    pub fn missing_tool(message: String) -> MasonError { MasonError::MissingTool(ErrorContext { message: message, ..Default::default() }) }
+    pub fn tool_failure(message: String) -> MasonError { MasonError::ToolFailure(ErrorContext { message: message, ..Default::default() }) }
+    pub fn check_failure(message: String) -> MasonError { MasonError::CheckFailure(ErrorContext { message: message, ..Default::default() }) }
 
-    /* add list of object files generated */
-    for obj in context.objects.iter()
+    pub fn exit_code(&self) -> i32
     {
-        cmd.arg(obj);
+        match self
+        {
+            MasonError::Host(_) => 1,
+            MasonError::Config(_) => 2,
+            MasonError::MissingTool(_) => 3,
+            MasonError::ToolFailure(_) => 4,
+            MasonError::CheckFailure(_) => 5
+        }
     }
 
-    /* run command */
-    let result = cmd.output().expect(format!("Failed to execute command to archive {}", &archive_path).as_str());
+    pub fn name(&self) -> &'static str
+    {
+        match self
+        {
+            MasonError::Host(_) => "host",
+            MasonError::Config(_) => "config",
+            MasonError::MissingTool(_) => "missing_tool",
+            MasonError::ToolFailure(_) => "tool_failure",
+            MasonError::CheckFailure(_) => "check_failure"
+        }
+    }
 
-    if result.status.success() != true
+    pub fn context(&self) -> &ErrorContext
     {
-        panic!("Archiving {} failed:\n{}\n{}",
-            &archive_path, String::from_utf8(result.stdout).unwrap(), String::from_utf8(result.stderr).unwrap());
+        match self
+        {
+            MasonError::Host(c) | MasonError::Config(c) | MasonError::MissingTool(c) | MasonError::ToolFailure(c) | MasonError::CheckFailure(c) => c
+        }
     }
 
-    /* tell the linker where to find our archive, and ensure anything relying on it is rebuilt as necessary */
-    println!("cargo:rustc-link-search={}", &context.output_dir);
-    println!("cargo:rustc-link-lib=static={}", &archive_name);
-}
+    fn context_mut(&mut self) -> &mut ErrorContext
+    {
+        match self
+        {
+            MasonError::Host(c) | MasonError::Config(c) | MasonError::MissingTool(c) | MasonError::ToolFailure(c) | MasonError::CheckFailure(c) => c
+        }
+    }
 
-/* find, load, and parse a configuration file for this run */
-fn parse_config_file() -> Config
-{
-    let config_location = match search_for_config(CONFIG_FILE)
+    /* note the step of the build that failed, unless a more specific one was noted where the failure happened */
+    pub fn in_stage(mut self, stage: &str) -> MasonError
     {
-        Some(p) => p,
-        None => fatal_error(format!("Can't find configuration file {:?} in host file system", CONFIG_FILE))
-    };
+        let context = self.context_mut();
+        if context.stage.is_none() == true
+        {
+            context.stage = Some(stage.to_string());
+        }
+        self
+    }
 
-    let config_contents = match fs::read_to_string(&config_location)
+    /* note the file being processed when the failure happened, unless one already was */
+    pub fn with_file(mut self, file: &str) -> MasonError
     {
-        Ok(c) => c,
-        Err(e) => fatal_error(format!("Can't read configuration file {:?} in host file system: {}", config_location, e))
-    };
+        let context = self.context_mut();
+        if context.file.is_none() == true
+        {
+            context.file = Some(file.to_string());
+        }
+        self
+    }
 
-    match toml::from_str(config_contents.as_str())
+    /* note the tool that failed, and what it printed to stderr, if that isn't already in the message */
+    pub fn with_command(mut self, cmd: &Command, stderr: Option<&[u8]>) -> MasonError
     {
-        Ok(c) => c,
-        Err(e) => fatal_error(format!("Can't parse configuration file {:?}: {}", config_location, e))
+        let context = self.context_mut();
+        context.command = Some(std::iter::once(cmd.get_program()).chain(cmd.get_args()).map(|a| a.to_string_lossy().into_owned()).collect());
+        context.stderr = stderr.map(|s| String::from_utf8_lossy(s).trim_end().to_string()).filter(|s| s.len() > 0);
+        self
     }
 }
 
-/* starting in the current working directory, check for the presence of the
-   required config file, and if it's not there, check inside the parent.
-   continue up the host file system tree until after hitting the root node.
-   this function gives up after SEARCH_MAX iterations to avoid infinite loops.
-   => leafname = config file leafname to look for
-   <= returns filename of found config file, or None if unsuccessful */
-fn search_for_config(leafname: &str) -> Option<PathBuf>
+impl std::fmt::Display for MasonError
 {
-    let mut path = match env::current_dir()
+    /* give the message, followed by an indented line for each thing known about where the failure happened */
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
     {
-        Ok(p) => p,
-        Err(e) => fatal_error(format!("Can't get the current working directory ({})", e))
-    };
+        let context = self.context();
+        write!(f, "{}", &context.message)?;
+        if let Some(stage) = &context.stage
+        {
+            write!(f, "\n  stage: {}", stage)?;
+        }
+        if let Some(file) = &context.file
+        {
+            write!(f, "\n  file: {}", file)?;
+        }
+        if let Some(command) = &context.command
+        {
+            write!(f, "\n  command: {}", command.iter().map(|a| shell_word(a)).collect::<Vec<String>>().join(" "))?;
+        }
+        if let Some(stderr) = &context.stderr
+        {
+            write!(f, "\n  stderr:")?;
+            for line in stderr.lines()
+            {
+                write!(f, "\n    {}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
 
-    for _ in 0..SEARCH_MAX
+/* machine-readable description of a failure */
+#[derive(Serialize)]
+struct ErrorReport<'a>
+{
+    class: &'a str,
+    exit_code: i32,
+    #[serde(flatten)]
+    context: &'a ErrorContext
+}
+
+/* describe a build's plan in the file named by MASON_PLAN_JSON, if set, so that
+   test harnesses can compare it against a known-good snapshot
+   => plan = plan to describe
+      host = environment the build runs in, naming the file */
+fn write_plan(plan: &Plan, host: &HostEnv) -> Result<(), MasonError>
+{
+    if let Some(path) = host.var(PLAN_JSON_VAR)
     {
-        let mut attempt = path.clone();
-        attempt.push(leafname);
-        if attempt.exists() == true
+        if let Err(e) = fs::write(&path, serde_json::to_string_pretty(plan).unwrap())
         {
-            return Some(attempt);
+            return Err(MasonError::host(format!("Can't write build plan to {}: {}", &path, e)));
         }
+    }
+    Ok(())
+}
 
-        path = match path.parent()
+/* if asked to, write the commands a build's plan will run to build its inputs to a JSON file, as an array
+   of objects with keys step, input, and argv, for tests checking the options generated for a target
+   => build = build the plan is for
+      plan = plan resolved for the build */
+fn write_commands(build: &Build, plan: &Plan) -> Result<(), MasonError>
+{
+    if let Some(path) = build.context.host.var(COMMANDS_JSON_VAR)
+    {
+        if let Err(e) = fs::write(&path, serde_json::to_string_pretty(&build.commands(plan)).unwrap())
         {
-            Some(p) => p.to_path_buf(),
-            None => return None /* give up if we can't go any higher in the tree */
+            return Err(MasonError::host(format!("Can't write build commands to {}: {}", &path, e)));
         }
     }
+    Ok(())
+}
 
-    None
+/* how important a logged message is */
+#[derive(Clone, Copy, PartialEq)]
+enum LogLevel
+{
+    Step,    /* step of the build, reported only if cargo is verbose */
+    Info,    /* result of a command, not reported if cargo is quiet */
+    Warning, /* something worth the user's attention, always reported */
+    Error    /* failure, always reported */
 }
 
-/* parse a ConfigEntry structure and add any found file paths to the given arrays
-   => entry = ConFigEntry structure to parse
-      include_files = table to which 'include_files' string entries will be added
-      asm_dirs = table to which 'asm_dirs' string entries will be added
-*/
-fn add_file_paths_from_config(entry: &ConfigEntry, include_files: &mut HashSet<String>, asm_dirs: &mut HashSet<String>)
+impl LogLevel
 {
-    match &entry.include_files
+    pub fn name(&self) -> &'static str
     {
-        Some(files) => for file in files
+        match self
         {
-            include_files.insert(file.to_string());
-        },
-        None => ()
+            LogLevel::Step => "step",
+            LogLevel::Info => "info",
+            LogLevel::Warning => "warning",
+            LogLevel::Error => "error"
+        }
     }
+}
 
-    match &entry.asm_dirs
+/* machine-readable description of a logged message */
+#[derive(Serialize)]
+struct LogEvent<'a>
+{
+    level: &'a str,
+    event: &'a str,
+    message: &'a str
+}
+
+/* Report a message, either as a line of text or, if MASON_LOG_FORMAT is json, as a JSON event on a line
+   of its own. Steps are only reported if CARGO_TERM_VERBOSE is true, and info only if CARGO_TERM_QUIET isn't.
+   Warnings are passed to cargo to show the user, unless they're logged as JSON. These variables are read
+   from the environment of the build doing the logging, so builds in one process can each log their own way
+   => host = environment of the build the message is from
+      level = how important the message is
+      event = short name of what the message is about, eg assemble
+      msg = message to report */
+fn log_event(host: &HostEnv, level: LogLevel, event: &str, msg: String)
+{
+    let verbose = host.var("CARGO_TERM_VERBOSE") == Some("true");
+    let quiet = host.var("CARGO_TERM_QUIET") == Some("true");
+    if (level == LogLevel::Step && verbose == false) || (level == LogLevel::Info && quiet == true)
     {
-        Some(files) => for file in files
+        return;
+    }
+
+    if host.var(LOG_FORMAT_VAR) == Some("json")
+    {
+        let event = LogEvent
         {
-            asm_dirs.insert(file.to_string());
-        },
-        None => ()
+            level: level.name(),
+            event: event,
+            message: msg.as_str()
+        };
+
+        println!("{}", serde_json::to_string(&event).unwrap());
+        return;
+    }
+
+    match level
+    {
+        LogLevel::Warning => println!("cargo:warning=Mason: {}", msg),
+        LogLevel::Error => eprintln!("Mason error: {}", msg),
+        _ => println!("Mason: {}", msg)
     }
 }
 
-/* bail out with an error msg */
-fn fatal_error(msg: String) -> !
+/* report a failure passed back up to main(), and bail out with the exit code for its class
+   of failure, describing it in the file named by MASON_ERROR_JSON, if set
+   => error = the failure
+      host = environment of the process, which gives MASON_ERROR_JSON and how to log the failure */
+fn fatal_error(error: MasonError, host: &HostEnv) -> !
 {
-    println!("Mason error: {}", msg);
-    exit(1);
+    log_event(host, LogLevel::Error, error.name(), error.to_string());
+
+    if let Some(path) = host.var(ERROR_JSON_VAR)
+    {
+        let report = ErrorReport
+        {
+            class: error.name(),
+            exit_code: error.exit_code(),
+            context: error.context()
+        };
+
+        if let Err(e) = fs::write(&path, serde_json::to_string(&report).unwrap())
+        {
+            log_event(host, LogLevel::Error, "error_json", format!("Can't write error description to {}: {}", &path, e));
+        }
+    }
+
+    /* cargo may only show the user its diagnostics, so give the failure as one too, and make sure
+    everything printed is written out before exiting, which doesn't flush buffered output */
+    println!("cargo:warning=Mason error: {}", error.context().message.lines().next().unwrap_or(""));
+    eprintln!("Mason failed: {} error, exit code {}", error.name(), error.exit_code());
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+
+    exit(error.exit_code());
 }
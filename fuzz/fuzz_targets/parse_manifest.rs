@@ -0,0 +1,32 @@
+/* Fuzz target for Mason's config file parser
+ *
+ * parse_manifest() checks and merges a config file's settings without touching the file system, and must
+ * return an error, rather than panic or exit, whatever it's given. The build script isn't a library, so its
+ * source is included here to reach the parser. Each input is parsed for two targets Mason has built-in
+ * definitions for, and one it has none for.
+ * Run with: cargo fuzz run parse_manifest
+ *
+ * See README and LICENSE for usage and copying.
+ */
+
+#![no_main]
+
+#[allow(dead_code)]
+mod mason
+{
+    include!("../../build.rs");
+}
+
+/* targets to parse each input for */
+static TRIPLES: [&str; 3] = ["riscv64gc-unknown-none-elf", "aarch64-unknown-none", "x86_64-unknown-linux-gnu"];
+
+libfuzzer_sys::fuzz_target!(|data: &[u8]|
+{
+    if let Ok(contents) = std::str::from_utf8(data)
+    {
+        for triple in TRIPLES.iter()
+        {
+            let _ = mason::parse_manifest(contents, triple);
+        }
+    }
+});
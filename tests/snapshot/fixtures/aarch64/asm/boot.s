@@ -0,0 +1,5 @@
+// entry point
+.section .text.boot
+.global _start
+_start:
+    b _start
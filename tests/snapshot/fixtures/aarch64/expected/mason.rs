@@ -0,0 +1,64 @@
+/* generated by Mason: do not edit. include!() this file to access packaged binaries */
+
+pub struct MasonBlob
+{
+    start: &'static [u8; 0],
+    end: &'static [u8; 0],
+    pub hash: u64,
+    pub load_address: Option<usize>
+}
+
+impl core::ops::Deref for MasonBlob
+{
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8]
+    {
+        let start = self.start.as_ptr();
+        unsafe { core::slice::from_raw_parts(start, self.end.as_ptr() as usize - start as usize) }
+    }
+}
+
+/* blobs/2nd-stage.bin */
+unsafe extern "C"
+{
+    #[link_name = "_blob_2nd-stage_bin_start"]
+    static _blob_2nd_stage_bin_start: [u8; 0];
+    #[link_name = "_blob_2nd-stage_bin_end"]
+    static _blob_2nd_stage_bin_end: [u8; 0];
+}
+
+pub static _2ND_STAGE_BIN: MasonBlob = MasonBlob { start: unsafe { &_blob_2nd_stage_bin_start }, end: unsafe { &_blob_2nd_stage_bin_end }, hash: 0xc99e211060dfca0d, load_address: None };
+
+/* blobs/dtb.bin */
+unsafe extern "C"
+{
+    #[link_name = "_blob_dtb_bin_start"]
+    static _blob_dtb_bin_start: [u8; 0];
+    #[link_name = "_blob_dtb_bin_end"]
+    static _blob_dtb_bin_end: [u8; 0];
+}
+
+pub static DTB_BIN: MasonBlob = MasonBlob { start: unsafe { &_blob_dtb_bin_start }, end: unsafe { &_blob_dtb_bin_end }, hash: 0x714e67586ba33271, load_address: None };
+
+/* every packaged binary, by leafname */
+pub static MASON_BLOBS: [(&str, &MasonBlob); 2] =
+[
+    ("2nd-stage.bin", &_2ND_STAGE_BIN),
+    ("dtb.bin", &DTB_BIN)
+];
+
+pub struct MasonGuest
+{
+    pub name: &'static str,
+    pub image: &'static MasonBlob,
+    pub ram: u64,
+    pub vcpus: u32,
+    pub boot_args: &'static str
+}
+
+/* every guest virtual machine, by name */
+pub static MASON_GUESTS: [MasonGuest; 0] =
+[
+
+];
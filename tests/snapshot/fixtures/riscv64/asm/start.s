@@ -0,0 +1,8 @@
+# entry point: set up a stack and call into Rust
+.include "mason.inc"
+
+.section .text.start
+.global _start
+_start:
+    la sp, _stack_top
+    call kmain
@@ -0,0 +1,5 @@
+# trap vector
+.section .text
+.global trap_vector
+trap_vector:
+    j trap_vector
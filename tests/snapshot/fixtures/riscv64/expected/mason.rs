@@ -0,0 +1,76 @@
+/* generated by Mason: do not edit. include!() this file to access packaged binaries */
+
+pub struct MasonBlob
+{
+    start: &'static [u8; 0],
+    end: &'static [u8; 0],
+    pub hash: u64,
+    pub load_address: Option<usize>
+}
+
+impl core::ops::Deref for MasonBlob
+{
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8]
+    {
+        let start = self.start.as_ptr();
+        unsafe { core::slice::from_raw_parts(start, self.end.as_ptr() as usize - start as usize) }
+    }
+}
+
+/* blobs/firmware.bin */
+unsafe extern "C"
+{
+    #[link_name = "_binary_firmware_bin_start"]
+    static _binary_firmware_bin_start: [u8; 0];
+    #[link_name = "_binary_firmware_bin_end"]
+    static _binary_firmware_bin_end: [u8; 0];
+}
+
+pub static FIRMWARE_BIN: MasonBlob = MasonBlob { start: unsafe { &_binary_firmware_bin_start }, end: unsafe { &_binary_firmware_bin_end }, hash: 0xc39ec4dbbcf8c189, load_address: Some(0x80200000) };
+
+/* blobs/initrd.bin */
+unsafe extern "C"
+{
+    #[link_name = "_binary_initrd_bin_start"]
+    static _binary_initrd_bin_start: [u8; 0];
+    #[link_name = "_binary_initrd_bin_end"]
+    static _binary_initrd_bin_end: [u8; 0];
+}
+
+pub static INITRD_BIN: MasonBlob = MasonBlob { start: unsafe { &_binary_initrd_bin_start }, end: unsafe { &_binary_initrd_bin_end }, hash: 0xe3b0c44298fc1c14, load_address: None };
+
+/* blobs/kernel.bin */
+unsafe extern "C"
+{
+    #[link_name = "_binary_kernel_bin_start"]
+    static _binary_kernel_bin_start: [u8; 0];
+    #[link_name = "_binary_kernel_bin_end"]
+    static _binary_kernel_bin_end: [u8; 0];
+}
+
+pub static KERNEL_BIN: MasonBlob = MasonBlob { start: unsafe { &_binary_kernel_bin_start }, end: unsafe { &_binary_kernel_bin_end }, hash: 0x6f64c2d2f55490a1, load_address: None };
+
+/* every packaged binary, by leafname */
+pub static MASON_BLOBS: [(&str, &MasonBlob); 3] =
+[
+    ("firmware.bin", &FIRMWARE_BIN),
+    ("initrd.bin", &INITRD_BIN),
+    ("kernel.bin", &KERNEL_BIN)
+];
+
+pub struct MasonGuest
+{
+    pub name: &'static str,
+    pub image: &'static MasonBlob,
+    pub ram: u64,
+    pub vcpus: u32,
+    pub boot_args: &'static str
+}
+
+/* every guest virtual machine, by name */
+pub static MASON_GUESTS: [MasonGuest; 0] =
+[
+
+];
@@ -0,0 +1,108 @@
+#!/bin/sh
+# Snapshot tests for Mason: build each project in fixtures/ with the stub toolchain in stubs/, and
+# compare what Mason did against the known-good copies in the fixture's expected/ directory:
+#
+#   plan.json       = the plan Mason resolved, from MASON_PLAN_JSON
+#   commands.json   = the command lines it planned to run, from MASON_COMMANDS_JSON
+#   directives.txt  = the cargo directives it printed
+#   objects.txt     = the objects it put in the bundle, from mason-objects.txt
+#   mason.rs        = the Rust shim it generated, naming the symbols of each packaged binary, which
+#                     is also compiled with rustc, if it's installed
+#   mason.ld        = the linker script fragment it generated for the bundle's sections
+#   tools.txt       = the tools it actually ran, with their arguments, in sorted order
+#
+# Paths within the build's output directory and the fixture are given as $OUT_DIR and $PROJECT, so
+# the snapshots are the same wherever they're run. Usage:
+#
+#   tests/snapshot/run.sh [--update] <mason>
+#
+# where <mason> is Mason's build.rs compiled as an executable, eg the build-script-build cargo builds
+# for a crate using Mason, under target/debug/build/<crate>-<hash>/. --update rewrites the snapshots
+# from this run rather than checking them, for when a change to Mason's behaviour is intended
+
+set -u
+
+update=false
+if [ "${1:-}" = "--update" ]; then
+  update=true
+  shift
+fi
+
+if [ $# -ne 1 ] || [ ! -x "$1" ]; then
+  echo "Usage: $0 [--update] <mason executable>" >&2
+  exit 2
+fi
+
+mason=$(cd "$(dirname "$1")" && pwd)/$(basename "$1")
+here=$(cd "$(dirname "$0")" && pwd)
+scratch=$(mktemp -d)
+trap 'rm -rf "$scratch"' EXIT
+
+failures=0
+for fixture in "$here"/fixtures/*/; do
+  fixture=${fixture%/}
+  name=$(basename "$fixture")
+  project="$scratch/$name/project"
+  out="$scratch/$name/out"
+  results="$scratch/$name/results"
+  mkdir -p "$project" "$out" "$results"
+  cp -R "$fixture"/. "$project"
+  rm -rf "$project/expected"
+
+  # run Mason as cargo would, with nothing from this shell's environment but the stub tools and a basic PATH
+  (cd "$project" && env -i \
+    PATH="$here/stubs:/usr/bin:/bin" \
+    TARGET="$(cat "$fixture/target.txt")" \
+    OUT_DIR="$out" \
+    NUM_JOBS=2 \
+    STUB_LOG="$results/tools.log" \
+    MASON_PLAN_JSON="$results/plan.json" \
+    MASON_COMMANDS_JSON="$results/commands.json" \
+    "$mason" > "$results/stdout" 2> "$results/stderr")
+  status=$?
+
+  if [ $status -ne 0 ]; then
+    echo "FAIL $name: Mason exited with status $status:" >&2
+    cat "$results/stderr" >&2
+    failures=$((failures + 1))
+    continue
+  fi
+
+  # give paths relative to where the fixture was built, and tools in the order of their arguments,
+  # as parallel assemblers may run in any order
+  normalize() { sed -e "s|$out|\$OUT_DIR|g" -e "s|$project|\$PROJECT|g"; }
+  normalize < "$results/plan.json" > "$results/plan.json.n"
+  normalize < "$results/commands.json" > "$results/commands.json.n"
+  grep '^cargo:' "$results/stdout" | normalize > "$results/directives.txt.n"
+  normalize < "$out/mason-objects.txt" > "$results/objects.txt.n"
+  normalize < "$out/mason.rs" > "$results/mason.rs.n"
+  normalize < "$out/mason.ld" > "$results/mason.ld.n"
+  normalize < "$results/tools.log" | LC_ALL=C sort > "$results/tools.txt.n"
+
+  for snapshot in plan.json commands.json directives.txt objects.txt mason.rs mason.ld tools.txt; do
+    expected="$fixture/expected/$snapshot"
+    if [ $update = true ]; then
+      mkdir -p "$fixture/expected"
+      cp "$results/$snapshot.n" "$expected"
+    elif ! diff -u "$expected" "$results/$snapshot.n" > "$results/$snapshot.diff" 2>&1; then
+      echo "FAIL $name: $snapshot differs from its snapshot:" >&2
+      cat "$results/$snapshot.diff" >&2
+      failures=$((failures + 1))
+    fi
+  done
+
+  # the generated Rust must also compile, whatever the binaries are named
+  if command -v rustc > /dev/null 2>&1 && ! rustc --edition 2021 --crate-type lib --crate-name mason_shim \
+      -o "$results/libmason_shim.rlib" "$out/mason.rs" > "$results/rustc.log" 2>&1; then
+    echo "FAIL $name: mason.rs doesn't compile:" >&2
+    cat "$results/rustc.log" >&2
+    failures=$((failures + 1))
+  fi
+
+  [ $update = true ] && echo "updated $name" || echo "checked $name"
+done
+
+if [ $failures -gt 0 ]; then
+  echo "$failures snapshot(s) failed" >&2
+  exit 1
+fi